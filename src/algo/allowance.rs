@@ -0,0 +1,99 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::machine::MachineProfile;
+use crate::utils::wire::{WireKind, WireMessage};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone)]
+pub struct AllowanceParameters {
+    //extra stock on the first straight, it is added after the straight is made as long as the clamp die
+    pub grip_len: f64,
+    //extra stock on the last straight, cut off after bending
+    pub trim_len: f64,
+    pub min_end_straight: f64,
+}
+impl AllowanceParameters {
+    pub fn default() -> Self {
+        Self {
+            grip_len: 0.0,
+            trim_len: 0.0,
+            min_end_straight: 0.0,
+        }
+    }
+    //[grip_len, trim_len, min_end_straight]
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => self.grip_len = v.clone() as f64,
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.trim_len = v.clone() as f64,
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.min_end_straight = v.clone() as f64,
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        Vec::from([self.grip_len as f32, self.trim_len as f32, self.min_end_straight as f32])
+    }
+}
+
+//Length added to the first and the last straight of the program
+#[derive(Debug, Clone)]
+pub struct Allowance {
+    pub start: f64,
+    pub end: f64,
+}
+impl Allowance {
+    pub fn default() -> Self {
+        Self { start: 0.0, end: 0.0 }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.start <= 0.0 && self.end <= 0.0
+    }
+    //The same stock seen from the other end of the tube
+    pub fn reversed(&self) -> Allowance {
+        Allowance { start: self.end, end: self.start }
+    }
+    //Header fields 1 start, 2 end
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Allowance);
+        msg.header.push(1, self.start);
+        msg.header.push(2, self.end);
+        msg
+    }
+}
+impl Display for Allowance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "grip +{:.2} trim +{:.2}", self.start, self.end)
+    }
+}
+
+pub fn apply_allowances(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile, params: &AllowanceParameters) -> (Vec<LRACLR>, Allowance) {
+    let mut ret = lraclr_arr.clone();
+    let mut allowance = Allowance::default();
+    if (ret.is_empty()) {
+        return (ret, allowance);
+    }
+    let first_l = ret[0].l;
+    let start_l = first_l.max(machine.min_clamp_len) + params.grip_len.max(0.0);
+    allowance.start = start_l - first_l;
+    ret[0].l = start_l;
+    let last = ret.len() - 1;
+    let last_l = ret[last].l;
+    let end_l = last_l.max(params.min_end_straight) + params.trim_len.max(0.0);
+    allowance.end = end_l - last_l;
+    ret[last].l = end_l;
+    (ret, allowance)
+}
+
+pub fn strip_allowances(lraclr_arr: &Vec<LRACLR>, allowance: &Allowance) -> Vec<LRACLR> {
+    let mut ret = lraclr_arr.clone();
+    if (!ret.is_empty()) {
+        let last = ret.len() - 1;
+        ret[last].l = (ret[last].l - allowance.end).max(0.0);
+        ret[0].l = (ret[0].l - allowance.start).max(0.0);
+    }
+    ret
+}
@@ -0,0 +1,254 @@
+use crate::algo::cnc::{cnc_to_poly, cnc_to_poly_animate, LRACLR};
+use crate::algo::mesh_export::{mesh_groups, GlbWriter, MeshGroup, GLTF_BEND, GLTF_STRAIGHT, GLTF_TOOL};
+use crate::algo::{BendToro, MainCylinder, P_UP};
+use crate::device::graphics::{AnimState, BendParameters};
+use crate::utils::dorn::{dorn_cylinder, dorn_pose, DORN_PARK_SCALE};
+use cgmath::num_traits::abs;
+use cgmath::{Deg, InnerSpace, Matrix3, Quaternion, Rad, Rotation, Rotation3};
+use log::warn;
+use std::ops::Sub;
+use truck_base::cgmath64::Vector3;
+
+//Every segment is one node over a shared mesh, a frame only adds its pose to the animation
+const ANIM_FPS: f64 = 10.0;
+const MAX_ANIM_FRAMES: usize = 300;
+//A bend is drawn by slices of its final mesh so it can grow without a mesh of its own per frame
+const ANIM_SLICE_DEG: f64 = 5.0;
+
+//Seconds the bend sequence takes with the animation speeds
+pub fn sequence_duration(lraclr_arr: &Vec<LRACLR>, bend_params: &BendParameters) -> f64 {
+    let time = |v: f64, speed: f64| -> f64 { if (speed > 0.0) { abs(v) / speed } else { 0.0 } };
+    lraclr_arr.iter().map(|lra| time(lra.l, bend_params.stright_speed) + time(lra.r, bend_params.rotate_speed) + time(lra.a, bend_params.angle_speed)).sum()
+}
+
+//Node of one segment with its pose at every frame, hidden frames have a zero scale
+struct AnimTrack {
+    node: usize,
+    is_shown: bool,
+    translations: Vec<[f32; 3]>,
+    rotations: Vec<[f32; 4]>,
+    scales: Vec<[f32; 3]>,
+}
+impl AnimTrack {
+    fn new(node: usize) -> Self {
+        Self { node: node, is_shown: false, translations: vec![], rotations: vec![], scales: vec![] }
+    }
+    //Starts a frame with the segment hidden where it was
+    fn hide(&mut self) {
+        let translation = self.translations.last().cloned().unwrap_or([0.0, 0.0, 0.0]);
+        let rotation = self.rotations.last().cloned().unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        self.translations.push(translation);
+        self.rotations.push(rotation);
+        self.scales.push([0.0, 0.0, 0.0]);
+    }
+    //Poses the segment in the current frame, the quaternion keeps the sign of the previous frame so it is not turned the long way.
+    //The frames before it is shown first take its first pose, so it does not fly in from the origin
+    fn show(&mut self, translation: Vector3, rotation: Quaternion<f64>, scale: [f32; 3]) {
+        let i = self.scales.len() - 1;
+        let mut q = [rotation.v.x as f32, rotation.v.y as f32, rotation.v.z as f32, rotation.s as f32];
+        if (self.is_shown && i > 0) {
+            let prev = self.rotations[i - 1];
+            if (prev[0] * q[0] + prev[1] * q[1] + prev[2] * q[2] + prev[3] * q[3] < 0.0) {
+                q = [-q[0], -q[1], -q[2], -q[3]];
+            }
+        }
+        let t = [translation.x as f32, translation.y as f32, translation.z as f32];
+        if (!self.is_shown) {
+            self.translations.iter_mut().for_each(|v| *v = t);
+            self.rotations.iter_mut().for_each(|v| *v = q);
+            self.is_shown = true;
+        }
+        self.translations[i] = t;
+        self.rotations[i] = q;
+        self.scales[i] = scale;
+    }
+}
+
+//Slices of one bend, all of them are instances of the same mesh
+struct BendTrack {
+    indx: usize,
+    slice_angle: f64,
+    slices: Vec<AnimTrack>,
+}
+
+//glTF 2.0 binary of the bend sequence. A straight is a unit tube scaled to its length, a bend is a row of
+//slices shown while the bend angle reaches them and the dorn is moved and scaled along the same key times
+pub fn bending_to_glb(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, bend_params: &BendParameters, name: &str) -> Vec<u8> {
+    let mut w = GlbWriter::new(name);
+    if (lraclr_arr.is_empty()) {
+        return w.to_bytes();
+    }
+    let duration = sequence_duration(lraclr_arr, bend_params);
+    let dt = (1.0 / ANIM_FPS).max(duration / MAX_ANIM_FRAMES as f64);
+
+    let dorn = mesh_groups(&vec![dorn_cylinder()], &vec![]);
+    let dorn_prims: Vec<(usize, usize, usize)> = dorn
+        .iter()
+        .map(|g| {
+            let (position, normal) = w.add_group(g);
+            (position, normal, GLTF_TOOL)
+        })
+        .collect();
+    let dorn_mesh = w.add_mesh("dorn", &dorn_prims);
+    let dorn_node = w.add_node("dorn", Some(dorn_mesh), None, "");
+
+    //the head left past the die after a bend is the straight of the next row, so there is one more straight than rows
+    let mut straight_meshes: Vec<(f64, usize)> = vec![];
+    let mut straights: Vec<AnimTrack> = vec![];
+    (0..=lraclr_arr.len()).for_each(|i| {
+        let pipe_radius = lraclr_arr[i.min(lraclr_arr.len() - 1)].pipe_radius;
+        let mesh = straight_mesh(&mut w, &mut straight_meshes, pipe_radius);
+        straights.push(AnimTrack::new(w.add_node(format!("straight {}", i).as_str(), Some(mesh), Some([0.0, 0.0, 0.0]), "")));
+    });
+    let stock_mesh = straight_mesh(&mut w, &mut straight_meshes, lraclr_arr[lraclr_arr.len() - 1].pipe_radius);
+    let mut stock = AnimTrack::new(w.add_node("stock", Some(stock_mesh), Some([0.0, 0.0, 0.0]), ""));
+    let mut bends: Vec<BendTrack> = vec![];
+    lraclr_arr.iter().enumerate().filter(|(_i, lra)| lra.clr > 0.0 && lra.a > 0.0).for_each(|(i, lra)| {
+        let count = (lra.a / ANIM_SLICE_DEG).ceil().max(1.0) as usize;
+        let slice_angle = lra.a / count as f64;
+        let mesh = bend_slice_mesh(&mut w, lra, slice_angle, format!("bend {}", i).as_str());
+        let slices: Vec<AnimTrack> = (0..count)
+            .map(|s| AnimTrack::new(w.add_node(format!("bend {} slice {}", i, s).as_str(), Some(mesh), Some([0.0, 0.0, 0.0]), "")))
+            .collect();
+        bends.push(BendTrack { indx: i, slice_angle: slice_angle, slices: slices });
+    });
+
+    let mut times: Vec<f32> = vec![];
+    let mut dorn_scales: Vec<[f32; 3]> = vec![];
+    let mut dorn_moves: Vec<[f32; 3]> = vec![];
+    let mut dorn_scale = DORN_PARK_SCALE;
+    let mut anim_state: AnimState = AnimState::default();
+    anim_state.opcode = 0;
+    let mut is_done = false;
+    //every op ends one frame past its value, a zero speed would never end
+    let max_frames = MAX_ANIM_FRAMES + lraclr_arr.len() * 3;
+    while (!is_done && times.len() < max_frames) {
+        let (cyls, tors, next_stage) = cnc_to_poly_animate(lraclr_arr, &anim_state, v_up_orign, dt, bend_params);
+        if (next_stage.opcode == 4) {
+            is_done = true;
+        } else {
+            straights.iter_mut().for_each(|t| t.hide());
+            stock.hide();
+            bends.iter_mut().for_each(|b| b.slices.iter_mut().for_each(|s| s.hide()));
+            pose_frame(lraclr_arr, &cyls, &tors, next_stage.stright_len > 0.0, &mut straights, &mut stock, &mut bends);
+            times.push((dt * times.len() as f64) as f32);
+            let (scale, translation) = dorn_pose(&next_stage, v_up_orign, &dorn_scale);
+            dorn_scale = scale;
+            dorn_scales.push([scale.x as f32, scale.y as f32, scale.z as f32]);
+            dorn_moves.push([translation.x as f32, translation.y as f32, translation.z as f32]);
+            anim_state = next_stage;
+        }
+    }
+    if (!is_done) {
+        warn!("ANIM TRUNCATED AFTER {} FRAMES AT OP {}", times.len(), anim_state.op_counter);
+    }
+    if (!times.is_empty()) {
+        let input = w.add_scalar(&times);
+        straights.iter().chain(std::iter::once(&stock)).for_each(|t| add_track(&mut w, t, input, false));
+        bends.iter().for_each(|b| b.slices.iter().for_each(|s| add_track(&mut w, s, input, true)));
+        let scales = w.add_vec3(&dorn_scales, false, false);
+        let moves = w.add_vec3(&dorn_moves, false, false);
+        w.add_channel(dorn_node, "scale", input, scales, true);
+        w.add_channel(dorn_node, "translation", input, moves, true);
+    }
+    w.to_bytes()
+}
+
+//The animation draws the reversed program of the stage, so its k-th straight is the row n-1-k of the program and
+//its k-th bend the k-th of the rows below n-1 that bend. The stock behind the head is the last straight
+fn pose_frame(
+    lraclr_arr: &Vec<LRACLR>,
+    cyls: &Vec<MainCylinder>,
+    tors: &Vec<BendToro>,
+    is_with_stock: bool,
+    straights: &mut Vec<AnimTrack>,
+    stock: &mut AnimTrack,
+    bends: &mut Vec<BendTrack>,
+) {
+    let n = if (is_with_stock && !cyls.is_empty()) { cyls.len() - 1 } else { cyls.len() };
+    cyls.iter().enumerate().for_each(|(k, c)| {
+        let track = if (k < n) { straights.get_mut(n - 1 - k) } else { Some(&mut *stock) };
+        match track {
+            None => {}
+            Some(t) => {
+                let up = c.ca.radius_dir.normalize();
+                let dir = c.ca.dir.normalize();
+                let rotation = Quaternion::from(Matrix3::from_cols(dir, up.cross(dir), up));
+                t.show(Vector3::new(c.ca.loc.x, c.ca.loc.y, c.ca.loc.z), rotation, [c.h as f32, 1.0, 1.0]);
+            }
+        }
+    });
+    let bent_rows: Vec<usize> = (0..n.saturating_sub(1).min(lraclr_arr.len())).rev().filter(|j| lraclr_arr[*j].clr > 0.0).collect();
+    if (bent_rows.len() != tors.len()) {
+        warn!("ANIM {} BENDS IN A FRAME OF {} ROWS", tors.len(), n);
+    }
+    tors.iter().zip(bent_rows.iter()).for_each(|(t, j)| match bends.iter_mut().find(|b| b.indx == *j) {
+        None => {}
+        Some(bend) => {
+            let up = t.bend_plane_norm.normalize();
+            let dir = t.ca.dir.normalize();
+            let frame = Quaternion::from(Matrix3::from_cols(dir, up.cross(dir), up));
+            let angle = Deg::from(t.angle()).0;
+            let step = bend.slice_angle;
+            let visible = ((angle / step) - 1e-6).ceil().max(0.0) as usize;
+            let sp = t.ca.loc.sub(t.bend_center_point);
+            bend.slices.iter_mut().take(visible).enumerate().for_each(|(s, slice)| {
+                //the last slice ends at the bend angle, it overlaps the one before it
+                let at = (s as f64 * step).min(angle - step).max(0.0);
+                let turn: Quaternion<f64> = Rotation3::from_axis_angle(up, Rad::from(Deg(at)));
+                let p = t.bend_center_point + turn.rotate_vector(sp);
+                slice.show(Vector3::new(p.x, p.y, p.z), turn * frame, [1.0, 1.0, 1.0]);
+            });
+        }
+    });
+}
+
+//Straights of one radius share a unit tube along +X
+fn straight_mesh(w: &mut GlbWriter, meshes: &mut Vec<(f64, usize)>, pipe_radius: f64) -> usize {
+    match meshes.iter().find(|(r, _m)| abs(*r - pipe_radius) < 1e-9) {
+        Some((_r, m)) => *m,
+        None => {
+            let mut unit = LRACLR::default();
+            unit.l = 1.0;
+            unit.pipe_radius = pipe_radius;
+            let (cyls, tors) = cnc_to_poly(&vec![unit], &P_UP);
+            let mesh = add_segment_mesh(w, &mesh_groups(&cyls, &tors), false, "straight");
+            meshes.push((pipe_radius, mesh));
+            mesh
+        }
+    }
+}
+
+//Slice of the bend starting at the origin along +X and bending around +Z
+fn bend_slice_mesh(w: &mut GlbWriter, lra: &LRACLR, slice_angle: f64, name: &str) -> usize {
+    let mut slice = lra.clone();
+    slice.l = 0.0;
+    slice.r = 0.0;
+    slice.a = slice_angle;
+    let (cyls, tors) = cnc_to_poly(&vec![slice], &P_UP);
+    add_segment_mesh(w, &mesh_groups(&cyls, &tors), true, name)
+}
+
+fn add_segment_mesh(w: &mut GlbWriter, groups: &Vec<MeshGroup>, is_bend: bool, name: &str) -> usize {
+    let mut merged = MeshGroup { id: 0, name: String::new(), is_bend: is_bend, positions: vec![], normals: vec![] };
+    groups.iter().filter(|g| g.is_bend == is_bend).for_each(|g| {
+        merged.positions.extend_from_slice(&g.positions);
+        merged.normals.extend_from_slice(&g.normals);
+    });
+    let mut prims: Vec<(usize, usize, usize)> = vec![];
+    if (!merged.positions.is_empty()) {
+        let (position, normal) = w.add_group(&merged);
+        prims.push((position, normal, if (is_bend) { GLTF_BEND } else { GLTF_STRAIGHT }));
+    }
+    w.add_mesh(name, &prims)
+}
+
+//Slices only switch on and off, a straight grows with the feed
+fn add_track(w: &mut GlbWriter, t: &AnimTrack, input: usize, is_step_scale: bool) {
+    let translations = w.add_vec3(&t.translations, false, false);
+    let rotations = w.add_vec4(&t.rotations);
+    let scales = w.add_vec3(&t.scales, false, false);
+    w.add_channel(t.node, "translation", input, translations, false);
+    w.add_channel(t.node, "rotation", input, rotations, false);
+    w.add_channel(t.node, "scale", input, scales, is_step_scale);
+}
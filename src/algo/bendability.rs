@@ -0,0 +1,211 @@
+use crate::algo::cnc::LRACLR;
+use std::fmt::{Display, Formatter};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+const DEFAULT_WALL: f64 = 1.5;
+//Flattening of an unsupported bend in percent, roughly proportional to OD^2 / (CLR * wall)
+const OVALITY_FACTOR: f64 = 0.5;
+const WRINKLE_LIMIT: f64 = 60.0;
+const WIPER_LIMIT: f64 = 10.0;
+const MIN_D_OF_BEND: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct TubeMaterial {
+    pub name: String,
+    pub max_thinning: f64,
+    pub max_ovality: f64,
+}
+impl TubeMaterial {
+    pub fn presets() -> Vec<TubeMaterial> {
+        vec![
+            TubeMaterial { name: "steel".to_string(), max_thinning: 25.0, max_ovality: 8.0 },
+            TubeMaterial { name: "stainless".to_string(), max_thinning: 30.0, max_ovality: 6.0 },
+            TubeMaterial { name: "aluminium".to_string(), max_thinning: 18.0, max_ovality: 8.0 },
+            TubeMaterial { name: "copper".to_string(), max_thinning: 30.0, max_ovality: 10.0 },
+            TubeMaterial { name: "titanium".to_string(), max_thinning: 15.0, max_ovality: 5.0 },
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TubeStock {
+    pub material: TubeMaterial,
+    pub wall: f64,
+}
+impl TubeStock {
+    pub fn default() -> Self {
+        Self {
+            material: TubeMaterial::presets()[0].clone(),
+            wall: DEFAULT_WALL,
+        }
+    }
+    //[material preset, wall, max_thinning, max_ovality] limits override the preset when present
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => match TubeMaterial::presets().get(*v as usize) {
+                None => {}
+                Some(m) => self.material = m.clone(),
+            },
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.wall = v.clone() as f64,
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.material.max_thinning = v.clone() as f64,
+        }
+        match vec.get(3) {
+            None => {}
+            Some(v) => self.material.max_ovality = v.clone() as f64,
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        let indx = TubeMaterial::presets().iter().position(|m| m.name == self.material.name).unwrap_or(0);
+        Vec::from([indx as f32, self.wall as f32, self.material.max_thinning as f32, self.material.max_ovality as f32])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MandrelType {
+    None = 0,
+    Plug = 1,
+    Ball1 = 2,
+    Ball2 = 3,
+    Ball3 = 4,
+    Ball4 = 5,
+}
+impl MandrelType {
+    //Selection by bending difficulty, wall factor over D of bend, after the usual mandrel charts
+    pub fn by_difficulty(difficulty: f64) -> MandrelType {
+        if (difficulty < 5.0) {
+            MandrelType::None
+        } else if (difficulty < 10.0) {
+            MandrelType::Plug
+        } else if (difficulty < 20.0) {
+            MandrelType::Ball1
+        } else if (difficulty < 35.0) {
+            MandrelType::Ball2
+        } else if (difficulty < 50.0) {
+            MandrelType::Ball3
+        } else {
+            MandrelType::Ball4
+        }
+    }
+    //A supporting mandrel keeps most of the cross section round
+    pub fn ovality_reduction(&self) -> f64 {
+        match self {
+            MandrelType::None => 1.0,
+            MandrelType::Plug => 0.5,
+            MandrelType::Ball1 => 0.35,
+            MandrelType::Ball2 => 0.25,
+            MandrelType::Ball3 => 0.2,
+            MandrelType::Ball4 => 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BendRisk {
+    Thinning = 1,
+    Ovality = 2,
+    Wrinkling = 4,
+}
+
+#[derive(Debug, Clone)]
+pub struct BendAssessment {
+    pub id1: i32,
+    pub id2: i32,
+    pub od: f64,
+    pub wall: f64,
+    pub clr: f64,
+    pub wall_factor: f64,
+    pub d_of_bend: f64,
+    pub thinning: f64,
+    pub ovality: f64,
+    pub mandrel: MandrelType,
+    pub is_wiper_needed: bool,
+    pub risks: Vec<BendRisk>,
+}
+impl BendAssessment {
+    pub fn is_risky(&self) -> bool {
+        !self.risks.is_empty()
+    }
+    pub fn risk_mask(&self) -> i32 {
+        self.risks.iter().fold(0, |acc, r| acc | *r as i32)
+    }
+    //Row fields 1 id1, 2 id2, 3 wall_factor, 4 d_of_bend, 5 thinning, 6 ovality, 7 mandrel, 8 wiper, 9 risk_mask
+    pub fn to_wire(assessments: &Vec<BendAssessment>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Bendability);
+        assessments.iter().for_each(|a| {
+            let mut row = WireRecord::new();
+            row.push(1, a.id1 as f64);
+            row.push(2, a.id2 as f64);
+            row.push(3, a.wall_factor);
+            row.push(4, a.d_of_bend);
+            row.push(5, a.thinning);
+            row.push(6, a.ovality);
+            row.push(7, a.mandrel as i32 as f64);
+            row.push_bool(8, a.is_wiper_needed);
+            row.push(9, a.risk_mask() as f64);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for BendAssessment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} WF {:.1} D {:.2} thinning {:.1}% ovality {:.1}% mandrel {:?} wiper {} risks {:?}",
+            self.id1, self.id2, self.wall_factor, self.d_of_bend, self.thinning, self.ovality, self.mandrel, self.is_wiper_needed, self.risks
+        )
+    }
+}
+
+pub fn assess_bend(lra: &LRACLR, stock: &TubeStock) -> BendAssessment {
+    let od = lra.pipe_radius * 2.0;
+    let wall = stock.wall.max(f64::EPSILON);
+    let wall_factor = od / wall;
+    let d_of_bend = lra.clr / od;
+    let difficulty = wall_factor / d_of_bend.max(f64::EPSILON);
+    let mandrel = MandrelType::by_difficulty(difficulty);
+    //outer wall strain with the neutral axis on the centerline
+    let thinning = 100.0 / (2.0 * d_of_bend + 1.0);
+    let ovality = OVALITY_FACTOR * od * od / (lra.clr * wall) * mandrel.ovality_reduction();
+    let mut risks: Vec<BendRisk> = vec![];
+    if (thinning > stock.material.max_thinning) {
+        risks.push(BendRisk::Thinning);
+    }
+    if (ovality > stock.material.max_ovality) {
+        risks.push(BendRisk::Ovality);
+    }
+    if (difficulty > WRINKLE_LIMIT || d_of_bend < MIN_D_OF_BEND) {
+        risks.push(BendRisk::Wrinkling);
+    }
+    BendAssessment {
+        id1: lra.id1,
+        id2: lra.id2,
+        od: od,
+        wall: wall,
+        clr: lra.clr,
+        wall_factor: wall_factor,
+        d_of_bend: d_of_bend,
+        thinning: thinning,
+        ovality: ovality,
+        mandrel: mandrel,
+        is_wiper_needed: difficulty >= WIPER_LIMIT,
+        risks: risks,
+    }
+}
+
+pub fn assess_lraclr(lraclr_arr: &Vec<LRACLR>, stock: &TubeStock) -> Vec<BendAssessment> {
+    let mut ret: Vec<BendAssessment> = vec![];
+    lraclr_arr.iter().for_each(|lra| {
+        if (lra.clr > 0.0 && lra.pipe_radius > 0.0) {
+            ret.push(assess_bend(lra, stock));
+        }
+    });
+    ret
+}
@@ -1,6 +1,8 @@
+use crate::algo::output::wrap_rotation;
 use crate::algo::{export_to_pt_str, perpendicular_rand_dir, project_point_to_vec, round_by_dec, BendToro, MainCircle, MainCylinder, DIVIDER, EXTRA_LEN_CALC, EXTRA_R_CALC, P_FORWARD, P_FORWARD_REVERSE, P_RIGHT, P_UP, ROT_DIR_CCW, TOLE};
 use crate::device::graphics::{AnimState, BendParameters};
 use crate::device::{MeshVertex, StepVertexBuffer};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord, P_A, P_CLR, P_ID1, P_ID2, P_L, P_LT, P_PIPE_RADIUS, P_R};
 use cgmath::num_traits::{abs, signum};
 use cgmath::{
     Basis3, Deg, InnerSpace, Matrix, Matrix3, MetricSpace, Rad, Rotation, Rotation3, SquareMatrix,
@@ -68,6 +70,105 @@ impl LRACLR {
         });
         arr
     }
+    pub fn to_wire(cmnd: &Vec<LRACLR>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Program);
+        cmnd.iter().for_each(|cmd| {
+            let mut row = WireRecord::new();
+            row.push(P_ID1, cmd.id1 as f64);
+            row.push(P_ID2, cmd.id2 as f64);
+            row.push(P_L, cmd.l);
+            row.push(P_LT, cmd.lt);
+            row.push(P_R, cmd.r);
+            row.push(P_A, cmd.a);
+            row.push(P_CLR, cmd.clr);
+            row.push(P_PIPE_RADIUS, cmd.pipe_radius);
+            msg.rows.push(row);
+        });
+        msg
+    }
+    //Missing arc lengths are taken from the angle and clr, as the operator usually leaves them out
+    pub fn from_wire(msg: &WireMessage) -> Vec<LRACLR> {
+        let mut ret: Vec<LRACLR> = vec![];
+        msg.rows.iter().for_each(|row| {
+            let a = abs(row.get_or(P_A, 0.0));
+            let clr = abs(row.get_or(P_CLR, 0.0));
+            ret.push(LRACLR {
+                id1: row.get_or(P_ID1, (ret.len() * 2) as f64).round() as i32,
+                id2: row.get_or(P_ID2, (ret.len() * 2 + 1) as f64).round() as i32,
+                l: abs(row.get_or(P_L, 0.0)),
+                lt: row.get(P_LT).unwrap_or(Rad::from(Deg(a)).0 * clr),
+                r: row.get_or(P_R, 0.0),
+                a: a,
+                clr: clr,
+                pipe_radius: abs(row.get_or(P_PIPE_RADIUS, 0.0)),
+            });
+        });
+        ret
+    }
+    pub fn to_csv(cmnd: &Vec<LRACLR>) -> String {
+        let mut ret = String::from("id1,id2,l,lt,r,a,clr,pipe_radius\n");
+        cmnd.iter().for_each(|cmd| {
+            ret.push_str(format!("{},{},{},{},{},{},{},{}\n", cmd.id1, cmd.id2, cmd.l, cmd.lt, cmd.r, cmd.a, cmd.clr, cmd.pipe_radius).as_str());
+        });
+        ret
+    }
+    //Same columns as to_csv, the header is optional and an empty lt is taken from the angle and clr like from_wire
+    pub fn from_csv(csv: &str) -> Result<Vec<LRACLR>, String> {
+        let mut ret: Vec<LRACLR> = vec![];
+        for (i, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if (line.is_empty() || line.starts_with("id1")) {
+                continue;
+            }
+            let mut v: Vec<Option<f64>> = vec![];
+            for cell in line.split(|c| c == ',' || c == ';') {
+                let cell = cell.trim();
+                if (cell.is_empty()) {
+                    v.push(None);
+                } else {
+                    match cell.parse::<f64>() {
+                        Ok(n) => v.push(Some(n)),
+                        Err(_) => return Err(format!("line {}: {} is not a number", i + 1, cell)),
+                    }
+                }
+            }
+            if (v.len() < 7) {
+                return Err(format!("line {}: {} columns, 7 or 8 expected", i + 1, v.len()));
+            }
+            let a = abs(v[5].unwrap_or(0.0));
+            let clr = abs(v[6].unwrap_or(0.0));
+            ret.push(LRACLR {
+                id1: v[0].unwrap_or((ret.len() * 2) as f64).round() as i32,
+                id2: v[1].unwrap_or((ret.len() * 2 + 1) as f64).round() as i32,
+                l: abs(v[2].unwrap_or(0.0)),
+                lt: v[3].unwrap_or(Rad::from(Deg(a)).0 * clr),
+                r: v[4].unwrap_or(0.0),
+                a: a,
+                clr: clr,
+                pipe_radius: abs(v.get(7).cloned().flatten().unwrap_or(0.0)),
+            });
+        }
+        Ok(ret)
+    }
+    pub fn to_json(cmnd: &Vec<LRACLR>, part_number: &str) -> String {
+        let (tl, outd) = LRACLR::total_len_out_d(cmnd);
+        let rows: Vec<String> = cmnd
+            .iter()
+            .map(|cmd| {
+                format!(
+                    "{{\"id1\":{},\"id2\":{},\"l\":{},\"lt\":{},\"r\":{},\"a\":{},\"clr\":{}}}",
+                    cmd.id1, cmd.id2, cmd.l, cmd.lt, cmd.r, cmd.a, cmd.clr
+                )
+            })
+            .collect();
+        format!(
+            "{{\"part_number\":\"{}\",\"od\":{},\"cut_length\":{},\"program\":[{}]}}\n",
+            part_number.replace('\\', "\\\\").replace('"', "\\\""),
+            outd * 2.0,
+            tl,
+            rows.join(",")
+        )
+    }
 
     pub fn total_len_out_d(cmnd: &Vec<LRACLR>) -> (f64, f64) {
         let mut tl: f64 = 0.0;
@@ -171,6 +272,9 @@ fn tot_pipe_len(lraclr_arr: &Vec<LRACLR>) -> f64 {
 }
 pub fn reverse_lraclr(lraclr: &Vec<LRACLR>) -> Vec<LRACLR> {
     let mut ret: Vec<LRACLR> = vec![];
+    if (lraclr.is_empty()) {
+        return ret;
+    }
     let mut ops: Vec<LRACLR> = lraclr.clone();
     ops.reverse();
     let mut is_first = false;
@@ -275,6 +379,88 @@ pub fn cnc_to_poly(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, ) -> (Vec<Mai
     all_to_one(&cyls, &tors);
     (cyls, tors)
 }
+//Start point, the intersection of the straight lines around every bend and the end point,
+//the same frame walk as cnc_to_poly. A half turn has no intersection, its start of bend is used
+pub fn intersection_points(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3) -> Vec<Point3> {
+    let mut sp: Point3 = Point3::new(0.0, 0.0, 0.0);
+    let mut v_up: Vector3 = v_up_orign.clone();
+    let mut v_frw = P_FORWARD;
+    let mut ret: Vec<Point3> = vec![sp.clone()];
+    lraclr_arr.iter().for_each(|lracl| {
+        let tp = sp + v_frw.mul(lracl.l);
+        if (lracl.clr > 0.0) {
+            let x_rotation = Rad::from(Deg(lracl.r * ROT_DIR_CCW));
+            let rotation_x: Basis3<f64> = Rotation3::from_axis_angle(v_frw, x_rotation);
+            v_up = rotation_x.rotate_vector(v_up);
+            let rotation_bend: Basis3<f64> = Rotation3::from_axis_angle(v_up, Rad::from(Deg(lracl.a)));
+            let v_frw_s = v_frw.clone();
+            v_frw = rotation_bend.rotate_vector(v_frw);
+            if (lracl.a < 179.9) {
+                let tangent = lracl.clr * Rad::from(Deg(lracl.a / 2.0)).0.tan();
+                let ip = tp + v_frw_s.mul(tangent);
+                ret.push(ip);
+                sp = ip + v_frw.mul(tangent);
+            } else {
+                ret.push(tp);
+                sp = tp + v_up.cross(v_frw_s).normalize().mul(lracl.clr * 2.0);
+            }
+        } else {
+            ret.push(tp);
+            sp = tp;
+        }
+    });
+    ret
+}
+//Program the animation draws for a stage, reversed so the tube starts at the bend head.
+//The stock still behind the head is not part of it
+pub fn anim_lraclr(lraclr_arr: &Vec<LRACLR>, anim_state: &AnimState) -> Vec<LRACLR> {
+    let id = anim_state.id;
+    let indx = if (id.is_odd()) { ((id - 1) / 2) as usize } else { (id / 2) as usize };
+    if (lraclr_arr.is_empty() || indx >= lraclr_arr.len()) {
+        return vec![];
+    }
+    let mut anim_lra: Vec<LRACLR> = lraclr_arr.iter().take(indx + 1).cloned().collect();
+    if (id.is_odd()) {
+        let mut dumb: LRACLR = lraclr_arr.last().unwrap().clone();
+        dumb.l = 0.5;
+        anim_lra.push(dumb);
+        let mut reversed: Vec<LRACLR> = reverse_lraclr(&anim_lra);
+        reversed[0].a = anim_state.value;
+        reversed
+    } else {
+        match anim_state.opcode {
+            0 => {
+                anim_lra[indx].l = anim_state.value;
+                reverse_lraclr(&anim_lra)
+            }
+            _ => {
+                let mut reversed: Vec<LRACLR> = reverse_lraclr(&anim_lra);
+                reversed[0].r = anim_state.value;
+                reversed
+            }
+        }
+    }
+}
+//Cuts the given lengths off the first and the last straight into meshes of their own ids,
+//the straights keep their ids so the rest of the tube is coloured as before
+pub fn split_allowances(cyls: &mut Vec<MainCylinder>, start: f64, end: f64, start_id: u64, end_id: u64) {
+    if (cyls.is_empty()) {
+        return;
+    }
+    let last = cyls.len() - 1;
+    if (start > 0.0 && start < cyls[0].h) {
+        let cyl = cyls[0].clone();
+        let mp = cyl.ca.loc + cyl.ca.dir.mul(start);
+        cyls.push(generate_cyl_by_2pts(start_id, cyl.ca.loc, mp, cyl.r, cyl.ca.dir, cyl.ca.radius_dir));
+        cyls[0] = generate_cyl_by_2pts(cyl.id, mp, cyl.cb.loc, cyl.r, cyl.ca.dir, cyl.ca.radius_dir);
+    }
+    if (end > 0.0 && end < cyls[last].h) {
+        let cyl = cyls[last].clone();
+        let mp = cyl.cb.loc - cyl.ca.dir.mul(end);
+        cyls.push(generate_cyl_by_2pts(end_id, mp, cyl.cb.loc, cyl.r, cyl.ca.dir, cyl.ca.radius_dir));
+        cyls[last] = generate_cyl_by_2pts(cyl.id, cyl.ca.loc, mp, cyl.r, cyl.ca.dir, cyl.ca.radius_dir);
+    }
+}
 fn generate_tor_by_2pts(id: u64, sp: Point3, ep: Point3, radius: f64, fwd_dir_s: Vector3, fwd_dir_e: Vector3, up_dir: Vector3, bend_r: f64, ) -> BendToro
 {
     let bend_center_point = sp + up_dir.cross(fwd_dir_s).mul(bend_r);
@@ -310,13 +496,7 @@ fn generate_tor_by_2pts(id: u64, sp: Point3, ep: Point3, radius: f64, fwd_dir_s:
 }
 pub fn optimize_lraclr(lraclrs: &mut Vec<LRACLR>) {
     lraclrs.iter_mut().for_each(|lracl| {
-        if (abs(lracl.r) >= 360.0) {
-            let rounds = abs(lracl.r as i64 / 360);
-            lracl.r = (abs(lracl.r) - 360.0 * rounds as f64) * signum(lracl.r);
-        }
-        if (abs(lracl.r) > 180.0) {
-            lracl.r = -((360.0 - abs(lracl.r)) * signum(lracl.r));
-        }
+        lracl.r = wrap_rotation(lracl.r);
     });
 }
 pub fn cnc_to_poly_animate(lraclr_arr: &Vec<LRACLR>, anim_state: &AnimState, v_up_orign: &Vector3, dt: f64, bend_params: &BendParameters, ) -> (Vec<MainCylinder>, Vec<BendToro>, AnimState) {
@@ -553,39 +733,3 @@ pub fn all_to_one(c: &Vec<MainCylinder>, t: &Vec<BendToro>) -> (Vec<MeshVertex>,
     let i: Vec<i32> = (0..v.len() as i32).collect();
     (v, i)
 }
-
-pub fn all_to_stp(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> Vec<u8> {
-    use truck_modeling::*;
-    let mut shells: Vec<truck_topology::Shell<Point3, Curve, Surface>> = vec![];
-    tors.iter().for_each(|t| {
-        let p = t.ca.loc + t.ca.radius_dir * t.r;
-        let vertex1 = builder::vertex(p);
-        let circle = builder::rsweep(&vertex1, t.ca.loc, t.ca.dir, Rad(7.0));
-        let disk = builder::try_attach_plane(&[circle]).unwrap();
-        let solid: truck_topology::Solid<Point3, Curve, Surface> = builder::rsweep(&disk, t.bend_center_point, t.bend_plane_norm, t.angle());
-        let shells_loc: Vec<truck_topology::Shell<Point3, Curve, Surface>> = solid.into_boundaries();
-        shells.extend(shells_loc);
-    });
-    cyls.iter().for_each(|c| {
-        let p = c.ca.loc + c.ca.radius_dir * c.r;
-        let vertex1 = builder::vertex(p);
-        let circle = builder::rsweep(&vertex1, c.ca.loc, c.ca.dir, Rad(7.0));
-        let disk = builder::try_attach_plane(&[circle]).unwrap();
-        let v = c.ca.dir.mul(c.h);
-        let solid = builder::tsweep(&disk, v);
-        let shells_loc: Vec<truck_topology::Shell<Point3, Curve, Surface>> = solid.into_boundaries();
-        shells.extend(shells_loc);
-    });
-    let solid = Solid::new(shells);
-    let step_string = out::CompleteStepDisplay::new(
-        out::StepModel::from(&solid.compress()),
-        out::StepHeaderDescriptor {
-            organization_system: "shape-to-step".to_owned(),
-            ..Default::default()
-        },
-    ).to_string();
-    let mut step_file: Vec<u8> = Vec::new();
-    std::io::Write::write_all(&mut step_file, step_string.as_ref()).unwrap();
-    let _ = ruststep::parser::parse(&step_string).unwrap();
-    step_file
-}
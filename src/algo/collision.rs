@@ -0,0 +1,380 @@
+use crate::algo::cnc::{anim_lraclr, LRACLR};
+use crate::algo::simulation::{simulate_at, SimOp, SimTimeline, OP_FEED, OP_ROTATE};
+use crate::algo::{BendToro, MainCylinder, P_FORWARD, ROT_DIR_CCW, TOLE};
+use crate::device::graphics::{AnimState, BendParameters};
+use cgmath::num_traits::abs;
+use cgmath::{Basis3, Deg, EuclideanSpace, InnerSpace, MetricSpace, Rad, Rotation, Rotation3};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::ops::{Mul, Sub};
+use truck_base::bounding_box::BoundingBox;
+use truck_base::cgmath64::{Point3, Vector3};
+use crate::utils::wire::{WireKind, WireMessage};
+
+//a single op is never sampled more often, longer moves are checked more coarsely
+const MAX_OP_SAMPLES: usize = 2000;
+const CENTERLINE_STEP_FACTOR: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub id: i32,
+    pub bbx: BoundingBox<Point3>,
+}
+impl Obstacle {
+    pub fn new(id: i32, min: Point3, max: Point3) -> Self {
+        let mut bbx: BoundingBox<Point3> = BoundingBox::new();
+        bbx.push(min);
+        bbx.push(max);
+        Self { id: id, bbx: bbx }
+    }
+    //[id, min_x, min_y, min_z, max_x, max_y, max_z, ...]
+    pub fn from_f32vec(vec: &Vec<f32>) -> Vec<Obstacle> {
+        let mut ret: Vec<Obstacle> = vec![];
+        if (vec.len() % 7 == 0) {
+            vec.chunks(7).for_each(|c| {
+                let min = Point3::new(c[1] as f64, c[2] as f64, c[3] as f64);
+                let max = Point3::new(c[4] as f64, c[5] as f64, c[6] as f64);
+                ret.push(Obstacle::new(c[0].round() as i32, min, max));
+            });
+        }
+        ret
+    }
+    pub fn to_f32vec(obstacles: &Vec<Obstacle>) -> Vec<f32> {
+        let mut ret: Vec<f32> = vec![];
+        obstacles.iter().for_each(|o| {
+            let min = o.bbx.min();
+            let max = o.bbx.max();
+            ret.push(o.id as f32);
+            ret.extend_from_slice(&[min.x as f32, min.y as f32, min.z as f32, max.x as f32, max.y as f32, max.z as f32]);
+        });
+        ret
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionKind {
+    Obstacle(i32),
+    SelfIntersection,
+}
+
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub anim_id: i32,
+    pub opcode: usize,
+    pub op_counter: i32,
+    pub value: f64,
+    pub position: Point3,
+    pub kind: CollisionKind,
+}
+impl Collision {
+    //Header fields 1 op_counter, 2 anim_id, 3 opcode, 4 value, 5 x, 6 y, 7 z, 8 kind(-1 self intersection or obstacle id)
+    pub fn to_wire(collision: &Option<Collision>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Collision);
+        match collision {
+            None => {}
+            Some(c) => {
+                let kind = match c.kind {
+                    CollisionKind::Obstacle(id) => id as f64,
+                    CollisionKind::SelfIntersection => -1.0,
+                };
+                msg.header.push(1, c.op_counter as f64);
+                msg.header.push(2, c.anim_id as f64);
+                msg.header.push(3, c.opcode as f64);
+                msg.header.push(4, c.value);
+                msg.header.push(5, c.position.x);
+                msg.header.push(6, c.position.y);
+                msg.header.push(7, c.position.z);
+                msg.header.push(8, kind);
+            }
+        }
+        msg
+    }
+}
+impl Display for Collision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "step {} op {} value {} at ({}, {}, {}) {:?}", self.anim_id, self.opcode, self.value, self.position.x, self.position.y, self.position.z, self.kind)
+    }
+}
+
+//Runs the bend sequence op by op and returns the first collision found. Every op is a rigid move of the bent part
+//or the growth of one bend, so it is sampled by how far the tube moves, not by time, and only the centerline is built
+pub fn simulate_collisions(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, bend_params: &BendParameters, obstacles: &Vec<Obstacle>) -> Option<Collision> {
+    if (lraclr_arr.is_empty()) {
+        return None;
+    }
+    let pipe_radius = lraclr_arr[0].pipe_radius;
+    let step = pipe_radius.max(TOLE);
+    let timeline = SimTimeline::new(lraclr_arr, bend_params);
+    let mut found: Option<Collision> = None;
+    timeline.ops.iter().for_each(|op| {
+        if (found.is_none() && abs(op.target) > TOLE) {
+            let start = stage_centerline(lraclr_arr, &timeline.op_state(lraclr_arr, op, 0.0), v_up_orign, step * CENTERLINE_STEP_FACTOR);
+            //the stock behind the bend head does not move, the bent part turns around the feed axis or the bend center
+            let bent = start.iter().filter(|(_p, s)| *s >= 0.0);
+            let disp = match op.opcode {
+                OP_FEED => abs(op.target),
+                OP_ROTATE => bent.map(|(p, _s)| (p.y * p.y + p.z * p.z).sqrt()).fold(0.0, f64::max) * Rad::from(Deg(abs(op.target))).0,
+                _ => (bent.map(|(p, _s)| p.to_vec().magnitude()).fold(0.0, f64::max) + op_clr(lraclr_arr, op)) * Rad::from(Deg(abs(op.target))).0,
+            };
+            let n = ((disp / step).ceil() as usize).max(1).min(MAX_OP_SAMPLES);
+            for i in 0..=n {
+                if (found.is_none()) {
+                    let anim_state = timeline.op_state(lraclr_arr, op, op.target * i as f64 / n as f64);
+                    let pts = if (i == 0) { start.clone() } else { stage_centerline(lraclr_arr, &anim_state, v_up_orign, step * CENTERLINE_STEP_FACTOR) };
+                    match check_centerline(&pts, pipe_radius, obstacles) {
+                        None => {}
+                        Some((position, kind)) => {
+                            found = Some(Collision {
+                                anim_id: anim_state.id,
+                                opcode: anim_state.opcode,
+                                op_counter: anim_state.op_counter,
+                                value: anim_state.value,
+                                position: position,
+                                kind: kind,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    });
+    found
+}
+
+//Tube geometry once the bend sequence has finished op_counter steps, the same frames the animation shows
+pub fn simulate_to_step(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, bend_params: &BendParameters, op_counter: i32) -> (Vec<MainCylinder>, Vec<BendToro>) {
+    let timeline = SimTimeline::new(lraclr_arr, bend_params);
+    let (cyls, tors, _pose) = simulate_at(lraclr_arr, v_up_orign, bend_params, timeline.op_start(op_counter));
+    (cyls, tors)
+}
+
+fn op_clr(lraclr_arr: &Vec<LRACLR>, op: &SimOp) -> f64 {
+    lraclr_arr.get(op.row).map(|lra| lra.clr).unwrap_or(0.0)
+}
+
+//Centerline of the tube at an animation stage, the bent part from the bend head on and the stock behind it
+pub fn stage_centerline(lraclr_arr: &Vec<LRACLR>, anim_state: &AnimState, v_up_orign: &Vector3, step: f64) -> Vec<(Point3, f64)> {
+    let mut ret: Vec<(Point3, f64)> = vec![];
+    if (anim_state.stright_len > TOLE) {
+        let n = ((anim_state.stright_len / step).ceil() as usize).max(1);
+        for i in (1..=n).rev() {
+            let d = anim_state.stright_len * i as f64 / n as f64;
+            ret.push((Point3::new(0.0, 0.0, 0.0) - P_FORWARD.mul(d), -d));
+        }
+    }
+    ret.extend(program_centerline(&anim_lraclr(lraclr_arr, anim_state), v_up_orign, step));
+    ret
+}
+
+//Samples the centerline of a program with arc length parameter, the same frame walk as cnc_to_poly
+pub fn program_centerline(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, step: f64) -> Vec<(Point3, f64)> {
+    let mut sp: Point3 = Point3::new(0.0, 0.0, 0.0);
+    let mut v_up: Vector3 = v_up_orign.clone();
+    let mut v_frw = P_FORWARD;
+    let mut s: f64 = 0.0;
+    let mut ret: Vec<(Point3, f64)> = vec![(sp.clone(), 0.0)];
+    lraclr_arr.iter().for_each(|lracl| {
+        let n = ((lracl.l / step).ceil() as usize).max(1);
+        for i in 1..=n {
+            ret.push((sp + v_frw.mul(lracl.l * i as f64 / n as f64), s + lracl.l * i as f64 / n as f64));
+        }
+        sp = sp + v_frw.mul(lracl.l);
+        s = s + lracl.l;
+        if (lracl.clr > 0.0) {
+            let rotation_x: Basis3<f64> = Rotation3::from_axis_angle(v_frw, Rad::from(Deg(lracl.r * ROT_DIR_CCW)));
+            v_up = rotation_x.rotate_vector(v_up);
+            let center = sp + v_up.cross(v_frw).mul(lracl.clr);
+            let sv = sp.sub(center);
+            let angle = Rad::from(Deg(lracl.a)).0;
+            let arc = angle * lracl.clr;
+            let n = ((abs(arc) / step).ceil() as usize).max(1);
+            for i in 1..=n {
+                let rotation: Basis3<f64> = Rotation3::from_axis_angle(v_up, Rad(angle * i as f64 / n as f64));
+                ret.push((center + rotation.rotate_vector(sv), s + abs(arc) * i as f64 / n as f64));
+            }
+            let rotation_bend: Basis3<f64> = Rotation3::from_axis_angle(v_up, Rad(angle));
+            v_frw = rotation_bend.rotate_vector(v_frw);
+            sp = center + rotation_bend.rotate_vector(sv);
+            s = s + abs(arc);
+        }
+    });
+    ret
+}
+
+pub fn check_centerline(pts: &Vec<(Point3, f64)>, pipe_radius: f64, obstacles: &Vec<Obstacle>) -> Option<(Point3, CollisionKind)> {
+    let mut found: Option<(Point3, CollisionKind)> = None;
+    obstacles.iter().for_each(|o| {
+        if (found.is_none()) {
+            match pts.iter().find(|(p, _s)| bbx_distance(p, &o.bbx) < pipe_radius - TOLE) {
+                None => {}
+                Some((p, _s)) => found = Some((p.clone(), CollisionKind::Obstacle(o.id))),
+            }
+        }
+    });
+    if (found.is_none()) {
+        match centerline_self_intersection(pts, pipe_radius) {
+            None => {}
+            Some(p) => found = Some((p, CollisionKind::SelfIntersection)),
+        }
+    }
+    found
+}
+
+//Points closer than the tube diameter are only a collision when they are more than PI*r apart along the centerline,
+//closer points belong to the same bend as long as CLR is not smaller than the pipe radius
+pub fn centerline_self_intersection(pts: &Vec<(Point3, f64)>, pipe_radius: f64) -> Option<Point3> {
+    let cell = pipe_radius * 2.0;
+    let min_dist = pipe_radius * 2.0 - TOLE;
+    let min_arc = PI * pipe_radius + TOLE;
+    let key = |p: &Point3| -> (i64, i64, i64) { ((p.x / cell).floor() as i64, (p.y / cell).floor() as i64, (p.z / cell).floor() as i64) };
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    pts.iter().enumerate().for_each(|(i, (p, _s))| {
+        grid.entry(key(p)).or_insert(vec![]).push(i);
+    });
+    let mut found: Option<Point3> = None;
+    pts.iter().for_each(|(p, s)| {
+        if (found.is_none()) {
+            let (kx, ky, kz) = key(p);
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        match grid.get(&(kx + dx, ky + dy, kz + dz)) {
+                            None => {}
+                            Some(indxs) => {
+                                indxs.iter().for_each(|j| {
+                                    let (other, other_s) = &pts[*j];
+                                    if (found.is_none() && abs(s - other_s) > min_arc && p.distance(*other) < min_dist) {
+                                        found = Some(Point3::new((p.x + other.x) / 2.0, (p.y + other.y) / 2.0, (p.z + other.z) / 2.0));
+                                    }
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+    found
+}
+
+//Chains straights and bends by their end points and samples the centerline with arc length parameter
+pub fn tube_centerline(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>, step: f64) -> Vec<(Point3, f64)> {
+    let mut segments: Vec<Vec<Point3>> = vec![];
+    cyls.iter().for_each(|c| {
+        let n = ((c.h / step).ceil() as usize).max(1);
+        let dir = c.cb.loc.sub(c.ca.loc);
+        let mut seg: Vec<Point3> = vec![];
+        for i in 0..=n {
+            seg.push(c.ca.loc + dir * (i as f64 / n as f64));
+        }
+        segments.push(seg);
+    });
+    tors.iter().for_each(|t| {
+        segments.push(tor_centerline(t, step));
+    });
+
+    let mut ret: Vec<(Point3, f64)> = vec![];
+    if (segments.is_empty()) {
+        return ret;
+    }
+    let start_indx: usize = {
+        let mut indx = 0;
+        let mut is_found = false;
+        segments.iter().enumerate().for_each(|(i, seg)| {
+            let first = seg.first().unwrap();
+            let is_shared = segments.iter().enumerate().any(|(j, other)| {
+                j != i && (first.distance(*other.first().unwrap()) < TOLE || first.distance(*other.last().unwrap()) < TOLE)
+            });
+            let last = seg.last().unwrap();
+            let is_last_shared = segments.iter().enumerate().any(|(j, other)| {
+                j != i && (last.distance(*other.first().unwrap()) < TOLE || last.distance(*other.last().unwrap()) < TOLE)
+            });
+            if (!is_found && (!is_shared || !is_last_shared)) {
+                indx = i;
+                is_found = true;
+            }
+        });
+        indx
+    };
+    let mut used: Vec<bool> = vec![false; segments.len()];
+    let mut curr: Vec<Point3> = segments[start_indx].clone();
+    let is_first_free = !segments.iter().enumerate().any(|(j, other)| {
+        j != start_indx && (curr.first().unwrap().distance(*other.first().unwrap()) < TOLE || curr.first().unwrap().distance(*other.last().unwrap()) < TOLE)
+    });
+    if (!is_first_free) {
+        curr.reverse();
+    }
+    used[start_indx] = true;
+    let mut s: f64 = 0.0;
+    let mut has_next = true;
+    while (has_next) {
+        curr.iter().for_each(|p| {
+            match ret.last() {
+                None => ret.push((p.clone(), 0.0)),
+                Some((prev, _)) => {
+                    let d = prev.distance(*p);
+                    if (d > 0.0) {
+                        s = s + d;
+                        ret.push((p.clone(), s));
+                    }
+                }
+            }
+        });
+        let tail = curr.last().unwrap().clone();
+        has_next = false;
+        let mut next: Option<Vec<Point3>> = None;
+        segments.iter().enumerate().for_each(|(j, other)| {
+            if (next.is_none() && !used[j]) {
+                if (tail.distance(*other.first().unwrap()) < TOLE) {
+                    next = Some(other.clone());
+                    used[j] = true;
+                } else if (tail.distance(*other.last().unwrap()) < TOLE) {
+                    let mut rev = other.clone();
+                    rev.reverse();
+                    next = Some(rev);
+                    used[j] = true;
+                }
+            }
+        });
+        match next {
+            None => {}
+            Some(n) => {
+                curr = n;
+                has_next = true;
+            }
+        }
+    }
+    ret
+}
+
+fn tor_centerline(t: &BendToro, step: f64) -> Vec<Point3> {
+    let sv = t.ca.loc.sub(t.bend_center_point);
+    let ev = t.cb.loc.sub(t.bend_center_point);
+    let angle = sv.angle(ev).0;
+    let axis: Vector3 = {
+        let n = sv.cross(ev);
+        if (n.magnitude() > TOLE) {
+            n.normalize()
+        } else {
+            t.bend_plane_norm.normalize()
+        }
+    };
+    let n = ((angle * t.bend_radius / step).ceil() as usize).max(1);
+    let mut pts: Vec<Point3> = vec![];
+    for i in 0..=n {
+        let rotation: Basis3<f64> = Rotation3::from_axis_angle(axis, Rad(angle * i as f64 / n as f64));
+        pts.push(t.bend_center_point + rotation.rotate_vector(sv));
+    }
+    pts
+}
+
+fn bbx_distance(p: &Point3, bbx: &BoundingBox<Point3>) -> f64 {
+    if (bbx.is_empty()) {
+        f64::INFINITY
+    } else {
+        let (min, max) = (bbx.min(), bbx.max());
+        let closest = Point3::new(p.x.max(min.x).min(max.x), p.y.max(min.y).min(max.y), p.z.max(min.z).min(max.z));
+        p.distance(closest)
+    }
+}
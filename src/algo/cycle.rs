@@ -0,0 +1,200 @@
+use crate::algo::cnc::LRACLR;
+use cgmath::num_traits::abs;
+use std::fmt::{Display, Formatter};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AxisProfile {
+    pub speed: f64,
+    pub accel: f64,
+}
+impl AxisProfile {
+    pub fn new(speed: f64, accel: f64) -> Self {
+        Self { speed: speed, accel: accel }
+    }
+    //Trapezoidal velocity profile, triangular when the axis can not reach full speed
+    pub fn move_time(&self, dist: f64) -> f64 {
+        let d = abs(dist);
+        if (d == 0.0 || self.speed <= 0.0) {
+            0.0
+        } else if (self.accel <= 0.0) {
+            d / self.speed
+        } else {
+            let acc_dist = self.speed * self.speed / self.accel;
+            if (d >= acc_dist) {
+                d / self.speed + self.speed / self.accel
+            } else {
+                2.0 * (d / self.accel).sqrt()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CycleParameters {
+    pub feed: AxisProfile,
+    pub rotate: AxisProfile,
+    pub bend: AxisProfile,
+    pub bend_return: AxisProfile,
+    pub clamp_time: f64,
+    pub unclamp_time: f64,
+    pub pressure_die_time: f64,
+    pub load_time: f64,
+    pub unload_time: f64,
+    pub is_feed_with_rotate: bool,
+}
+impl CycleParameters {
+    pub fn default() -> Self {
+        Self {
+            feed: AxisProfile::new(500.0, 1000.0),
+            rotate: AxisProfile::new(180.0, 360.0),
+            bend: AxisProfile::new(45.0, 90.0),
+            bend_return: AxisProfile::new(90.0, 180.0),
+            clamp_time: 0.5,
+            unclamp_time: 0.5,
+            pressure_die_time: 0.3,
+            load_time: 5.0,
+            unload_time: 3.0,
+            is_feed_with_rotate: false,
+        }
+    }
+    //[feed_v, feed_a, rot_v, rot_a, bend_v, bend_a, return_v, return_a, clamp, unclamp, pressure_die, load, unload, feed_with_rotate]
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        let get = |i: usize, def: f64| -> f64 {
+            match vec.get(i) {
+                None => def,
+                Some(v) => v.clone() as f64,
+            }
+        };
+        self.feed = AxisProfile::new(get(0, self.feed.speed), get(1, self.feed.accel));
+        self.rotate = AxisProfile::new(get(2, self.rotate.speed), get(3, self.rotate.accel));
+        self.bend = AxisProfile::new(get(4, self.bend.speed), get(5, self.bend.accel));
+        self.bend_return = AxisProfile::new(get(6, self.bend_return.speed), get(7, self.bend_return.accel));
+        self.clamp_time = get(8, self.clamp_time);
+        self.unclamp_time = get(9, self.unclamp_time);
+        self.pressure_die_time = get(10, self.pressure_die_time);
+        self.load_time = get(11, self.load_time);
+        self.unload_time = get(12, self.unload_time);
+        self.is_feed_with_rotate = get(13, if (self.is_feed_with_rotate) { 1.0 } else { 0.0 }) > 0.0;
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        Vec::from([
+            self.feed.speed as f32,
+            self.feed.accel as f32,
+            self.rotate.speed as f32,
+            self.rotate.accel as f32,
+            self.bend.speed as f32,
+            self.bend.accel as f32,
+            self.bend_return.speed as f32,
+            self.bend_return.accel as f32,
+            self.clamp_time as f32,
+            self.unclamp_time as f32,
+            self.pressure_die_time as f32,
+            self.load_time as f32,
+            self.unload_time as f32,
+            if (self.is_feed_with_rotate) { 1.0 } else { 0.0 },
+        ])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StepTime {
+    pub id1: i32,
+    pub id2: i32,
+    pub feed: f64,
+    pub rotate: f64,
+    pub clamp: f64,
+    pub pressure_die: f64,
+    pub bend: f64,
+    pub unclamp: f64,
+    pub bend_return: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CycleTime {
+    pub load: f64,
+    pub unload: f64,
+    //feeding the last straight out of the bend head after the last bend
+    pub feed_out: f64,
+    pub steps: Vec<StepTime>,
+    pub total: f64,
+}
+impl CycleTime {
+    pub fn default() -> Self {
+        Self {
+            load: 0.0,
+            unload: 0.0,
+            feed_out: 0.0,
+            steps: vec![],
+            total: 0.0,
+        }
+    }
+    //Header fields 1 total, 2 load, 3 unload, 4 feed_out.
+    //Row fields 1 id1, 2 id2, 3 feed, 4 rotate, 5 clamp, 6 pressure_die, 7 bend, 8 unclamp, 9 bend_return, 10 total
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Cycle);
+        msg.header.push(1, self.total);
+        msg.header.push(2, self.load);
+        msg.header.push(3, self.unload);
+        msg.header.push(4, self.feed_out);
+        self.steps.iter().for_each(|s| {
+            let mut row = WireRecord::new();
+            row.push(1, s.id1 as f64);
+            row.push(2, s.id2 as f64);
+            row.push(3, s.feed);
+            row.push(4, s.rotate);
+            row.push(5, s.clamp);
+            row.push(6, s.pressure_die);
+            row.push(7, s.bend);
+            row.push(8, s.unclamp);
+            row.push(9, s.bend_return);
+            row.push(10, s.total);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for CycleTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cycle {:.1}s (load {:.1}s, {} bends, feed out {:.1}s, unload {:.1}s)", self.total, self.load, self.steps.len(), self.feed_out, self.unload)
+    }
+}
+
+pub fn cycle_time(lraclr_arr: &Vec<LRACLR>, params: &CycleParameters) -> CycleTime {
+    let mut ret = CycleTime::default();
+    if (lraclr_arr.is_empty()) {
+        return ret;
+    }
+    ret.load = params.load_time;
+    ret.unload = params.unload_time;
+    ret.total = ret.load + ret.unload;
+    lraclr_arr.iter().for_each(|lra| {
+        if (lra.clr > 0.0) {
+            let feed = params.feed.move_time(lra.l);
+            let rotate = params.rotate.move_time(lra.r);
+            let bend = params.bend.move_time(lra.a);
+            let bend_return = params.bend_return.move_time(lra.a);
+            let positioning = if (params.is_feed_with_rotate) { feed.max(rotate) } else { feed + rotate };
+            let total = positioning + params.clamp_time + params.pressure_die_time + bend + params.unclamp_time + bend_return;
+            ret.total = ret.total + total;
+            ret.steps.push(StepTime {
+                id1: lra.id1,
+                id2: lra.id2,
+                feed: feed,
+                rotate: rotate,
+                clamp: params.clamp_time,
+                pressure_die: params.pressure_die_time,
+                bend: bend,
+                unclamp: params.unclamp_time,
+                bend_return: bend_return,
+                total: total,
+            });
+        } else {
+            let feed = params.feed.move_time(lra.l);
+            ret.feed_out = ret.feed_out + feed;
+            ret.total = ret.total + feed;
+        }
+    });
+    ret
+}
@@ -0,0 +1,117 @@
+use crate::algo::{extract_position3d, name_to_id, ROT_DIR_CCW};
+use cgmath::InnerSpace;
+use ruststep::tables::PlaceHolder;
+use std::fmt::{Display, Formatter};
+use std::ops::Mul;
+use truck_base::cgmath64::Vector3;
+use truck_stepio::r#in::Table;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatumKind {
+    //the first bend plane is the loading plane, the first rotation is zero
+    None = 0,
+    //a direction in model coordinates
+    Direction = 1,
+    //the normal of a planar face or the axis of a cylindrical face, by STEP entity id
+    Face = 2,
+}
+
+//What has to point up at the machine when the tube is loaded. The recognised program then starts
+//with the collet rotation that turns the first bend plane away from it
+#[derive(Debug, Clone)]
+pub struct RotationDatum {
+    pub kind: DatumKind,
+    pub direction: Vector3,
+    pub face_id: u64,
+}
+impl RotationDatum {
+    pub fn default() -> Self {
+        Self {
+            kind: DatumKind::None,
+            direction: Vector3::new(0.0, 0.0, 1.0),
+            face_id: 0,
+        }
+    }
+    pub fn is_set(&self) -> bool {
+        self.kind != DatumKind::None
+    }
+    //[kind, x, y, z] for a direction, [kind, face_id] for a face
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => {
+                self.kind = match v.round() as i32 {
+                    1 => DatumKind::Direction,
+                    2 => DatumKind::Face,
+                    _ => DatumKind::None,
+                }
+            }
+        }
+        match self.kind {
+            DatumKind::Direction => {
+                if (vec.len() >= 4) {
+                    let d = Vector3::new(vec[1] as f64, vec[2] as f64, vec[3] as f64);
+                    if (d.magnitude() > 0.0) {
+                        self.direction = d.normalize();
+                    } else {
+                        self.kind = DatumKind::None;
+                    }
+                }
+            }
+            DatumKind::Face => match vec.get(1) {
+                None => {}
+                Some(v) => self.face_id = v.round().max(0.0) as u64,
+            },
+            DatumKind::None => {}
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        match self.kind {
+            DatumKind::None => vec![0.0],
+            DatumKind::Direction => vec![1.0, self.direction.x as f32, self.direction.y as f32, self.direction.z as f32],
+            DatumKind::Face => vec![2.0, self.face_id as f32],
+        }
+    }
+    //Model direction of the datum, a face that is not a plane or a cylinder gives none
+    pub fn resolve(&self, table: &Table, scale: f64) -> Option<Vector3> {
+        match self.kind {
+            DatumKind::None => None,
+            DatumKind::Direction => Some(self.direction.clone()),
+            DatumKind::Face => face_direction(table, self.face_id, scale),
+        }
+    }
+}
+impl Display for RotationDatum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            DatumKind::None => write!(f, "first bend plane"),
+            DatumKind::Direction => write!(f, "direction {:.3} {:.3} {:.3}", self.direction.x, self.direction.y, self.direction.z),
+            DatumKind::Face => write!(f, "face #{}", self.face_id),
+        }
+    }
+}
+
+fn face_direction(table: &Table, face_id: u64, scale: f64) -> Option<Vector3> {
+    let face = table.face_surface.get(&face_id)?;
+    match &face.face_geometry {
+        PlaceHolder::Ref(name) => {
+            let id = name_to_id(name.clone());
+            let position = match table.plane.get(&id) {
+                Some(plane) => Some(&plane.position),
+                None => table.cylindrical_surface.get(&id).map(|cyl| &cyl.position),
+            };
+            match position {
+                None => None,
+                Some(position) => {
+                    let (_loc, dir, _dir_ref) = extract_position3d(table, position, scale);
+                    //directions come back turned by ROT_DIR_CCW, the face sense makes a plane normal point out of the material
+                    dir.map(|d| {
+                        let d = d.mul(ROT_DIR_CCW);
+                        if (face.same_sense) { d } else { d.mul(-1.0) }
+                    })
+                }
+            }
+        }
+        PlaceHolder::Owned(_) => None,
+    }
+}
@@ -0,0 +1,491 @@
+use crate::algo::cnc::{cnc_to_poly, LRACLR};
+use crate::algo::datum::RotationDatum;
+use crate::algo::{analyze_extracted, extract_cyls, extract_faces, parse_stp, BendToro, FaceGeometry, MainCylinder, StpError, StpFace, P_UP_REVERSE, TOLE};
+use cgmath::num_traits::abs;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix3, MetricSpace, Quaternion, SquareMatrix};
+use log::warn;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::ops::{Mul, Sub};
+use truck_base::cgmath64::{Point3, Vector3};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+pub const MAX_DEVIATION: f64 = 0.5;
+const ICP_ITERATIONS: usize = 500;
+const ICP_START_ITERATIONS: usize = 20;
+const ICP_CONVERGENCE: f64 = 1e-6;
+const JACOBI_SWEEPS: usize = 50;
+//grid over the span of every sampled face
+const FACE_SAMPLES: usize = 8;
+//a gap between edge angles smaller than this is the seam of a full turn
+const SEAM_GAP: f64 = PI / 4.0;
+
+#[derive(Debug, Clone)]
+pub struct SegmentDeviation {
+    pub id: i32,
+    pub max: f64,
+    pub rms: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviationReport {
+    pub segments: Vec<SegmentDeviation>,
+    pub max: f64,
+    pub rms: f64,
+    pub samples: usize,
+    pub tolerance: f64,
+    pub is_passed: bool,
+}
+impl DeviationReport {
+    pub fn default() -> Self {
+        Self {
+            segments: vec![],
+            max: 0.0,
+            rms: 0.0,
+            samples: 0,
+            tolerance: MAX_DEVIATION,
+            is_passed: false,
+        }
+    }
+    //No points could be sampled off the faces, the program was not checked against them
+    pub fn is_checked(&self) -> bool {
+        self.samples > 0
+    }
+    pub fn is_failed(&self) -> bool {
+        self.is_checked() && !self.is_passed
+    }
+    pub fn failed_ids(&self) -> Vec<i32> {
+        self.segments.iter().filter(|s| s.max > self.tolerance).map(|s| s.id).collect()
+    }
+    //Header fields 1 is_passed, 2 max, 3 rms, 4 tolerance, 5 samples, 6 is_checked.
+    //Row fields 1 id, 2 max, 3 rms, 4 count, one row per segment
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Deviation);
+        msg.header.push_bool(1, self.is_passed);
+        msg.header.push(2, self.max);
+        msg.header.push(3, self.rms);
+        msg.header.push(4, self.tolerance);
+        msg.header.push(5, self.samples as f64);
+        msg.header.push_bool(6, self.is_checked());
+        self.segments.iter().for_each(|s| {
+            let mut row = WireRecord::new();
+            row.push(1, s.id as f64);
+            row.push(2, s.max);
+            row.push(3, s.rms);
+            row.push(4, s.count as f64);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for DeviationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let _ = writeln!(
+            f,
+            "{} max {:.3} rms {:.3} tolerance {} samples {}",
+            if (!self.is_checked()) { "NOT CHECKED" } else if (self.is_passed) { "PASSED" } else { "FAILED" },
+            self.max,
+            self.rms,
+            self.tolerance,
+            self.samples
+        );
+        self.segments.iter().for_each(|s| {
+            let _ = writeln!(f, "segment {} max {:.3} rms {:.3} samples {}", s.id, s.max, s.rms, s.count);
+        });
+        Ok(())
+    }
+}
+
+//Recognizes the program and checks it against the original faces in one pass,
+//the datum is resolved in the same table and sets the first rotation.
+//The faces are read again from the table, the recognition does not decide what is checked
+pub fn analyze_stp_verified(stp: &Vec<u8>, tolerance: f64, datum: &RotationDatum) -> Result<(Vec<LRACLR>, DeviationReport), StpError> {
+    let (table, scale) = parse_stp(stp)?;
+    let (cyls, tors) = extract_cyls(&table, scale);
+    let up = if (datum.is_set()) {
+        let up = datum.resolve(&table, scale);
+        if (up.is_none()) {
+            warn!("DATUM {} NOT FOUND", datum);
+        }
+        up
+    } else {
+        None
+    };
+    let lraclr_arr = analyze_extracted(&cyls, &tors, &table, scale, up);
+    match lraclr_arr.first() {
+        None => Ok((lraclr_arr, DeviationReport::default())),
+        Some(lra) => {
+            let points = sample_tube_faces(&extract_faces(&table, scale), lra.pipe_radius);
+            let report = check_deviation(&points, &lraclr_arr, tolerance);
+            Ok((lraclr_arr, report))
+        }
+    }
+}
+
+//Points on the outer wall. Cylinder and torus faces of the pipe radius are sampled on their own surface over
+//the span their edges cover, faces of the inner wall, the end planes and other surfaces are not on it
+pub fn sample_tube_faces(faces: &Vec<StpFace>, pipe_radius: f64) -> Vec<Point3> {
+    let mut ret: Vec<Point3> = vec![];
+    faces.iter().for_each(|face| match &face.geometry {
+        FaceGeometry::Cylinder { loc, axis, ref_dir, radius } if (abs(radius - pipe_radius) < TOLE) => {
+            let a = axis.normalize();
+            let u = ref_dir.normalize();
+            let v = a.cross(u);
+            let params: Vec<(f64, f64)> = face
+                .edge_points
+                .iter()
+                .map(|p| {
+                    let d = p.sub(*loc);
+                    (d.dot(a), d.dot(v).atan2(d.dot(u)))
+                })
+                .collect();
+            let (t0, t1) = params.iter().fold((f64::MAX, f64::MIN), |(t0, t1), (t, _)| (t0.min(*t), t1.max(*t)));
+            let (s0, sweep) = angle_span(&params.iter().map(|(_, s)| *s).collect());
+            ret.extend(face.edge_points.iter().cloned());
+            grid(t0, t1, s0, sweep).iter().for_each(|(t, s)| {
+                ret.push(*loc + a.mul(*t) + u.mul(radius * s.cos()) + v.mul(radius * s.sin()));
+            });
+        }
+        FaceGeometry::Torus { loc, axis, ref_dir, major_radius, minor_radius } if (abs(minor_radius - pipe_radius) < TOLE) => {
+            let a = axis.normalize();
+            let u = ref_dir.normalize();
+            let v = a.cross(u);
+            let params: Vec<(f64, f64)> = face
+                .edge_points
+                .iter()
+                .map(|p| {
+                    let d = p.sub(*loc);
+                    let phi = d.dot(v).atan2(d.dot(u));
+                    let e = u.mul(phi.cos()) + v.mul(phi.sin());
+                    let q = d - e.mul(*major_radius);
+                    (phi, q.dot(a).atan2(q.dot(e)))
+                })
+                .collect();
+            let (p0, p_sweep) = angle_span(&params.iter().map(|(phi, _)| *phi).collect());
+            let (s0, sweep) = angle_span(&params.iter().map(|(_, s)| *s).collect());
+            ret.extend(face.edge_points.iter().cloned());
+            grid(p0, p0 + p_sweep, s0, sweep).iter().for_each(|(phi, s)| {
+                let e = u.mul(phi.cos()) + v.mul(phi.sin());
+                ret.push(*loc + e.mul(major_radius + minor_radius * s.cos()) + a.mul(minor_radius * s.sin()));
+            });
+        }
+        _ => {}
+    });
+    ret
+}
+
+//Start and sweep of the angles the edges cover, the largest gap between them is outside the face
+fn angle_span(angles: &Vec<f64>) -> (f64, f64) {
+    let mut sorted = angles.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    match (sorted.first(), sorted.last()) {
+        (Some(first), Some(last)) => {
+            let mut start = *first;
+            let mut gap = *first + 2.0 * PI - *last;
+            sorted.windows(2).for_each(|w| {
+                if (w[1] - w[0] > gap) {
+                    gap = w[1] - w[0];
+                    start = w[1];
+                }
+            });
+            if (gap < SEAM_GAP) { (start, 2.0 * PI) } else { (start, 2.0 * PI - gap) }
+        }
+        _ => (0.0, 0.0),
+    }
+}
+
+fn grid(t0: f64, t1: f64, s0: f64, sweep: f64) -> Vec<(f64, f64)> {
+    let mut ret: Vec<(f64, f64)> = vec![];
+    for i in 1..FACE_SAMPLES {
+        for j in 0..FACE_SAMPLES {
+            ret.push((t0 + (t1 - t0) * i as f64 / FACE_SAMPLES as f64, s0 + sweep * j as f64 / FACE_SAMPLES as f64));
+        }
+    }
+    ret
+}
+
+//Points of degenerate faces that are not finite are left out, they would spoil the fit and the maximum
+pub fn check_deviation(points: &Vec<Point3>, lraclr_arr: &Vec<LRACLR>, tolerance: f64) -> DeviationReport {
+    let mut ret = DeviationReport::default();
+    ret.tolerance = tolerance;
+    let points: Vec<Point3> = points.iter().filter(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite()).cloned().collect();
+    if (points.is_empty() || lraclr_arr.is_empty()) {
+        return ret;
+    }
+    let (cyls, tors) = cnc_to_poly(lraclr_arr, &P_UP_REVERSE);
+    let (rotation, translation) = best_fit(&points, &cyls, &tors);
+
+    let mut by_segment: HashMap<i32, (f64, f64, usize)> = HashMap::new();
+    let mut sum_sq: f64 = 0.0;
+    points.iter().for_each(|p| {
+        let tp = Point3::from_vec(rotation * p.to_vec() + translation);
+        match closest_on_tube(&tp, &cyls, &tors) {
+            None => {}
+            Some((_, _, d)) if (!d.is_finite()) => {}
+            Some((id, _, d)) => {
+                let d = abs(d);
+                let e = by_segment.entry(id).or_insert((0.0, 0.0, 0));
+                e.0 = e.0.max(d);
+                e.1 = e.1 + d * d;
+                e.2 = e.2 + 1;
+                ret.max = ret.max.max(d);
+                sum_sq = sum_sq + d * d;
+                ret.samples = ret.samples + 1;
+            }
+        }
+    });
+    if (ret.samples > 0) {
+        ret.rms = (sum_sq / ret.samples as f64).sqrt();
+    }
+    by_segment.iter().for_each(|(id, (max, sq, count))| {
+        ret.segments.push(SegmentDeviation {
+            id: *id,
+            max: *max,
+            rms: (sq / *count as f64).sqrt(),
+            count: *count,
+        });
+    });
+    ret.segments.sort_by(|a, b| a.id.cmp(&b.id));
+    ret.is_passed = ret.samples > 0 && ret.max <= tolerance;
+    ret
+}
+
+//Rigid transform moving the sampled points onto the regenerated tube. Principal axes give
+//the start poses, every proper sign combination gets a few ICP steps and the best one is refined
+pub fn best_fit(points: &Vec<Point3>, cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> (Matrix3<f64>, Vector3) {
+    let mut model_points: Vec<Point3> = vec![];
+    cyls.iter().for_each(|c| {
+        model_points.extend(c.ca.gen_points());
+        model_points.extend(c.cb.gen_points());
+    });
+    tors.iter().for_each(|t| {
+        model_points.extend(t.ca.gen_points());
+        model_points.extend(t.cb.gen_points());
+    });
+    let (src_center, src_axes) = principal_axes(points);
+    let (dst_center, dst_axes) = principal_axes(&model_points);
+
+    let mut best: (Matrix3<f64>, Vector3, f64) = (Matrix3::identity(), Vector3::new(0.0, 0.0, 0.0), f64::MAX);
+    [(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)].iter().for_each(|(s0, s1)| {
+        let a0 = src_axes.x * *s0;
+        let a1 = src_axes.y * *s1;
+        let src = Matrix3::from_cols(a0, a1, a0.cross(a1));
+        let dst = Matrix3::from_cols(dst_axes.x, dst_axes.y, dst_axes.x.cross(dst_axes.y));
+        let rotation = dst * src.transpose();
+        let translation = dst_center.to_vec() - rotation * src_center.to_vec();
+        let (rotation, translation, rms) = icp(points, cyls, tors, rotation, translation, ICP_START_ITERATIONS);
+        if (rms < best.2) {
+            best = (rotation, translation, rms);
+        }
+    });
+    let (rotation, translation, _) = icp(points, cyls, tors, best.0, best.1, ICP_ITERATIONS);
+    (rotation, translation)
+}
+
+fn icp(points: &Vec<Point3>, cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>, rotation: Matrix3<f64>, translation: Vector3, iterations: usize) -> (Matrix3<f64>, Vector3, f64) {
+    let mut rotation = rotation;
+    let mut translation = translation;
+    let mut prev_rms = f64::MAX;
+    let mut rms = f64::MAX;
+    for _ in 0..iterations {
+        let mut src: Vec<Point3> = vec![];
+        let mut dst: Vec<Point3> = vec![];
+        let mut sum_sq: f64 = 0.0;
+        points.iter().for_each(|p| {
+            let tp = Point3::from_vec(rotation * p.to_vec() + translation);
+            match closest_on_tube(&tp, cyls, tors) {
+                None => {}
+                Some((_, cp, d)) => {
+                    src.push(p.clone());
+                    dst.push(cp);
+                    sum_sq = sum_sq + d * d;
+                }
+            }
+        });
+        if (src.is_empty()) {
+            break;
+        }
+        rms = (sum_sq / src.len() as f64).sqrt();
+        let (r, t) = horn(&src, &dst);
+        rotation = r;
+        translation = t;
+        if (abs(prev_rms - rms) < ICP_CONVERGENCE) {
+            break;
+        }
+        prev_rms = rms;
+    }
+    (rotation, translation, rms)
+}
+
+//Absolute orientation by unit quaternions (Horn 1987)
+fn horn(src: &Vec<Point3>, dst: &Vec<Point3>) -> (Matrix3<f64>, Vector3) {
+    let n = src.len() as f64;
+    let cs = src.iter().fold(Vector3::new(0.0, 0.0, 0.0), |acc, p| acc + p.to_vec()) / n;
+    let cd = dst.iter().fold(Vector3::new(0.0, 0.0, 0.0), |acc, p| acc + p.to_vec()) / n;
+    let mut m = [[0.0f64; 3]; 3];
+    src.iter().zip(dst.iter()).for_each(|(a, b)| {
+        let a = a.to_vec() - cs;
+        let b = b.to_vec() - cd;
+        let av = [a.x, a.y, a.z];
+        let bv = [b.x, b.y, b.z];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = m[i][j] + av[i] * bv[j];
+            }
+        }
+    });
+    let (sxx, sxy, sxz) = (m[0][0], m[0][1], m[0][2]);
+    let (syx, syy, syz) = (m[1][0], m[1][1], m[1][2]);
+    let (szx, szy, szz) = (m[2][0], m[2][1], m[2][2]);
+    let nm: [[f64; 4]; 4] = [
+        [sxx + syy + szz, syz - szy, szx - sxz, sxy - syx],
+        [syz - szy, sxx - syy - szz, sxy + syx, szx + sxz],
+        [szx - sxz, sxy + syx, -sxx + syy - szz, syz + szy],
+        [sxy - syx, szx + sxz, syz + szy, -sxx - syy + szz],
+    ];
+    let (values, vectors) = jacobi_eigen(nm);
+    let mut best = 0;
+    for i in 1..4 {
+        if (values[i] > values[best]) {
+            best = i;
+        }
+    }
+    let q = Quaternion::new(vectors[0][best], vectors[1][best], vectors[2][best], vectors[3][best]).normalize();
+    let rotation = Matrix3::from(q);
+    let translation = cd - rotation * cs;
+    (rotation, translation)
+}
+
+fn principal_axes(points: &Vec<Point3>) -> (Point3, Matrix3<f64>) {
+    let n = points.len().max(1) as f64;
+    let c = points.iter().fold(Vector3::new(0.0, 0.0, 0.0), |acc, p| acc + p.to_vec()) / n;
+    let mut m = [[0.0f64; 3]; 3];
+    points.iter().for_each(|p| {
+        let d = p.to_vec() - c;
+        let dv = [d.x, d.y, d.z];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = m[i][j] + dv[i] * dv[j];
+            }
+        }
+    });
+    let (values, vectors) = jacobi_eigen(m);
+    let mut order: Vec<usize> = vec![0, 1, 2];
+    order.sort_by(|a, b| values[*b].total_cmp(&values[*a]));
+    let axis = |i: usize| Vector3::new(vectors[0][i], vectors[1][i], vectors[2][i]);
+    let ax = axis(order[0]);
+    let ay = axis(order[1]);
+    (Point3::from_vec(c), Matrix3::from_cols(ax, ay, ax.cross(ay)))
+}
+
+//Eigen decomposition of a small symmetric matrix, eigenvectors are the columns
+fn jacobi_eigen<const N: usize>(m: [[f64; N]; N]) -> ([f64; N], [[f64; N]; N]) {
+    let mut a = m;
+    let mut v = [[0.0f64; N]; N];
+    for i in 0..N {
+        v[i][i] = 1.0;
+    }
+    for _ in 0..JACOBI_SWEEPS {
+        let mut off: f64 = 0.0;
+        for p in 0..N {
+            for q in (p + 1)..N {
+                off = off + a[p][q] * a[p][q];
+            }
+        }
+        if (off < 1e-18) {
+            break;
+        }
+        for p in 0..N {
+            for q in (p + 1)..N {
+                if (abs(a[p][q]) > 1e-300) {
+                    let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                    let t = theta.signum() / (abs(theta) + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..N {
+                        let akp = a[k][p];
+                        let akq = a[k][q];
+                        a[k][p] = c * akp - s * akq;
+                        a[k][q] = s * akp + c * akq;
+                    }
+                    for k in 0..N {
+                        let apk = a[p][k];
+                        let aqk = a[q][k];
+                        a[p][k] = c * apk - s * aqk;
+                        a[q][k] = s * apk + c * aqk;
+                    }
+                    for k in 0..N {
+                        let vkp = v[k][p];
+                        let vkq = v[k][q];
+                        v[k][p] = c * vkp - s * vkq;
+                        v[k][q] = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+    }
+    let mut values = [0.0f64; N];
+    for i in 0..N {
+        values[i] = a[i][i];
+    }
+    (values, v)
+}
+
+//Segment id, closest point on the tube surface and signed distance, positive outside the tube
+pub fn closest_on_tube(p: &Point3, cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> Option<(i32, Point3, f64)> {
+    let mut best: Option<(i32, Point3, f64)> = None;
+    let mut update = |id: i32, axis_point: Point3, r: f64| {
+        let radial = p.sub(axis_point);
+        let len = radial.magnitude();
+        let cp = if (len > 0.0) { axis_point + radial * (r / len) } else { axis_point };
+        let d = p.distance(cp) * if (len >= r) { 1.0 } else { -1.0 };
+        let is_better = match &best {
+            None => true,
+            Some((_, _, bd)) => abs(d) < abs(*bd),
+        };
+        if (is_better) {
+            best = Some((id, cp, d));
+        }
+    };
+    cyls.iter().for_each(|c| {
+        let dir = c.cb.loc.sub(c.ca.loc);
+        let len_sq = dir.magnitude2();
+        let t = if (len_sq > 0.0) { (p.sub(c.ca.loc).dot(dir) / len_sq).max(0.0).min(1.0) } else { 0.0 };
+        update(c.id as i32, c.ca.loc + dir * t, c.r);
+    });
+    tors.iter().for_each(|t| {
+        let sv = t.ca.loc.sub(t.bend_center_point);
+        let ev = t.cb.loc.sub(t.bend_center_point);
+        let n: Vector3 = {
+            let n = sv.cross(ev);
+            if (n.magnitude() > TOLE) {
+                n.normalize()
+            } else {
+                t.bend_plane_norm.normalize()
+            }
+        };
+        let v = p.sub(t.bend_center_point);
+        let in_plane = v - n * v.dot(n);
+        let axis_point = {
+            if (in_plane.magnitude() < TOLE) {
+                t.ca.loc
+            } else {
+                let dir = in_plane.normalize();
+                let total = sv.angle(ev).0;
+                let a_s = sv.angle(dir).0;
+                let a_e = ev.angle(dir).0;
+                if (abs(a_s + a_e - total) < 1e-6) {
+                    t.bend_center_point + dir * t.bend_radius
+                } else if (a_s < a_e) {
+                    t.ca.loc
+                } else {
+                    t.cb.loc
+                }
+            }
+        };
+        update(t.id as i32, axis_point, t.r);
+    });
+    best
+}
@@ -0,0 +1,224 @@
+use crate::algo::cnc::{intersection_points, LRACLR};
+use crate::algo::output::wrap_rotation;
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+use cgmath::num_traits::abs;
+use cgmath::MetricSpace;
+use std::fmt::{Display, Formatter};
+use truck_base::cgmath64::Vector3;
+
+const DIFF_LEN_TOLE: f64 = 0.1;
+const DIFF_ANGLE_TOLE: f64 = 0.1;
+const DIFF_CLR_TOLE: f64 = 0.1;
+//a bend matched with the end straight is worse than one removed and one added
+const COST_GAP: usize = 1;
+const COST_CHANGED: usize = 1;
+const COST_MISMATCH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    Same = 0,
+    Changed = 1,
+    //only in the new revision
+    Added = 2,
+    //only in the old revision
+    Removed = 3,
+}
+
+//One row of the comparison, a is the row of the old revision and b of the new one
+#[derive(Debug, Clone)]
+pub struct RowDiff {
+    pub kind: DiffKind,
+    pub index_a: Option<usize>,
+    pub index_b: Option<usize>,
+    pub a: Option<LRACLR>,
+    pub b: Option<LRACLR>,
+    //distance between the intersection points of the matched rows
+    pub shift: f64,
+}
+impl RowDiff {
+    fn delta(&self, f: fn(&LRACLR) -> f64) -> f64 {
+        match (&self.a, &self.b) {
+            (Some(a), Some(b)) => f(b) - f(a),
+            _ => 0.0,
+        }
+    }
+    pub fn dl(&self) -> f64 {
+        self.delta(|lra| lra.l)
+    }
+    pub fn dr(&self) -> f64 {
+        wrap_rotation(self.delta(|lra| lra.r))
+    }
+    pub fn da(&self) -> f64 {
+        self.delta(|lra| lra.a)
+    }
+    pub fn dclr(&self) -> f64 {
+        self.delta(|lra| lra.clr)
+    }
+    pub fn is_stright_changed(&self) -> bool {
+        abs(self.dl()) > DIFF_LEN_TOLE
+    }
+    pub fn is_bend_changed(&self) -> bool {
+        abs(self.dr()) > DIFF_ANGLE_TOLE || abs(self.da()) > DIFF_ANGLE_TOLE || abs(self.dclr()) > DIFF_CLR_TOLE
+    }
+}
+impl Display for RowDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let idx = |i: Option<usize>| -> String { i.map(|i| format!("{}", i)).unwrap_or("-".to_string()) };
+        match self.kind {
+            DiffKind::Same => write!(f, "{}/{} same", idx(self.index_a), idx(self.index_b)),
+            DiffKind::Changed => write!(
+                f,
+                "{}/{} changed dL {:.2} dR {:.2} dA {:.2} dCLR {:.2} shift {:.2}",
+                idx(self.index_a),
+                idx(self.index_b),
+                self.dl(),
+                self.dr(),
+                self.da(),
+                self.dclr(),
+                self.shift
+            ),
+            DiffKind::Added => write!(f, "-/{} added {}", idx(self.index_b), self.b.as_ref().map(|lra| format!("{}", lra)).unwrap_or(String::new())),
+            DiffKind::Removed => write!(f, "{}/- removed {}", idx(self.index_a), self.a.as_ref().map(|lra| format!("{}", lra)).unwrap_or(String::new())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgramDiff {
+    pub rows: Vec<RowDiff>,
+    pub max_shift: f64,
+}
+impl ProgramDiff {
+    //Rows are aligned with the fewest changes, so a bend put in or taken out does not show as every following bend changed.
+    //The centerlines are built with the same up direction and compared at the matched intersection points
+    pub fn compare(lraclr_a: &Vec<LRACLR>, lraclr_b: &Vec<LRACLR>, v_up_orign: &Vector3) -> Self {
+        let lraclr_b = &align_first_rotation(lraclr_b, lraclr_a);
+        let (n, m) = (lraclr_a.len(), lraclr_b.len());
+        let mut cost: Vec<Vec<usize>> = vec![vec![0; m + 1]; n + 1];
+        for i in 0..=n {
+            cost[i][m] = (n - i) * COST_GAP;
+        }
+        for j in 0..=m {
+            cost[n][j] = (m - j) * COST_GAP;
+        }
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                let matched = cost[i + 1][j + 1] + row_cost(&lraclr_a[i], &lraclr_b[j]);
+                let removed = cost[i + 1][j] + COST_GAP;
+                let added = cost[i][j + 1] + COST_GAP;
+                cost[i][j] = matched.min(removed).min(added);
+            }
+        }
+        let pts_a = intersection_points(lraclr_a, v_up_orign);
+        let pts_b = intersection_points(lraclr_b, v_up_orign);
+        let mut rows: Vec<RowDiff> = vec![];
+        let (mut i, mut j) = (0, 0);
+        while (i < n || j < m) {
+            if (i < n && j < m && cost[i][j] == cost[i + 1][j + 1] + row_cost(&lraclr_a[i], &lraclr_b[j])) {
+                let shift = match (pts_a.get(i + 1), pts_b.get(j + 1)) {
+                    (Some(pa), Some(pb)) => pa.distance(*pb),
+                    _ => 0.0,
+                };
+                let mut row = RowDiff { kind: DiffKind::Same, index_a: Some(i), index_b: Some(j), a: Some(lraclr_a[i].clone()), b: Some(lraclr_b[j].clone()), shift: shift };
+                if (row.is_stright_changed() || row.is_bend_changed()) {
+                    row.kind = DiffKind::Changed;
+                }
+                rows.push(row);
+                i = i + 1;
+                j = j + 1;
+            } else if (i < n && (j >= m || cost[i][j] == cost[i + 1][j] + COST_GAP)) {
+                rows.push(RowDiff { kind: DiffKind::Removed, index_a: Some(i), index_b: None, a: Some(lraclr_a[i].clone()), b: None, shift: 0.0 });
+                i = i + 1;
+            } else {
+                rows.push(RowDiff { kind: DiffKind::Added, index_a: None, index_b: Some(j), a: None, b: Some(lraclr_b[j].clone()), shift: 0.0 });
+                j = j + 1;
+            }
+        }
+        let max_shift = rows.iter().fold(0.0, |acc: f64, row| acc.max(row.shift));
+        Self { rows: rows, max_shift: max_shift }
+    }
+    pub fn is_same(&self) -> bool {
+        self.rows.iter().all(|row| row.kind == DiffKind::Same)
+    }
+    pub fn count(&self, kind: DiffKind) -> usize {
+        self.rows.iter().filter(|row| row.kind == kind).count()
+    }
+    //Mesh ids to highlight, a changed row marks its straight or its bend or both, added and removed rows mark both
+    pub fn mesh_ids(&self, kind: DiffKind, is_b: bool) -> Vec<i32> {
+        let mut ret: Vec<i32> = vec![];
+        self.rows.iter().filter(|row| row.kind == kind).for_each(|row| {
+            let lra = if (is_b) { &row.b } else { &row.a };
+            match lra {
+                None => {}
+                Some(lra) => match row.kind {
+                    DiffKind::Same => {}
+                    DiffKind::Changed => {
+                        if (row.is_stright_changed()) {
+                            ret.push(lra.id1);
+                        }
+                        if (row.is_bend_changed()) {
+                            ret.push(lra.id2);
+                        }
+                    }
+                    DiffKind::Added | DiffKind::Removed => {
+                        ret.push(lra.id1);
+                        ret.push(lra.id2);
+                    }
+                },
+            }
+        });
+        ret
+    }
+    //Row fields 1 kind, 2 row of the old revision, 3 row of the new one (-1 when missing), 4 dL, 5 dR, 6 dA, 7 dCLR, 8 shift
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Diff);
+        self.rows.iter().for_each(|d| {
+            let mut row = WireRecord::new();
+            row.push(1, d.kind as i32 as f64);
+            row.push(2, d.index_a.map(|i| i as f64).unwrap_or(-1.0));
+            row.push(3, d.index_b.map(|i| i as f64).unwrap_or(-1.0));
+            row.push(4, d.dl());
+            row.push(5, d.dr());
+            row.push(6, d.da());
+            row.push(7, d.dclr());
+            row.push(8, d.shift);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for ProgramDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let _ = writeln!(
+            f,
+            "changed {} added {} removed {} max shift {:.2}",
+            self.count(DiffKind::Changed),
+            self.count(DiffKind::Added),
+            self.count(DiffKind::Removed),
+            self.max_shift
+        );
+        self.rows.iter().filter(|row| row.kind != DiffKind::Same).for_each(|row| {
+            let _ = writeln!(f, "{}", row);
+        });
+        Ok(())
+    }
+}
+
+//The first rotation only turns the whole part in the collet, the other revision is loaded the same way as the reference
+pub fn align_first_rotation(lraclr_arr: &Vec<LRACLR>, reference: &Vec<LRACLR>) -> Vec<LRACLR> {
+    let mut ret = lraclr_arr.clone();
+    match (ret.first_mut(), reference.first()) {
+        (Some(lra), Some(r)) => lra.r = r.r,
+        _ => {}
+    }
+    ret
+}
+
+fn row_cost(a: &LRACLR, b: &LRACLR) -> usize {
+    if ((a.clr > 0.0) != (b.clr > 0.0)) {
+        COST_MISMATCH
+    } else {
+        let row = RowDiff { kind: DiffKind::Same, index_a: None, index_b: None, a: Some(a.clone()), b: Some(b.clone()), shift: 0.0 };
+        if (row.is_stright_changed() || row.is_bend_changed()) { COST_CHANGED } else { 0 }
+    }
+}
@@ -0,0 +1,443 @@
+use crate::algo::cnc::{cnc_to_poly, intersection_points, LRACLR};
+use crate::algo::collision::tube_centerline;
+use crate::algo::output::{to_output, OutputMode};
+use truck_base::cgmath64::{Point3, Vector3};
+
+//A3 landscape, millimetres with y up
+pub const SHEET_W: f64 = 420.0;
+pub const SHEET_H: f64 = 297.0;
+pub const MARGIN: f64 = 10.0;
+const VIEWS_W: f64 = 290.0;
+pub const TEXT_H: f64 = 2.5;
+pub const ROW_H: f64 = 4.5;
+const DIM_OFFSET: f64 = 8.0;
+const STANDARD_SCALES: [f64; 13] = [10.0, 5.0, 2.0, 1.0, 0.5, 0.2, 0.1, 0.05, 0.02, 0.01, 0.005, 0.002, 0.001];
+const ISO_COS: f64 = 0.8660254037844386;
+//Line types the layers refer to, the centre line dashes are the ones of the svg with the gaps negative
+const DXF_LINETYPES: [(&str, &str, &[f64]); 2] = [("CONTINUOUS", "Solid line", &[]), ("CENTER", "Center ____ _ ____ _", &[6.0, -1.5, 1.0, -1.5])];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawingFormat {
+    Svg = 0,
+    Dxf = 1,
+}
+impl DrawingFormat {
+    pub fn from_i32(v: i32) -> DrawingFormat {
+        match v {
+            1 => DrawingFormat::Dxf,
+            _ => DrawingFormat::Svg,
+        }
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DrawingFormat::Svg => "svg",
+            DrawingFormat::Dxf => "dxf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    Frame,
+    Tube,
+    Center,
+    Dim,
+    Text,
+}
+impl Layer {
+    fn name(&self) -> &'static str {
+        match self {
+            Layer::Frame => "FRAME",
+            Layer::Tube => "TUBE",
+            Layer::Center => "CENTER",
+            Layer::Dim => "DIM",
+            Layer::Text => "TEXT",
+        }
+    }
+    fn linetype(&self) -> &'static str {
+        match self {
+            Layer::Center => "CENTER",
+            _ => "CONTINUOUS",
+        }
+    }
+    //AutoCAD colour index
+    fn aci(&self) -> i32 {
+        match self {
+            Layer::Frame => 7,
+            Layer::Tube => 8,
+            Layer::Center => 1,
+            Layer::Dim => 3,
+            Layer::Text => 7,
+        }
+    }
+    fn svg_style(&self) -> &'static str {
+        match self {
+            Layer::Frame => "stroke:#000;stroke-width:0.5;fill:none",
+            Layer::Tube => "stroke:#c8c8c8;stroke-linecap:round;stroke-linejoin:round;fill:none",
+            Layer::Center => "stroke:#c00;stroke-width:0.25;stroke-dasharray:6,1.5,1,1.5;fill:none",
+            Layer::Dim => "stroke:#070;stroke-width:0.18;fill:none",
+            Layer::Text => "stroke:#000;stroke-width:0.18;fill:none",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DrawItem {
+    //points and a constant width, 0 is a hairline
+    Polyline(Vec<[f64; 2]>, f64, Layer),
+    Circle([f64; 2], f64, Layer),
+    Text([f64; 2], f64, String, Layer),
+}
+
+#[derive(Debug, Clone)]
+pub struct DrawingInfo {
+    pub part_number: String,
+    pub material: String,
+    pub wall: f64,
+    pub notes: String,
+    //feed and rotation columns of the LRA table
+    pub output: OutputMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct Drawing {
+    pub items: Vec<DrawItem>,
+}
+impl Drawing {
+    pub fn new() -> Self {
+        Self { items: vec![] }
+    }
+    pub fn line(&mut self, p0: [f64; 2], p1: [f64; 2], layer: Layer) {
+        self.items.push(DrawItem::Polyline(vec![p0, p1], 0.0, layer));
+    }
+    pub fn rect(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, layer: Layer) {
+        self.items.push(DrawItem::Polyline(vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1], [x0, y0]], 0.0, layer));
+    }
+    pub fn text(&mut self, p: [f64; 2], h: f64, s: &str, layer: Layer) {
+        self.items.push(DrawItem::Text(p, h, s.to_string(), layer));
+    }
+    pub fn to_svg(&self) -> String {
+        let mut ret = String::new();
+        ret.push_str(
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" viewBox=\"0 0 {} {}\">\n<rect width=\"{}\" height=\"{}\" fill=\"#fff\"/>\n",
+                SHEET_W, SHEET_H, SHEET_W, SHEET_H, SHEET_W, SHEET_H
+            )
+            .as_str(),
+        );
+        self.items.iter().for_each(|item| match item {
+            DrawItem::Polyline(pts, width, layer) => {
+                let pts: Vec<String> = pts.iter().map(|p| format!("{:.3},{:.3}", p[0], SHEET_H - p[1])).collect();
+                let width = if (*width > 0.0) { format!(";stroke-width:{:.3}", width) } else { String::new() };
+                ret.push_str(format!("<polyline points=\"{}\" style=\"{}{}\"/>\n", pts.join(" "), layer.svg_style(), width).as_str());
+            }
+            DrawItem::Circle(c, r, layer) => {
+                ret.push_str(format!("<circle cx=\"{:.3}\" cy=\"{:.3}\" r=\"{:.3}\" style=\"{}\"/>\n", c[0], SHEET_H - c[1], r, layer.svg_style()).as_str());
+            }
+            DrawItem::Text(p, h, s, _layer) => {
+                ret.push_str(format!("<text x=\"{:.3}\" y=\"{:.3}\" font-family=\"monospace\" font-size=\"{:.2}\">{}</text>\n", p[0], SHEET_H - p[1], h * 1.4, xml_text(s)).as_str());
+            }
+        });
+        ret.push_str("</svg>\n");
+        ret
+    }
+    //R12 ASCII, widths go into POLYLINE so the tube silhouette survives
+    pub fn to_dxf(&self) -> String {
+        let mut ret = String::new();
+        let mut pair = |code: i32, v: String| {
+            ret.push_str(format!("{}\n{}\n", code, v).as_str());
+        };
+        pair(0, "SECTION".to_string());
+        pair(2, "HEADER".to_string());
+        pair(9, "$INSUNITS".to_string());
+        pair(70, "4".to_string());
+        pair(0, "ENDSEC".to_string());
+        pair(0, "SECTION".to_string());
+        pair(2, "TABLES".to_string());
+        pair(0, "TABLE".to_string());
+        pair(2, "LTYPE".to_string());
+        pair(70, DXF_LINETYPES.len().to_string());
+        DXF_LINETYPES.iter().for_each(|(name, description, dashes)| {
+            pair(0, "LTYPE".to_string());
+            pair(2, name.to_string());
+            pair(70, "0".to_string());
+            pair(3, description.to_string());
+            pair(72, "65".to_string());
+            pair(73, dashes.len().to_string());
+            pair(40, format!("{:.4}", dashes.iter().map(|d| d.abs()).sum::<f64>()));
+            dashes.iter().for_each(|d| pair(49, format!("{:.4}", d)));
+        });
+        pair(0, "ENDTAB".to_string());
+        pair(0, "TABLE".to_string());
+        pair(2, "LAYER".to_string());
+        let layers = [Layer::Frame, Layer::Tube, Layer::Center, Layer::Dim, Layer::Text];
+        pair(70, layers.len().to_string());
+        layers.iter().for_each(|l| {
+            pair(0, "LAYER".to_string());
+            pair(2, l.name().to_string());
+            pair(70, "0".to_string());
+            pair(62, l.aci().to_string());
+            pair(6, l.linetype().to_string());
+        });
+        pair(0, "ENDTAB".to_string());
+        pair(0, "ENDSEC".to_string());
+        pair(0, "SECTION".to_string());
+        pair(2, "ENTITIES".to_string());
+        self.items.iter().for_each(|item| match item {
+            DrawItem::Polyline(pts, width, layer) => {
+                if (pts.len() == 2 && *width <= 0.0) {
+                    pair(0, "LINE".to_string());
+                    pair(8, layer.name().to_string());
+                    pair(10, format!("{:.4}", pts[0][0]));
+                    pair(20, format!("{:.4}", pts[0][1]));
+                    pair(11, format!("{:.4}", pts[1][0]));
+                    pair(21, format!("{:.4}", pts[1][1]));
+                } else {
+                    pair(0, "POLYLINE".to_string());
+                    pair(8, layer.name().to_string());
+                    pair(66, "1".to_string());
+                    pair(10, "0.0".to_string());
+                    pair(20, "0.0".to_string());
+                    pair(40, format!("{:.4}", width));
+                    pair(41, format!("{:.4}", width));
+                    pts.iter().for_each(|p| {
+                        pair(0, "VERTEX".to_string());
+                        pair(8, layer.name().to_string());
+                        pair(10, format!("{:.4}", p[0]));
+                        pair(20, format!("{:.4}", p[1]));
+                    });
+                    pair(0, "SEQEND".to_string());
+                    pair(8, layer.name().to_string());
+                }
+            }
+            DrawItem::Circle(c, r, layer) => {
+                pair(0, "CIRCLE".to_string());
+                pair(8, layer.name().to_string());
+                pair(10, format!("{:.4}", c[0]));
+                pair(20, format!("{:.4}", c[1]));
+                pair(40, format!("{:.4}", r));
+            }
+            DrawItem::Text(p, h, s, layer) => {
+                pair(0, "TEXT".to_string());
+                pair(8, layer.name().to_string());
+                pair(10, format!("{:.4}", p[0]));
+                pair(20, format!("{:.4}", p[1]));
+                pair(40, format!("{:.4}", h));
+                pair(1, s.clone());
+            }
+        });
+        pair(0, "ENDSEC".to_string());
+        pair(0, "EOF".to_string());
+        ret
+    }
+}
+
+fn xml_text(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[derive(Debug, Clone, Copy)]
+enum View {
+    Front,
+    Side,
+    Top,
+    Iso,
+}
+impl View {
+    fn project(&self, p: &Point3) -> [f64; 2] {
+        match self {
+            View::Front => [p.x, p.z],
+            View::Side => [p.y, p.z],
+            View::Top => [p.x, p.y],
+            View::Iso => [(p.x - p.y) * ISO_COS, (p.x + p.y) * 0.5 + p.z],
+        }
+    }
+    fn title(&self) -> &'static str {
+        match self {
+            View::Front => "FRONT (XZ)",
+            View::Side => "SIDE (YZ)",
+            View::Top => "TOP (XY)",
+            View::Iso => "ISOMETRIC",
+        }
+    }
+}
+
+fn bounds(pts: &Vec<[f64; 2]>) -> ([f64; 2], [f64; 2]) {
+    let mut min = [f64::MAX; 2];
+    let mut max = [f64::MIN; 2];
+    pts.iter().for_each(|p| {
+        for i in 0..2 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    });
+    (min, max)
+}
+
+//Largest standard scale that fits every view in its cell
+fn fit_scale(views: &Vec<Vec<[f64; 2]>>, cell_w: f64, cell_h: f64, od: f64) -> f64 {
+    let mut s = f64::MAX;
+    views.iter().for_each(|pts| {
+        let (min, max) = bounds(pts);
+        let w = max[0] - min[0] + od;
+        let h = max[1] - min[1] + od;
+        if (w > 0.0) {
+            s = s.min((cell_w - 2.0 * DIM_OFFSET - 10.0) / w);
+        }
+        if (h > 0.0) {
+            s = s.min((cell_h - 2.0 * DIM_OFFSET - 10.0) / h);
+        }
+    });
+    STANDARD_SCALES.iter().cloned().find(|std| *std <= s).unwrap_or(STANDARD_SCALES[STANDARD_SCALES.len() - 1])
+}
+
+fn scale_text(s: f64) -> String {
+    if (s >= 1.0) {
+        format!("{}:1", s)
+    } else {
+        format!("1:{}", (1.0 / s).round())
+    }
+}
+
+//Linear dimension between two sheet points, the dimension line is moved by offset along normal
+fn dimension(d: &mut Drawing, p0: [f64; 2], p1: [f64; 2], normal: [f64; 2], offset: f64, value: f64) {
+    let q0 = [p0[0] + normal[0] * offset, p0[1] + normal[1] * offset];
+    let q1 = [p1[0] + normal[0] * offset, p1[1] + normal[1] * offset];
+    d.line(p0, [q0[0] + normal[0] * 1.5, q0[1] + normal[1] * 1.5], Layer::Dim);
+    d.line(p1, [q1[0] + normal[0] * 1.5, q1[1] + normal[1] * 1.5], Layer::Dim);
+    d.line(q0, q1, Layer::Dim);
+    //architectural ticks, they read the same in every viewer
+    [q0, q1].iter().for_each(|q| {
+        d.line([q[0] - 1.0, q[1] - 1.0], [q[0] + 1.0, q[1] + 1.0], Layer::Dim);
+    });
+    let mid = [(q0[0] + q1[0]) / 2.0, (q0[1] + q1[1]) / 2.0];
+    let label = format!("{:.1}", value);
+    let w = label.len() as f64 * TEXT_H * 0.6;
+    if (normal[1] != 0.0) {
+        d.text([mid[0] - w / 2.0, mid[1] + normal[1] * 1.0 + if (normal[1] < 0.0) { -TEXT_H } else { 0.5 }], TEXT_H, label.as_str(), Layer::Text);
+    } else {
+        d.text([mid[0] + normal[0] * 1.0 - if (normal[0] < 0.0) { w } else { 0.0 }, mid[1] - TEXT_H / 2.0], TEXT_H, label.as_str(), Layer::Text);
+    }
+}
+
+fn draw_view(d: &mut Drawing, view: View, cell: [f64; 4], scale: f64, centerline: &Vec<Point3>, xyz: &Vec<Point3>, bend_ids: &Vec<i32>, od: f64) {
+    let [x0, y0, x1, y1] = cell;
+    let line2d: Vec<[f64; 2]> = centerline.iter().map(|p| view.project(p)).collect();
+    let xyz2d: Vec<[f64; 2]> = xyz.iter().map(|p| view.project(p)).collect();
+    let (min, max) = bounds(&line2d);
+    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+    let origin = [(x0 + x1) / 2.0, (y0 + y1) / 2.0 + TEXT_H];
+    let to_sheet = |p: &[f64; 2]| -> [f64; 2] { [origin[0] + (p[0] - center[0]) * scale, origin[1] + (p[1] - center[1]) * scale] };
+    let sheet_line: Vec<[f64; 2]> = line2d.iter().map(|p| to_sheet(p)).collect();
+    d.items.push(DrawItem::Polyline(sheet_line.clone(), od * scale, Layer::Tube));
+    d.items.push(DrawItem::Polyline(sheet_line, 0.0, Layer::Center));
+    let sheet_xyz: Vec<[f64; 2]> = xyz2d.iter().map(|p| to_sheet(p)).collect();
+    d.items.push(DrawItem::Polyline(sheet_xyz.clone(), 0.0, Layer::Dim));
+    //balloons with the bend id at the intersection points, the ends are P0 and Pn
+    sheet_xyz.iter().enumerate().for_each(|(i, p)| {
+        let label = if (i == 0 || i == sheet_xyz.len() - 1) { format!("P{}", i) } else { format!("{}", bend_ids[i - 1]) };
+        let c = [p[0] + 3.0, p[1] + 3.0];
+        d.items.push(DrawItem::Circle(c, 2.2, Layer::Dim));
+        d.text([c[0] - label.len() as f64 * TEXT_H * 0.3, c[1] - TEXT_H * 0.4], TEXT_H * 0.8, label.as_str(), Layer::Text);
+    });
+    match view {
+        View::Iso => {}
+        _ => {
+            let smin = to_sheet(&min);
+            let smax = to_sheet(&max);
+            if (max[0] - min[0] > 0.01) {
+                dimension(d, [smin[0], smin[1]], [smax[0], smin[1]], [0.0, -1.0], DIM_OFFSET + od * scale / 2.0, max[0] - min[0]);
+            }
+            if (max[1] - min[1] > 0.01) {
+                dimension(d, [smin[0], smin[1]], [smin[0], smax[1]], [-1.0, 0.0], DIM_OFFSET + od * scale / 2.0, max[1] - min[1]);
+            }
+        }
+    }
+    d.text([x0 + 2.0, y0 + 2.0], TEXT_H, view.title(), Layer::Text);
+    d.rect(x0, y0, x1, y1, Layer::Frame);
+}
+
+pub fn draw_table(d: &mut Drawing, x: f64, top: f64, widths: &[f64], header: &[&str], rows: &Vec<Vec<String>>) -> f64 {
+    let total_w: f64 = widths.iter().sum();
+    let mut y = top;
+    let draw_row = |d: &mut Drawing, cells: Vec<String>, y: f64| {
+        let mut cx = x;
+        cells.iter().zip(widths.iter()).for_each(|(c, w)| {
+            d.text([cx + 1.0, y - ROW_H + 1.0], TEXT_H, c.as_str(), Layer::Text);
+            cx = cx + w;
+        });
+        d.line([x, y - ROW_H], [x + total_w, y - ROW_H], Layer::Frame);
+    };
+    draw_row(d, header.iter().map(|h| h.to_string()).collect(), y);
+    y = y - ROW_H;
+    rows.iter().for_each(|r| {
+        draw_row(d, r.clone(), y);
+        y = y - ROW_H;
+    });
+    d.rect(x, y, x + total_w, top, Layer::Frame);
+    let mut cx = x;
+    widths.iter().take(widths.len() - 1).for_each(|w| {
+        cx = cx + w;
+        d.line([cx, y], [cx, top], Layer::Frame);
+    });
+    y
+}
+
+//Three orthographic views and an isometric of the program centerline, LRA and XYZ tables and the title block
+pub fn program_drawing(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, info: &DrawingInfo) -> Drawing {
+    let mut d = Drawing::new();
+    d.rect(MARGIN / 2.0, MARGIN / 2.0, SHEET_W - MARGIN / 2.0, SHEET_H - MARGIN / 2.0, Layer::Frame);
+    let (cut_len, pipe_radius) = LRACLR::total_len_out_d(lraclr_arr);
+    let od = pipe_radius * 2.0;
+
+    if (!lraclr_arr.is_empty()) {
+        let (cyls, tors) = cnc_to_poly(lraclr_arr, v_up_orign);
+        let centerline: Vec<Point3> = tube_centerline(&cyls, &tors, pipe_radius.max(1.0)).iter().map(|(p, _)| p.clone()).collect();
+        let xyz = intersection_points(lraclr_arr, v_up_orign);
+        let bend_ids: Vec<i32> = lraclr_arr.iter().map(|lra| lra.id2).collect();
+        let cell_w = (VIEWS_W - MARGIN) / 2.0;
+        let cell_h = (SHEET_H - 2.0 * MARGIN) / 2.0;
+        let cells = [
+            (View::Front, [MARGIN, MARGIN + cell_h, MARGIN + cell_w, SHEET_H - MARGIN]),
+            (View::Side, [MARGIN + cell_w, MARGIN + cell_h, VIEWS_W, SHEET_H - MARGIN]),
+            (View::Top, [MARGIN, MARGIN, MARGIN + cell_w, MARGIN + cell_h]),
+            (View::Iso, [MARGIN + cell_w, MARGIN, VIEWS_W, MARGIN + cell_h]),
+        ];
+        let ortho: Vec<Vec<[f64; 2]>> = cells.iter().take(3).map(|(v, _)| centerline.iter().map(|p| v.project(p)).collect()).collect();
+        let scale = fit_scale(&ortho, cell_w, cell_h, od);
+        let iso: Vec<Vec<[f64; 2]>> = vec![centerline.iter().map(|p| View::Iso.project(p)).collect()];
+        let iso_scale = fit_scale(&iso, cell_w, cell_h, od);
+        cells.iter().for_each(|(v, cell)| {
+            let s = match v {
+                View::Iso => iso_scale,
+                _ => scale,
+            };
+            draw_view(&mut d, *v, *cell, s, &centerline, &xyz, &bend_ids, od);
+        });
+        d.text([MARGIN + 2.0, SHEET_H - MARGIN - 2.0 - TEXT_H], TEXT_H, format!("SCALE {}  ISO {}", scale_text(scale), scale_text(iso_scale)).as_str(), Layer::Text);
+
+        let x = VIEWS_W + 3.0;
+        let lra_rows: Vec<Vec<String>> = to_output(lraclr_arr, &info.output)
+            .iter()
+            .map(|lra| vec![format!("{}/{}", lra.id1, lra.id2), format!("{:.2}", lra.l), format!("{:.2}", lra.r), format!("{:.2}", lra.a), format!("{:.1}", lra.clr)])
+            .collect();
+        let y = draw_table(&mut d, x, SHEET_H - MARGIN, &[20.0, 24.0, 24.0, 24.0, 20.0], &["ID", info.output.feed_name(), info.output.rotation_name(), "A", "CLR"], &lra_rows);
+        let xyz_rows: Vec<Vec<String>> = xyz.iter().enumerate().map(|(i, p)| vec![format!("P{}", i), format!("{:.2}", p.x), format!("{:.2}", p.y), format!("{:.2}", p.z)]).collect();
+        draw_table(&mut d, x, y - ROW_H, &[16.0, 32.0, 32.0, 32.0], &["PT", "X", "Y", "Z"], &xyz_rows);
+    }
+
+    //title block
+    let x = VIEWS_W + 3.0;
+    let rows: Vec<Vec<String>> = vec![
+        vec!["PART".to_string(), info.part_number.clone()],
+        vec!["MATERIAL".to_string(), info.material.clone()],
+        vec!["OD".to_string(), format!("{:.2}", od)],
+        vec!["WALL".to_string(), format!("{:.2}", info.wall)],
+        vec!["CUT LENGTH".to_string(), format!("{:.1}", cut_len)],
+        vec!["BENDS".to_string(), format!("{}", lraclr_arr.iter().filter(|lra| lra.clr > 0.0).count())],
+        vec!["NOTES".to_string(), info.notes.chars().take(24).collect()],
+    ];
+    let top = MARGIN + ROW_H * (rows.len() + 1) as f64;
+    draw_table(&mut d, x, top, &[32.0, 80.0], &["FREETUBEW", "BENDING DRAWING"], &rows);
+    d
+}
@@ -0,0 +1,221 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::output::wrap_rotation;
+use cgmath::{Deg, Rad};
+use std::fmt::{Display, Formatter};
+
+//Programs kept for undo, the oldest is dropped first
+const EDIT_HISTORY: usize = 100;
+
+//One change of the program. Rows are indexed as in the program, row i is the straight before bend i,
+//the rotation before it and the bend itself. The last row is the end straight and has no bend
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditOp {
+    SetStraight { index: usize, l: f64 },
+    SetRotation { index: usize, r: f64 },
+    SetAngle { index: usize, a: f64 },
+    SetClr { index: usize, clr: f64 },
+    //new straight and bend in front of row index
+    InsertBend { index: usize, l: f64, r: f64, a: f64, clr: f64 },
+    //the bend is straightened, its arc goes to the straight so the cut length stays
+    DeleteBend { index: usize },
+}
+impl EditOp {
+    //[kind, index, values...], kind 0 straight [l], 1 rotation [r], 2 angle [a], 3 clr [clr], 4 insert [l, r, a, clr], 5 delete
+    pub fn from_f32vec(vec: &Vec<f32>) -> Option<EditOp> {
+        let kind = vec.get(0)?.round() as i32;
+        let index = vec.get(1)?.round().max(0.0) as usize;
+        let v = |i: usize| -> Option<f64> { vec.get(i).map(|v| v.clone() as f64) };
+        match kind {
+            0 => Some(EditOp::SetStraight { index: index, l: v(2)? }),
+            1 => Some(EditOp::SetRotation { index: index, r: v(2)? }),
+            2 => Some(EditOp::SetAngle { index: index, a: v(2)? }),
+            3 => Some(EditOp::SetClr { index: index, clr: v(2)? }),
+            4 => Some(EditOp::InsertBend { index: index, l: v(2)?, r: v(3)?, a: v(4)?, clr: v(5)? }),
+            5 => Some(EditOp::DeleteBend { index: index }),
+            _ => None,
+        }
+    }
+    pub fn index(&self) -> usize {
+        match self {
+            EditOp::SetStraight { index, .. } => *index,
+            EditOp::SetRotation { index, .. } => *index,
+            EditOp::SetAngle { index, .. } => *index,
+            EditOp::SetClr { index, .. } => *index,
+            EditOp::InsertBend { index, .. } => *index,
+            EditOp::DeleteBend { index } => *index,
+        }
+    }
+}
+impl Display for EditOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditOp::SetStraight { index, l } => write!(f, "row {} L {:.2}", index, l),
+            EditOp::SetRotation { index, r } => write!(f, "row {} R {:.2}", index, r),
+            EditOp::SetAngle { index, a } => write!(f, "row {} A {:.2}", index, a),
+            EditOp::SetClr { index, clr } => write!(f, "row {} CLR {:.2}", index, clr),
+            EditOp::InsertBend { index, l, r, a, clr } => write!(f, "insert at {} L {:.2} R {:.2} A {:.2} CLR {:.2}", index, l, r, a, clr),
+            EditOp::DeleteBend { index } => write!(f, "delete bend {}", index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditError {
+    Empty,
+    IndexOutOfRange(usize),
+    //the end straight has no bend to change or delete
+    NotABend(usize),
+    BadLength(f64),
+    BadRotation(f64),
+    BadAngle(f64),
+    BadClr(f64),
+    NothingToUndo,
+    NothingToRedo,
+}
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::Empty => write!(f, "no program"),
+            EditError::IndexOutOfRange(i) => write!(f, "row {} does not exist", i),
+            EditError::NotABend(i) => write!(f, "row {} has no bend", i),
+            EditError::BadLength(v) => write!(f, "length {} is not a non-negative number", v),
+            EditError::BadRotation(v) => write!(f, "rotation {} is not a number", v),
+            EditError::BadAngle(v) => write!(f, "bend angle {} is not within 0..180", v),
+            EditError::BadClr(v) => write!(f, "clr {} is not positive", v),
+            EditError::NothingToUndo => write!(f, "nothing to undo"),
+            EditError::NothingToRedo => write!(f, "nothing to redo"),
+        }
+    }
+}
+
+fn check_length(l: f64) -> Result<f64, EditError> {
+    if (l >= 0.0 && l.is_finite()) { Ok(l) } else { Err(EditError::BadLength(l)) }
+}
+fn check_rotation(r: f64) -> Result<f64, EditError> {
+    if (r.is_finite()) { Ok(wrap_rotation(r)) } else { Err(EditError::BadRotation(r)) }
+}
+fn check_angle(a: f64) -> Result<f64, EditError> {
+    if (a > 0.0 && a <= 180.0) { Ok(a) } else { Err(EditError::BadAngle(a)) }
+}
+fn check_clr(clr: f64) -> Result<f64, EditError> {
+    if (clr > 0.0 && clr.is_finite()) { Ok(clr) } else { Err(EditError::BadClr(clr)) }
+}
+
+//The edited program, the input is left as it is
+pub fn apply_edit(lraclr_arr: &Vec<LRACLR>, op: &EditOp) -> Result<Vec<LRACLR>, EditError> {
+    if (lraclr_arr.is_empty()) {
+        return Err(EditError::Empty);
+    }
+    let index = op.index();
+    let last = lraclr_arr.len() - 1;
+    if (index > last) {
+        return Err(EditError::IndexOutOfRange(index));
+    }
+    let is_bend = lraclr_arr[index].clr > 0.0;
+    let mut ret = lraclr_arr.clone();
+    match op {
+        EditOp::SetStraight { l, .. } => ret[index].l = check_length(*l)?,
+        EditOp::SetRotation { r, .. } => {
+            if (!is_bend) {
+                return Err(EditError::NotABend(index));
+            }
+            ret[index].r = check_rotation(*r)?;
+        }
+        EditOp::SetAngle { a, .. } => {
+            if (!is_bend) {
+                return Err(EditError::NotABend(index));
+            }
+            ret[index].a = check_angle(*a)?;
+            ret[index].lt = arc_len(&ret[index]);
+        }
+        EditOp::SetClr { clr, .. } => {
+            if (!is_bend) {
+                return Err(EditError::NotABend(index));
+            }
+            ret[index].clr = check_clr(*clr)?;
+            ret[index].lt = arc_len(&ret[index]);
+        }
+        EditOp::InsertBend { l, r, a, clr, .. } => {
+            let mut row = lraclr_arr[index].clone();
+            row.l = check_length(*l)?;
+            row.r = check_rotation(*r)?;
+            row.a = check_angle(*a)?;
+            row.clr = check_clr(*clr)?;
+            row.lt = arc_len(&row);
+            ret.insert(index, row);
+            renumber(&mut ret);
+        }
+        EditOp::DeleteBend { .. } => {
+            if (!is_bend || index == last) {
+                return Err(EditError::NotABend(index));
+            }
+            //the next bend plane was counted from the deleted one
+            let removed = ret.remove(index);
+            ret[index].l = removed.l + removed.lt + ret[index].l;
+            ret[index].r = wrap_rotation(removed.r + ret[index].r);
+            renumber(&mut ret);
+        }
+    }
+    Ok(ret)
+}
+
+//Arc length of a changed bend, unchanged bends keep the one measured on the model
+fn arc_len(lra: &LRACLR) -> f64 {
+    Rad::from(Deg(lra.a)).0 * lra.clr
+}
+
+//Straights and bends are numbered one after the other as the mesh ids are
+fn renumber(lraclr_arr: &mut Vec<LRACLR>) {
+    lraclr_arr.iter_mut().enumerate().for_each(|(i, lra)| {
+        lra.id1 = (i * 2) as i32;
+        lra.id2 = (i * 2 + 1) as i32;
+    });
+}
+
+//Undo and redo over whole programs, small enough to copy at the sizes a tube program has
+#[derive(Debug, Clone)]
+pub struct ProgramEditor {
+    undo: Vec<Vec<LRACLR>>,
+    redo: Vec<Vec<LRACLR>>,
+}
+impl ProgramEditor {
+    pub fn default() -> Self {
+        Self { undo: vec![], redo: vec![] }
+    }
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    //The program before a successful edit becomes the next undo step and drops what could be redone
+    pub fn push(&mut self, lraclr_arr: &Vec<LRACLR>) {
+        self.undo.push(lraclr_arr.clone());
+        if (self.undo.len() > EDIT_HISTORY) {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+    pub fn undo(&mut self, lraclr_arr: &Vec<LRACLR>) -> Result<Vec<LRACLR>, EditError> {
+        match self.undo.pop() {
+            None => Err(EditError::NothingToUndo),
+            Some(prev) => {
+                self.redo.push(lraclr_arr.clone());
+                Ok(prev)
+            }
+        }
+    }
+    pub fn redo(&mut self, lraclr_arr: &Vec<LRACLR>) -> Result<Vec<LRACLR>, EditError> {
+        match self.redo.pop() {
+            None => Err(EditError::NothingToRedo),
+            Some(next) => {
+                self.undo.push(lraclr_arr.clone());
+                Ok(next)
+            }
+        }
+    }
+}
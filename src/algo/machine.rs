@@ -0,0 +1,219 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::collision::Obstacle;
+use crate::algo::cycle::CycleParameters;
+use crate::algo::output::OutputMode;
+use cgmath::num_traits::abs;
+use std::fmt::{Display, Formatter};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+const CLR_TOLE: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct MachineProfile {
+    pub name: String,
+    pub min_od: f64,
+    pub max_od: f64,
+    pub clrs: Vec<f64>,
+    pub clr_tole: f64,
+    pub max_bend_angle: f64,
+    pub min_clamp_len: f64,
+    pub min_stright_len: f64,
+    pub max_feed_len: f64,
+    pub min_rotation: f64,
+    pub max_rotation: f64,
+    pub obstacles: Vec<Obstacle>,
+    pub cycle: CycleParameters,
+    //how the controller wants the feed and rotation columns
+    pub output: OutputMode,
+}
+impl MachineProfile {
+    pub fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            min_od: 6.0,
+            max_od: 80.0,
+            clrs: vec![],
+            clr_tole: CLR_TOLE,
+            max_bend_angle: 180.0,
+            min_clamp_len: 0.0,
+            min_stright_len: 0.0,
+            max_feed_len: 6000.0,
+            min_rotation: -360.0,
+            max_rotation: 360.0,
+            obstacles: vec![],
+            cycle: CycleParameters::default(),
+            output: OutputMode::default(),
+        }
+    }
+    pub fn is_clr_available(&self, clr: f64) -> bool {
+        if (self.clrs.is_empty()) {
+            true
+        } else {
+            self.clrs.iter().any(|c| abs(c - clr) <= self.clr_tole)
+        }
+    }
+    //Die closest to the clr, none when the machine has no dies set
+    pub fn nearest_clr(&self, clr: f64) -> Option<f64> {
+        self.clrs.iter().cloned().min_by(|a, b| abs(a - clr).total_cmp(&abs(b - clr)))
+    }
+    //[min_od, max_od, max_bend_angle, min_clamp_len, min_stright_len, max_feed_len, min_rotation, max_rotation, clr_tole, clr0, clr1, ...]
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => self.min_od = v.clone() as f64,
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.max_od = v.clone() as f64,
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.max_bend_angle = v.clone() as f64,
+        }
+        match vec.get(3) {
+            None => {}
+            Some(v) => self.min_clamp_len = v.clone() as f64,
+        }
+        match vec.get(4) {
+            None => {}
+            Some(v) => self.min_stright_len = v.clone() as f64,
+        }
+        match vec.get(5) {
+            None => {}
+            Some(v) => self.max_feed_len = v.clone() as f64,
+        }
+        match vec.get(6) {
+            None => {}
+            Some(v) => self.min_rotation = v.clone() as f64,
+        }
+        match vec.get(7) {
+            None => {}
+            Some(v) => self.max_rotation = v.clone() as f64,
+        }
+        match vec.get(8) {
+            None => {}
+            Some(v) => self.clr_tole = v.clone() as f64,
+        }
+        if (vec.len() > 9) {
+            self.clrs = vec.iter().skip(9).map(|c| c.clone() as f64).collect();
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        let mut ret: Vec<f32> = Vec::from([
+            self.min_od as f32,
+            self.max_od as f32,
+            self.max_bend_angle as f32,
+            self.min_clamp_len as f32,
+            self.min_stright_len as f32,
+            self.max_feed_len as f32,
+            self.min_rotation as f32,
+            self.max_rotation as f32,
+            self.clr_tole as f32,
+        ]);
+        self.clrs.iter().for_each(|c| ret.push(c.clone() as f32));
+        ret
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViolationKind {
+    OdTooSmall = 0,
+    OdTooLarge = 1,
+    ClrNotAvailable = 2,
+    BendAngleTooLarge = 3,
+    ClampTooShort = 4,
+    StrightTooShort = 5,
+    FeedTooLong = 6,
+    RotationOutOfRange = 7,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub id1: i32,
+    pub id2: i32,
+    pub kind: ViolationKind,
+    pub value: f64,
+    pub limit: f64,
+}
+impl Violation {
+    //Mesh id of the segment responsible for the violation, straights are id1 and bends are id2
+    pub fn mesh_id(&self) -> i32 {
+        match self.kind {
+            ViolationKind::ClampTooShort | ViolationKind::StrightTooShort | ViolationKind::FeedTooLong | ViolationKind::OdTooSmall | ViolationKind::OdTooLarge => self.id1,
+            ViolationKind::ClrNotAvailable | ViolationKind::BendAngleTooLarge | ViolationKind::RotationOutOfRange => self.id2,
+        }
+    }
+    //Row fields 1 id1, 2 id2, 3 kind, 4 value, 5 limit
+    pub fn to_wire(violations: &Vec<Violation>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Violations);
+        violations.iter().for_each(|v| {
+            let mut row = WireRecord::new();
+            row.push(1, v.id1 as f64);
+            row.push(2, v.id2 as f64);
+            row.push(3, v.kind as i32 as f64);
+            row.push(4, v.value);
+            row.push(5, v.limit);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} {:?} value {} limit {}", self.id1, self.id2, self.kind, self.value, self.limit)
+    }
+}
+
+pub fn validate_lraclr(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile) -> Vec<Violation> {
+    let mut ret: Vec<Violation> = vec![];
+    let last_index = lraclr_arr.len() as i64 - 1;
+    let mut prev_arc: f64 = 0.0;
+    let mut rotation: f64 = 0.0;
+    lraclr_arr.iter().enumerate().for_each(|(i, lra)| {
+        let od = lra.pipe_radius * 2.0;
+        let is_bend = lra.clr > 0.0;
+        let mut push = |kind: ViolationKind, value: f64, limit: f64| {
+            ret.push(Violation {
+                id1: lra.id1,
+                id2: lra.id2,
+                kind: kind,
+                value: value,
+                limit: limit,
+            });
+        };
+        if (od < machine.min_od) {
+            push(ViolationKind::OdTooSmall, od, machine.min_od);
+        }
+        if (od > machine.max_od) {
+            push(ViolationKind::OdTooLarge, od, machine.max_od);
+        }
+        if (i == 0) {
+            if (lra.l < machine.min_clamp_len) {
+                push(ViolationKind::ClampTooShort, lra.l, machine.min_clamp_len);
+            }
+        } else if (i as i64 != last_index && lra.l < machine.min_stright_len) {
+            push(ViolationKind::StrightTooShort, lra.l, machine.min_stright_len);
+        }
+        //the clamp moves back after every bend, one feed is the arc of the bend before and the straight
+        let feed = prev_arc + lra.l;
+        if (feed > machine.max_feed_len) {
+            push(ViolationKind::FeedTooLong, feed, machine.max_feed_len);
+        }
+        prev_arc = lra.lt;
+        if (is_bend) {
+            if (!machine.is_clr_available(lra.clr)) {
+                push(ViolationKind::ClrNotAvailable, lra.clr, machine.nearest_clr(lra.clr).unwrap_or(lra.clr));
+            }
+            if (lra.a > machine.max_bend_angle) {
+                push(ViolationKind::BendAngleTooLarge, lra.a, machine.max_bend_angle);
+            }
+            rotation = rotation + lra.r;
+            if (rotation < machine.min_rotation) {
+                push(ViolationKind::RotationOutOfRange, rotation, machine.min_rotation);
+            } else if (rotation > machine.max_rotation) {
+                push(ViolationKind::RotationOutOfRange, rotation, machine.max_rotation);
+            }
+        }
+    });
+    ret
+}
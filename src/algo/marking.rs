@@ -0,0 +1,118 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::drawing::{draw_table, DrawItem, Drawing, Layer, MARGIN, ROW_H, SHEET_H, SHEET_W, TEXT_H};
+use crate::algo::output::{normalize_angle, to_output, FeedMode, OutputMode, RotationMode, RotationSense};
+use std::fmt::{Display, Formatter};
+
+const STRIP_H: f64 = 16.0;
+const STRIP_TOP: f64 = SHEET_H - MARGIN - 40.0;
+
+//One bend as it is marked on the straight tube. Angles are clockwise from the reference line
+//looking from the start of the tube to its end
+#[derive(Debug, Clone)]
+pub struct BendMark {
+    pub id: i32,
+    pub start: f64,
+    pub end: f64,
+    //rotation of the tube before this bend and the sum of all rotations so far
+    pub rotation: f64,
+    pub total_rotation: f64,
+    //where the inside of the bend lies around the tube
+    pub inside: f64,
+    pub angle: f64,
+    pub clr: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarkingLayout {
+    pub total_len: f64,
+    pub od: f64,
+    pub marks: Vec<BendMark>,
+}
+impl MarkingLayout {
+    //Positions are measured from the start of the stock the same way as the unbent length,
+    //every straight adds l and every bend adds its arc length lt
+    pub fn from_lraclr(lraclr_arr: &Vec<LRACLR>) -> Self {
+        let (total_len, pipe_radius) = LRACLR::total_len_out_d(lraclr_arr);
+        //the marks are the program in clockwise form, both rotations in the same sense
+        let mode = OutputMode { rotation: RotationMode::Absolute, feed: FeedMode::Absolute, sense: RotationSense::Cw };
+        let absolute = to_output(lraclr_arr, &mode);
+        let mode = OutputMode { rotation: RotationMode::Incremental, feed: FeedMode::Absolute, sense: RotationSense::Cw };
+        let incremental = to_output(lraclr_arr, &mode);
+        let mut marks: Vec<BendMark> = vec![];
+        lraclr_arr.iter().zip(absolute.iter()).zip(incremental.iter()).for_each(|((lra, abs_lra), inc_lra)| {
+            if (lra.clr > 0.0) {
+                marks.push(BendMark {
+                    id: lra.id2,
+                    start: abs_lra.l,
+                    end: abs_lra.l + lra.lt,
+                    rotation: inc_lra.r,
+                    total_rotation: abs_lra.r,
+                    inside: normalize_angle(abs_lra.r + 270.0),
+                    angle: lra.a,
+                    clr: lra.clr,
+                });
+            }
+        });
+        Self { total_len: total_len, od: pipe_radius * 2.0, marks: marks }
+    }
+
+    //A3 sheet with the tube as an unrolled strip, the reference line along its middle and the bend zones shaded,
+    //the table under it repeats the numbers
+    pub fn to_drawing(&self, part_number: &str) -> Drawing {
+        let mut d = Drawing::new();
+        d.rect(MARGIN / 2.0, MARGIN / 2.0, SHEET_W - MARGIN / 2.0, SHEET_H - MARGIN / 2.0, Layer::Frame);
+        d.text([MARGIN, SHEET_H - MARGIN - TEXT_H], TEXT_H * 1.4, format!("{} MARKING  OD {:.2}  CUT LENGTH {:.1}", part_number, self.od, self.total_len).as_str(), Layer::Text);
+        if (self.total_len <= 0.0) {
+            return d;
+        }
+        let x0 = MARGIN + 10.0;
+        let scale = (SHEET_W - 2.0 * x0) / self.total_len;
+        let to_x = |pos: f64| -> f64 { x0 + pos * scale };
+        let y_mid = STRIP_TOP - STRIP_H / 2.0;
+        d.rect(to_x(0.0), STRIP_TOP - STRIP_H, to_x(self.total_len), STRIP_TOP, Layer::Frame);
+        d.line([to_x(0.0), y_mid], [to_x(self.total_len), y_mid], Layer::Center);
+        d.text([to_x(0.0), y_mid + 0.5], TEXT_H * 0.8, "REF 0", Layer::Text);
+        self.marks.iter().enumerate().for_each(|(i, m)| {
+            let (xs, xe) = (to_x(m.start), to_x(m.end));
+            //bend zone as a thick band so it survives a monochrome print
+            d.items.push(DrawItem::Polyline(vec![[(xs + xe) / 2.0, STRIP_TOP - STRIP_H], [(xs + xe) / 2.0, STRIP_TOP]], (xe - xs).max(0.3), Layer::Tube));
+            d.line([xs, STRIP_TOP - STRIP_H - 3.0], [xs, STRIP_TOP + 3.0], Layer::Dim);
+            d.line([xe, STRIP_TOP - STRIP_H - 3.0], [xe, STRIP_TOP + 3.0], Layer::Dim);
+            //labels alternate above and below the strip so close bends do not overlap
+            let y = if (i % 2 == 0) { STRIP_TOP + 4.0 } else { STRIP_TOP - STRIP_H - 4.0 - 3.0 * TEXT_H };
+            d.text([xs, y + 2.0 * TEXT_H], TEXT_H, format!("B{} {:.1}-{:.1}", m.id, m.start, m.end).as_str(), Layer::Text);
+            d.text([xs, y + TEXT_H], TEXT_H, format!("ROT {:.1} ({:+.1})", m.total_rotation, m.rotation).as_str(), Layer::Text);
+            d.text([xs, y], TEXT_H, format!("A {:.1} IN {:.0}", m.angle, m.inside).as_str(), Layer::Text);
+        });
+        let rows: Vec<Vec<String>> = self
+            .marks
+            .iter()
+            .map(|m| {
+                vec![
+                    format!("{}", m.id),
+                    format!("{:.1}", m.start),
+                    format!("{:.1}", m.end),
+                    format!("{:.1}", m.rotation),
+                    format!("{:.1}", m.total_rotation),
+                    format!("{:.1}", m.angle),
+                    format!("{:.0}", m.inside),
+                    format!("{:.1}", m.clr),
+                ]
+            })
+            .collect();
+        let top = STRIP_TOP - STRIP_H - 4.0 * TEXT_H - 8.0;
+        let bottom = draw_table(&mut d, MARGIN, top, &[16.0, 26.0, 26.0, 24.0, 24.0, 22.0, 22.0, 20.0], &["BEND", "START", "END", "ROT", "ROT REF", "ANGLE", "INSIDE", "CLR"], &rows);
+        d.text([MARGIN, bottom - ROW_H], TEXT_H, "Angles in degrees clockwise from the reference line looking from the start of the tube", Layer::Text);
+        d
+    }
+}
+impl Display for MarkingLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let _ = writeln!(f, "cut length {:.1} OD {:.2} bends {}", self.total_len, self.od, self.marks.len());
+        let _ = writeln!(f, "bend start end rotation rotation_ref angle inside clr");
+        self.marks.iter().for_each(|m| {
+            let _ = writeln!(f, "{} {:.1} {:.1} {:.1} {:.1} {:.1} {:.0} {:.1}", m.id, m.start, m.end, m.rotation, m.total_rotation, m.angle, m.inside, m.clr);
+        });
+        Ok(())
+    }
+}
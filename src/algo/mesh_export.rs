@@ -0,0 +1,332 @@
+use crate::algo::{BendToro, MainCylinder};
+use crate::device::MeshVertex;
+use crate::utils::chunk::ChunkWriter;
+use cgmath::{InnerSpace, Vector3};
+
+pub const MESH_EXPORT_FILE: &str = "part";
+
+const STRAIGHT_RGBA: [f32; 4] = [0.27, 0.55, 0.75, 1.0];
+const BEND_RGBA: [f32; 4] = [0.85, 0.45, 0.35, 1.0];
+const TOOL_RGBA: [f32; 4] = [0.6, 0.6, 0.62, 1.0];
+pub const GLTF_STRAIGHT: usize = 0;
+pub const GLTF_BEND: usize = 1;
+pub const GLTF_TOOL: usize = 2;
+const GLTF_JSON: u32 = 0x4E4F534A;
+const GLTF_BIN: u32 = 0x004E4942;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshFormat {
+    Stl = 0,
+    Obj = 1,
+    Gltf = 2,
+}
+impl MeshFormat {
+    pub fn from_i32(v: i32) -> MeshFormat {
+        match v {
+            1 => MeshFormat::Obj,
+            2 => MeshFormat::Gltf,
+            _ => MeshFormat::Stl,
+        }
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MeshFormat::Stl => "stl",
+            MeshFormat::Obj => "obj",
+            MeshFormat::Gltf => "glb",
+        }
+    }
+}
+
+//Triangle soup of one segment, every three vertices make a triangle
+#[derive(Debug, Clone)]
+pub struct MeshGroup {
+    pub id: i32,
+    pub name: String,
+    pub is_bend: bool,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+impl MeshGroup {
+    fn new(id: i32, is_bend: bool) -> Self {
+        Self {
+            id: id,
+            name: if (is_bend) { format!("bend {}", id) } else { format!("straight {}", id) },
+            is_bend: is_bend,
+            positions: vec![],
+            normals: vec![],
+        }
+    }
+}
+
+//Splits the triangulation by vertex id, a straight cut by allowances gives several groups.
+//Triangles are rewound counter clockwise around the outward normal, the GPU buffers do not care about the winding
+pub fn mesh_groups(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> Vec<MeshGroup> {
+    let mut ret: Vec<MeshGroup> = vec![];
+    let mut push = |buffer: &Vec<MeshVertex>, is_bend: bool| {
+        buffer.chunks(3).filter(|tri| tri.len() == 3).for_each(|tri| {
+            let id = tri[0].id;
+            let group = match ret.iter().position(|g| g.id == id && g.is_bend == is_bend) {
+                None => {
+                    ret.push(MeshGroup::new(id, is_bend));
+                    ret.len() - 1
+                }
+                Some(i) => i,
+            };
+            let order = if (is_ccw(tri)) { [0, 1, 2] } else { [0, 2, 1] };
+            order.iter().for_each(|i| {
+                let v = &tri[*i];
+                ret[group].positions.push([v.position[0], v.position[1], v.position[2]]);
+                ret[group].normals.push([v.normal[0], v.normal[1], v.normal[2]]);
+            });
+        });
+    };
+    cyls.iter().for_each(|c| push(&c.step_vertex_buffer.buffer, false));
+    tors.iter().for_each(|t| push(&t.step_vertex_buffer.buffer, true));
+    ret
+}
+
+pub fn export_mesh(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>, format: MeshFormat, name: &str) -> Vec<u8> {
+    let groups = mesh_groups(cyls, tors);
+    match format {
+        MeshFormat::Stl => to_stl(&groups, name),
+        MeshFormat::Obj => to_obj(&groups, name),
+        MeshFormat::Gltf => to_glb(&groups, name),
+    }
+}
+
+//Binary STL has no groups, the segments follow each other in the program order
+pub fn to_stl(groups: &Vec<MeshGroup>, name: &str) -> Vec<u8> {
+    let mut w = ChunkWriter::new();
+    let mut header = format!("freetubew {}", name).into_bytes();
+    header.resize(80, b' ');
+    w.buf.extend_from_slice(&header);
+    let count: usize = groups.iter().map(|g| g.positions.len() / 3).sum();
+    w.write_u32(count as u32);
+    groups.iter().for_each(|g| {
+        g.positions.chunks(3).zip(g.normals.chunks(3)).for_each(|(p, n)| {
+            let n = facet_normal(p, n);
+            n.iter().for_each(|v| w.write_f32(*v));
+            p.iter().for_each(|v| {
+                w.write_f32(v[0]);
+                w.write_f32(v[1]);
+                w.write_f32(v[2]);
+            });
+            w.buf.extend_from_slice(&[0, 0]);
+        });
+    });
+    w.buf
+}
+
+pub fn to_obj(groups: &Vec<MeshGroup>, name: &str) -> Vec<u8> {
+    let mut ret = String::new();
+    ret.push_str(format!("# freetubew\no {}\n", name).as_str());
+    let mut base: usize = 1;
+    groups.iter().for_each(|g| {
+        ret.push_str(format!("g {}\n", g.name.replace(' ', "_")).as_str());
+        g.positions.iter().for_each(|p| ret.push_str(format!("v {} {} {}\n", p[0], p[1], p[2]).as_str()));
+        g.normals.iter().for_each(|n| ret.push_str(format!("vn {} {} {}\n", n[0], n[1], n[2]).as_str()));
+        (0..g.positions.len() / 3).for_each(|t| {
+            let i = base + t * 3;
+            ret.push_str(format!("f {}//{} {}//{} {}//{}\n", i, i, i + 1, i + 1, i + 2, i + 2).as_str());
+        });
+        base = base + g.positions.len();
+    });
+    ret.into_bytes()
+}
+
+//glTF 2.0 binary, one node per segment
+pub fn to_glb(groups: &Vec<MeshGroup>, name: &str) -> Vec<u8> {
+    let mut w = GlbWriter::new(name);
+    groups.iter().for_each(|g| {
+        let (position, normal) = w.add_group(g);
+        let mesh = w.add_mesh(g.name.as_str(), &vec![(position, normal, if (g.is_bend) { GLTF_BEND } else { GLTF_STRAIGHT })]);
+        w.add_node(g.name.as_str(), Some(mesh), None, format!("{{\"segment_id\":{}}}", g.id).as_str());
+    });
+    w.to_bytes()
+}
+
+//Buffers and JSON arrays of a glTF 2.0 binary. Every node hangs under a root node
+//that turns the Z up millimetres into Y up metres
+pub struct GlbWriter {
+    name: String,
+    bin: ChunkWriter,
+    views: Vec<String>,
+    accessors: Vec<String>,
+    meshes: Vec<String>,
+    nodes: Vec<String>,
+    samplers: Vec<String>,
+    channels: Vec<String>,
+}
+impl GlbWriter {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bin: ChunkWriter::new(),
+            views: vec![],
+            accessors: vec![],
+            meshes: vec![],
+            nodes: vec![],
+            samplers: vec![],
+            channels: vec![],
+        }
+    }
+    fn add_view(&mut self, data: &[f32], is_vertex: bool) -> usize {
+        let offset = self.bin.buf.len();
+        data.iter().for_each(|v| self.bin.write_f32(*v));
+        let target = if (is_vertex) { ",\"target\":34962" } else { "" };
+        self.views.push(format!("{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}{}}}", offset, data.len() * 4, target));
+        self.views.len() - 1
+    }
+    //POSITION and animation inputs need their bounds
+    pub fn add_vec3(&mut self, data: &Vec<[f32; 3]>, is_vertex: bool, is_bounded: bool) -> usize {
+        let flat: Vec<f32> = data.iter().flat_map(|v| v.iter().cloned()).collect();
+        let view = self.add_view(&flat, is_vertex);
+        let bounds = if (is_bounded) {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            data.iter().for_each(|p| {
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+            });
+            format!(",\"min\":[{},{},{}],\"max\":[{},{},{}]", min[0], min[1], min[2], max[0], max[1], max[2])
+        } else {
+            String::new()
+        };
+        self.accessors.push(format!("{{\"bufferView\":{},\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"{}}}", view, data.len(), bounds));
+        self.accessors.len() - 1
+    }
+    //Rotation outputs, quaternions as x, y, z, w
+    pub fn add_vec4(&mut self, data: &Vec<[f32; 4]>) -> usize {
+        let flat: Vec<f32> = data.iter().flat_map(|v| v.iter().cloned()).collect();
+        let view = self.add_view(&flat, false);
+        self.accessors.push(format!("{{\"bufferView\":{},\"componentType\":5126,\"count\":{},\"type\":\"VEC4\"}}", view, data.len()));
+        self.accessors.len() - 1
+    }
+    pub fn add_scalar(&mut self, data: &Vec<f32>) -> usize {
+        let view = self.add_view(data, false);
+        let min = data.iter().cloned().fold(f32::MAX, f32::min);
+        let max = data.iter().cloned().fold(f32::MIN, f32::max);
+        self.accessors.push(format!("{{\"bufferView\":{},\"componentType\":5126,\"count\":{},\"type\":\"SCALAR\",\"min\":[{}],\"max\":[{}]}}", view, data.len(), min, max));
+        self.accessors.len() - 1
+    }
+    pub fn add_group(&mut self, g: &MeshGroup) -> (usize, usize) {
+        let position = self.add_vec3(&g.positions, true, true);
+        let normals: Vec<[f32; 3]> = g.normals.iter().map(|n| normalized(n)).collect();
+        let normal = self.add_vec3(&normals, true, false);
+        (position, normal)
+    }
+    //Primitives are (POSITION, NORMAL, material)
+    pub fn add_mesh(&mut self, name: &str, primitives: &Vec<(usize, usize, usize)>) -> usize {
+        let prims: Vec<String> = primitives
+            .iter()
+            .map(|(position, normal, material)| format!("{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{}}},\"material\":{}}}", position, normal, material))
+            .collect();
+        self.meshes.push(format!("{{\"name\":\"{}\",\"primitives\":[{}]}}", json_text(name), prims.join(",")));
+        self.meshes.len() - 1
+    }
+    //Returns the node index, the root node takes index 0
+    pub fn add_node(&mut self, name: &str, mesh: Option<usize>, scale: Option<[f32; 3]>, extras: &str) -> usize {
+        let mut node = format!("{{\"name\":\"{}\"", json_text(name));
+        match mesh {
+            None => {}
+            Some(m) => node.push_str(format!(",\"mesh\":{}", m).as_str()),
+        }
+        match scale {
+            None => {}
+            Some(s) => node.push_str(format!(",\"scale\":[{},{},{}]", s[0], s[1], s[2]).as_str()),
+        }
+        if (!extras.is_empty()) {
+            node.push_str(format!(",\"extras\":{}", extras).as_str());
+        }
+        node.push('}');
+        self.nodes.push(node);
+        self.nodes.len()
+    }
+    //path is translation, rotation or scale
+    pub fn add_channel(&mut self, node: usize, path: &str, input: usize, output: usize, is_step: bool) {
+        let interpolation = if (is_step) { "STEP" } else { "LINEAR" };
+        self.samplers.push(format!("{{\"input\":{},\"output\":{},\"interpolation\":\"{}\"}}", input, output, interpolation));
+        self.channels.push(format!("{{\"sampler\":{},\"target\":{{\"node\":{},\"path\":\"{}\"}}}}", self.samplers.len() - 1, node, path));
+    }
+    pub fn to_bytes(self) -> Vec<u8> {
+        let children: Vec<String> = (1..=self.nodes.len()).map(|i| i.to_string()).collect();
+        let mut nodes = self.nodes;
+        let root = format!(
+            "{{\"name\":\"{}\",\"rotation\":[-0.70710677,0,0,0.70710677],\"scale\":[0.001,0.001,0.001],\"children\":[{}]}}",
+            json_text(&self.name),
+            children.join(",")
+        );
+        nodes.insert(0, root);
+        let material = |name: &str, rgba: &[f32; 4]| -> String {
+            format!(
+                "{{\"name\":\"{}\",\"doubleSided\":true,\"pbrMetallicRoughness\":{{\"baseColorFactor\":[{},{},{},{}],\"metallicFactor\":0.8,\"roughnessFactor\":0.4}}}}",
+                name, rgba[0], rgba[1], rgba[2], rgba[3]
+            )
+        };
+        let animations = if (self.channels.is_empty()) {
+            String::new()
+        } else {
+            format!(",\"animations\":[{{\"name\":\"bending\",\"samplers\":[{}],\"channels\":[{}]}}]", self.samplers.join(","), self.channels.join(","))
+        };
+        let mut data = self.bin.buf;
+        while (data.len() % 4 != 0 || data.is_empty()) {
+            data.push(0);
+        }
+        let mut json = format!(
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"freetubew\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{}],\"meshes\":[{}],\"materials\":[{},{},{}],\"accessors\":[{}],\"bufferViews\":[{}],\"buffers\":[{{\"byteLength\":{}}}]{}}}",
+            nodes.join(","),
+            self.meshes.join(","),
+            material("straight", &STRAIGHT_RGBA),
+            material("bend", &BEND_RGBA),
+            material("tool", &TOOL_RGBA),
+            self.accessors.join(","),
+            self.views.join(","),
+            data.len(),
+            animations
+        )
+        .into_bytes();
+        while (json.len() % 4 != 0) {
+            json.push(b' ');
+        }
+        let mut w = ChunkWriter::new();
+        w.buf.extend_from_slice(b"glTF");
+        w.write_u32(2);
+        w.write_u32((12 + 8 + json.len() + 8 + data.len()) as u32);
+        w.write_u32(json.len() as u32);
+        w.write_u32(GLTF_JSON);
+        w.buf.extend_from_slice(&json);
+        w.write_u32(data.len() as u32);
+        w.write_u32(GLTF_BIN);
+        w.buf.extend_from_slice(&data);
+        w.buf
+    }
+}
+
+fn is_ccw(tri: &[MeshVertex]) -> bool {
+    let p = |v: &MeshVertex| Vector3::new(v.position[0], v.position[1], v.position[2]);
+    let c = (p(&tri[1]) - p(&tri[0])).cross(p(&tri[2]) - p(&tri[0]));
+    c.dot(Vector3::new(tri[0].normal[0], tri[0].normal[1], tri[0].normal[2])) >= 0.0
+}
+
+fn facet_normal(p: &[[f32; 3]], n: &[[f32; 3]]) -> [f32; 3] {
+    let a = Vector3::new(p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]);
+    let b = Vector3::new(p[2][0] - p[0][0], p[2][1] - p[0][1], p[2][2] - p[0][2]);
+    let c = a.cross(b);
+    if (c.magnitude() > 0.0) { normalized(&[c.x, c.y, c.z]) } else { normalized(&n[0]) }
+}
+
+fn normalized(n: &[f32; 3]) -> [f32; 3] {
+    let v = Vector3::new(n[0], n[1], n[2]);
+    if (v.magnitude() > 0.0) {
+        let v = v.normalize();
+        [v.x, v.y, v.z]
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
+
+fn json_text(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,111 @@
+use crate::algo::cnc::{intersection_points, optimize_lraclr, LRACLR};
+use crate::algo::P_FORWARD;
+use crate::algo::output::wrap_rotation;
+use cgmath::{EuclideanSpace, InnerSpace, MetricSpace};
+use std::fmt::{Display, Formatter};
+use std::ops::{Mul, Sub};
+use truck_base::cgmath64::{Point3, Vector3};
+
+//Mirror planes in the loading position of the tube
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MirrorPlane {
+    //through the tube axis and the up direction, the usual left/right plane of a car
+    Side = 0,
+    //through the tube axis, perpendicular to the up direction
+    Top = 1,
+    //perpendicular to the tube axis, the same part as the side mirror turned half around the up direction
+    Front = 2,
+}
+impl MirrorPlane {
+    pub fn from_i32(v: i32) -> Self {
+        match v {
+            1 => MirrorPlane::Top,
+            2 => MirrorPlane::Front,
+            _ => MirrorPlane::Side,
+        }
+    }
+    //Normal of the plane through the start of the tube, the first straight runs forward
+    pub fn normal(&self, v_up: &Vector3) -> Vector3 {
+        match self {
+            MirrorPlane::Side => P_FORWARD.cross(*v_up),
+            MirrorPlane::Top => v_up.clone(),
+            MirrorPlane::Front => P_FORWARD,
+        }
+    }
+}
+impl Display for MirrorPlane {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MirrorPlane::Side => write!(f, "side"),
+            MirrorPlane::Top => write!(f, "top"),
+            MirrorPlane::Front => write!(f, "front"),
+        }
+    }
+}
+
+//Opposite hand of the program. Lengths, angles and the bend order stay, a mirror turns every bend plane the other way
+//so all rotations change sign, which alone is the mirror in the top plane as rotations are counted from the up direction.
+//The side mirror is the top mirror turned half a turn around the tube, that half turn goes to the first rotation.
+//The front mirror gives the same program, a half turn around the up direction brings it onto the side mirror
+pub fn mirror_lraclr(lraclr_arr: &Vec<LRACLR>, plane: MirrorPlane) -> Vec<LRACLR> {
+    let mut ret: Vec<LRACLR> = lraclr_arr
+        .iter()
+        .map(|lra| {
+            let mut m = lra.clone();
+            m.r = if (lra.r == 0.0) { 0.0 } else { -lra.r };
+            m
+        })
+        .collect();
+    if (plane != MirrorPlane::Top) {
+        match ret.first_mut() {
+            None => {}
+            Some(first) => first.r = wrap_rotation(180.0 + first.r),
+        }
+    }
+    optimize_lraclr(&mut ret);
+    ret
+}
+
+//Centerline of the opposite hand mirrored across the plane through its start. The front mirror is turned
+//half around the up direction so it starts along the tube axis as the program does
+pub fn mirror_centerline(points: &Vec<Point3>, plane: MirrorPlane, v_up: &Vector3) -> Vec<Point3> {
+    let origin = match points.first() {
+        None => return vec![],
+        Some(p) => p.clone(),
+    };
+    let mirrored = mirror_points(points, &origin, &plane.normal(v_up));
+    if (plane == MirrorPlane::Front) {
+        let u = v_up.normalize();
+        mirrored
+            .iter()
+            .map(|p| {
+                let d = p.sub(origin);
+                origin + u.mul(2.0 * d.dot(u)).sub(d)
+            })
+            .collect()
+    } else {
+        mirrored
+    }
+}
+
+//Largest distance between the intersection points of the mirrored program and the mirrored centerline of the part
+pub fn mirror_deviation(lraclr_arr: &Vec<LRACLR>, mirrored: &Vec<LRACLR>, plane: MirrorPlane, v_up: &Vector3) -> f64 {
+    let expected = mirror_centerline(&intersection_points(lraclr_arr, v_up), plane, v_up);
+    let actual = intersection_points(mirrored, v_up);
+    if (expected.len() != actual.len()) {
+        return f64::INFINITY;
+    }
+    expected.iter().zip(actual.iter()).fold(0.0, |m, (e, a)| m.max(e.distance(*a)))
+}
+
+//Centerline points mirrored across the plane through origin with the given normal
+pub fn mirror_points(points: &Vec<Point3>, origin: &Point3, normal: &Vector3) -> Vec<Point3> {
+    let n = normal.normalize();
+    points
+        .iter()
+        .map(|p| {
+            let dist = p.sub(origin).dot(n);
+            Point3::from_vec(p.to_vec().sub(n.mul(2.0 * dist)))
+        })
+        .collect()
+}
@@ -1,4 +1,26 @@
 pub mod cnc;
+pub mod machine;
+pub mod collision;
+pub mod cycle;
+pub mod optimizer;
+pub mod tools;
+pub mod deviation;
+pub mod roll;
+pub mod bendability;
+pub mod project;
+pub mod nesting;
+pub mod allowance;
+pub mod step_export;
+pub mod mesh_export;
+pub mod anim_export;
+pub mod drawing;
+pub mod marking;
+pub mod datum;
+pub mod output;
+pub mod mirror;
+pub mod edit;
+pub mod diff;
+pub mod simulation;
 use crate::algo::cnc::{gen_cyl, LRACLR};
 use crate::device::{MeshVertex, StepVertexBuffer};
 use cgmath::num_traits::real::Real;
@@ -14,6 +36,7 @@ use serde::{Deserialize, Serialize};
 use shipyard::Component;
 use std::collections::{HashMap, HashSet};
 use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufWriter, Read, Write};
 use std::ops::{Mul, Sub};
@@ -82,6 +105,7 @@ pub const TESS_TOR_STEP: u64 = 20;
 pub const Z_FIGHTING_FACTOR: f32 = 1.0;
 const CAP_TRIANGULATION: Rad<f64> = Rad(PI / 180.0);
 pub const TOLE: f64 = 0.9;
+const DATUM_TOLE: f64 = 0.001;
 pub const EXTRA_LEN_CALC: f64 = 3.0;
 pub const EXTRA_R_CALC: f64 = 1.2;
 pub const MAX_BEND_RADIUS: f64 = 500.0;
@@ -2243,38 +2267,228 @@ pub fn extract_plane_points(table: &Table, scale: f64) -> Vec<Point3> {
     });
     points
 }
-pub fn analyze_stp(_stp: &Vec<u8>) -> Vec<LRACLR> {
-    let mut transcoded = DecodeReaderBytesBuilder::new().encoding(Some(WINDOWS_1251)).build(_stp.as_slice());
-    let mut buf: Vec<u8> = vec![];
-    let mut stp: String = String::new();
-    match transcoded.read_to_end(&mut buf) {
-        Ok(b) => match String::from_utf8(buf) {
-            Ok(cont) => {
-                stp = cont.clone();
+//Surface of a STEP face with the axes as they are written in the file
+#[derive(Debug, Clone)]
+pub enum FaceGeometry {
+    Plane,
+    Cylinder { loc: Point3, axis: Vector3, ref_dir: Vector3, radius: f64 },
+    Torus { loc: Point3, axis: Vector3, ref_dir: Vector3, major_radius: f64, minor_radius: f64 },
+    Other,
+}
+//A face read straight from the table, its edges sampled along their curves
+#[derive(Debug, Clone)]
+pub struct StpFace {
+    pub id: u64,
+    pub geometry: FaceGeometry,
+    pub edge_points: Vec<Point3>,
+}
+const EDGE_SAMPLES: usize = 12;
+//Every face of every shell as the file has it, nothing of the recognition is used so the faces can check it
+pub fn extract_faces(table: &Table, scale: f64) -> Vec<StpFace> {
+    let mut ret: Vec<StpFace> = vec![];
+    table.shell.iter().for_each(|(_k, v)| {
+        v.cfs_faces.iter().for_each(|face_holder| match face_holder {
+            PlaceHolder::Ref(name) => {
+                let id = name_to_id(name.clone());
+                match table.face_surface.get(&id) {
+                    None => {}
+                    Some(face) => {
+                        let mut edge_points: Vec<Point3> = vec![];
+                        face.bounds.iter().for_each(|bound| {
+                            face_bound_edges(table, bound).iter().for_each(|edge_id| edge_points.extend(sample_edge(table, *edge_id, scale)));
+                        });
+                        let geometry = match &face.face_geometry {
+                            PlaceHolder::Ref(name) => face_geometry(table, name_to_id(name.clone()), scale),
+                            PlaceHolder::Owned(_) => FaceGeometry::Other,
+                        };
+                        ret.push(StpFace { id: id, geometry: geometry, edge_points: edge_points });
+                    }
+                }
+            }
+            PlaceHolder::Owned(_) => {}
+        })
+    });
+    ret
+}
+fn face_bound_edges(table: &Table, bound: &PlaceHolder<FaceBoundHolder>) -> Vec<u64> {
+    let mut ret: Vec<u64> = vec![];
+    let edge_loop = match bound {
+        PlaceHolder::Ref(name) => table.face_bound.get(&name_to_id(name.clone())).and_then(|face_bound| match &face_bound.bound {
+            PlaceHolder::Ref(name) => table.edge_loop.get(&name_to_id(name.clone())),
+            PlaceHolder::Owned(_) => None,
+        }),
+        PlaceHolder::Owned(_) => None,
+    };
+    match edge_loop {
+        None => {}
+        Some(edge_loop) => edge_loop.edge_list.iter().for_each(|oe_holder| match oe_holder {
+            PlaceHolder::Ref(name) => match table.oriented_edge.get(&name_to_id(name.clone())) {
+                None => {}
+                Some(oe) => match &oe.edge_element {
+                    PlaceHolder::Ref(name) => ret.push(name_to_id(name.clone())),
+                    PlaceHolder::Owned(_) => {}
+                },
+            },
+            PlaceHolder::Owned(_) => {}
+        }),
+    }
+    ret
+}
+//Circles are sampled along the arc the edge runs, b-splines over their knots, any other curve by its ends
+fn sample_edge(table: &Table, edge_id: u64, scale: f64) -> Vec<Point3> {
+    let c = match table.edge_curve.get(&edge_id) {
+        None => return vec![],
+        Some(c) => c,
+    };
+    let (sp, ep) = match (extract_vertex(table, &c.edge_start, scale), extract_vertex(table, &c.edge_end, scale)) {
+        (Some(sp), Some(ep)) => (sp, ep),
+        _ => return vec![],
+    };
+    let curve_id = match &c.edge_geometry {
+        PlaceHolder::Ref(name) => name_to_id(name.clone()),
+        PlaceHolder::Owned(_) => return vec![sp, ep],
+    };
+    match table.circle.get(&curve_id) {
+        None => {}
+        Some(circle) => {
+            let (loc, dir, dir_rad) = extract_position(table, &circle.position, scale);
+            match (loc, dir) {
+                (Some(loc), Some(dir)) => {
+                    //directions come back turned by ROT_DIR_CCW, the circle runs counter clockwise around its own axis
+                    let axis = dir.mul(ROT_DIR_CCW);
+                    let u = dir_rad.map(|d| d.mul(ROT_DIR_CCW)).unwrap_or(perpendicular_rand_dir(&axis).normalize());
+                    let v = axis.cross(u);
+                    let angle = |p: &Point3| -> f64 {
+                        let d = p.sub(loc);
+                        d.dot(v).atan2(d.dot(u))
+                    };
+                    let a0 = angle(&sp);
+                    let mut sweep = (angle(&ep) - a0).rem_euclid(2.0 * PI);
+                    if (sp.distance(ep) < TOLE * 0.01) {
+                        sweep = 2.0 * PI;
+                    }
+                    if (!c.same_sense) {
+                        sweep = sweep - 2.0 * PI;
+                    }
+                    let r = circle.radius * scale;
+                    return (0..=EDGE_SAMPLES)
+                        .map(|i| {
+                            let t = a0 + sweep * i as f64 / EDGE_SAMPLES as f64;
+                            loc + u.mul(r * t.cos()) + v.mul(r * t.sin())
+                        })
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+    }
+    match table.b_spline_curve_with_knots.get(&curve_id) {
+        None => {}
+        Some(spline) => {
+            let mut contrl_points: Vec<CartesianPoint> = vec![];
+            spline.control_points_list.iter().for_each(|cp| match extract_cartesian_point(table, &cp, scale) {
+                None => {}
+                Some(pnt) => contrl_points.push(CartesianPoint { label: "".to_string(), coordinates: Vec::from([pnt.x, pnt.y, pnt.z]) }),
+            });
+            let sspl: BSplineCurveWithKnots = BSplineCurveWithKnots {
+                label: "".to_string(),
+                degree: spline.degree,
+                control_points_list: contrl_points,
+                curve_form: spline.curve_form.clone(),
+                closed_curve: spline.closed_curve,
+                self_intersect: spline.self_intersect,
+                knot_multiplicities: spline.knot_multiplicities.clone(),
+                knots: spline.knots.clone(),
+                knot_spec: spline.knot_spec.clone(),
+            };
+            let res: Result<BSplineCurve<Point3>, _> = (&sspl).try_into();
+            match (res, spline.knots.first(), spline.knots.last()) {
+                (Ok(res), Some(k0), Some(k1)) => {
+                    return (0..=EDGE_SAMPLES).map(|i| res.subs(k0 + (k1 - k0) * i as f64 / EDGE_SAMPLES as f64)).collect();
+                }
+                _ => {}
             }
-            Err(e) => {
-                println!("{:?}", e)
+        }
+    }
+    vec![sp, ep]
+}
+fn face_geometry(table: &Table, surface_id: u64, scale: f64) -> FaceGeometry {
+    if (table.plane.contains_key(&surface_id)) {
+        return FaceGeometry::Plane;
+    }
+    match table.cylindrical_surface.get(&surface_id) {
+        None => {}
+        Some(cyl) => match extract_position3d(table, &cyl.position, scale) {
+            (Some(loc), Some(axis), dir_ref) => {
+                let ref_dir = dir_ref.unwrap_or(perpendicular_rand_dir(&axis).normalize());
+                return FaceGeometry::Cylinder { loc: loc, axis: axis, ref_dir: ref_dir, radius: cyl.radius * scale };
             }
+            _ => {}
         },
-        Err(e) => {
-            println!("{:?}", e)
+    }
+    match table.toroidal_surface.get(&surface_id) {
+        None => {}
+        Some(tor) => match extract_position3d(table, &tor.position, scale) {
+            (Some(loc), Some(axis), dir_ref) => {
+                let ref_dir = dir_ref.unwrap_or(perpendicular_rand_dir(&axis).normalize());
+                return FaceGeometry::Torus { loc: loc, axis: axis, ref_dir: ref_dir, major_radius: tor.major_radius * scale, minor_radius: tor.minor_radius * scale };
+            }
+            _ => {}
+        },
+    }
+    FaceGeometry::Other
+}
+//STEP file that could not be read, nothing was recognised from it
+#[derive(Debug, Clone, PartialEq)]
+pub enum StpError {
+    Encoding(String),
+    Parse(String),
+    NoData,
+}
+impl Display for StpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StpError::Encoding(e) => write!(f, "not a text file {}", e),
+            StpError::Parse(e) => write!(f, "not a STEP file {}", e),
+            StpError::NoData => write!(f, "no DATA section"),
         }
     }
+}
+pub fn analyze_stp(_stp: &Vec<u8>) -> Result<Vec<LRACLR>, StpError> {
+    let (table, scale) = parse_stp(_stp)?;
+    Ok(analyze_table(&table, scale))
+}
+pub fn parse_stp(_stp: &Vec<u8>) -> Result<(Table, f64), StpError> {
+    let mut transcoded = DecodeReaderBytesBuilder::new().encoding(Some(WINDOWS_1251)).build(_stp.as_slice());
+    let mut buf: Vec<u8> = vec![];
+    transcoded.read_to_end(&mut buf).map_err(|e| StpError::Encoding(e.to_string()))?;
+    let stp: String = String::from_utf8(buf).map_err(|e| StpError::Encoding(e.to_string()))?;
     let scale = extact_scale(&stp);
 
     let fixed_stp: String =stp.replace(",(),", ",'',");
 
-    let exchange = ruststep::parser::parse(&fixed_stp).unwrap();
-    let table: Table = Table::from_data_section(&exchange.data[0]);
-
+    let exchange = ruststep::parser::parse(&fixed_stp).map_err(|e| StpError::Parse(format!("{:?}", e)))?;
+    match exchange.data.first() {
+        None => Err(StpError::NoData),
+        Some(data) => Ok((Table::from_data_section(data), scale)),
+    }
+}
+pub fn analyze_table(table: &Table, scale: f64) -> Vec<LRACLR> {
+    analyze_table_with_datum(table, scale, None)
+}
+//The datum direction is in model coordinates, it is what points up at the machine before the first bend
+pub fn analyze_table_with_datum(table: &Table, scale: f64, datum: Option<Vector3>) -> Vec<LRACLR> {
     let (cyls, tors) = extract_cyls(&table, scale);
-
+    analyze_extracted(&cyls, &tors, table, scale, datum)
+}
+//Program from the straights and bends extract_cyls found in the table
+pub fn analyze_extracted(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>, table: &Table, scale: f64, datum: Option<Vector3>) -> Vec<LRACLR> {
     let cyls_no_dubs = MainCylinder::remove_dublicates(&cyls);
     let cyls_merged = MainCylinder::merge(&cyls_no_dubs);
-    let bend_toros_no_dublicates: Vec<BendToro> = BendToro::remove_dublicates(&tors);
+    let bend_toros_no_dublicates: Vec<BendToro> = BendToro::remove_dublicates(tors);
     let merged_tors = BendToro::merge(&bend_toros_no_dublicates);
     let racalculated_tors: Vec<BendToro> = recalc_tors_tole(&cyls_merged, &merged_tors);
-    let lracmd: Vec<LRACLR> = find_bending_surface(&cyls_merged, &racalculated_tors,&table,scale);
+    let lracmd: Vec<LRACLR> = find_bending_surface(&cyls_merged, &racalculated_tors,&table,scale,datum);
 
     lracmd
 
@@ -3488,7 +3702,7 @@ pub fn nurbs_to_circle(nurbs_points: &Vec<Point3>) -> Option<MainCircle> {
         None
     }
 }
-pub fn find_bending_surface(cyls: &Vec<MainCylinder>, tors: &Vec<(BendToro)>, table: &Table, scale: f64) -> Vec<LRACLR> {
+pub fn find_bending_surface(cyls: &Vec<MainCylinder>, tors: &Vec<(BendToro)>, table: &Table, scale: f64, datum: Option<Vector3>) -> Vec<LRACLR> {
     let mut cc: Vec<MainCylinder> = cyls.clone();
     let mut tt: Vec<BendToro> = tors.clone();
 
@@ -3561,12 +3775,12 @@ pub fn find_bending_surface(cyls: &Vec<MainCylinder>, tors: &Vec<(BendToro)>, ta
     }
 
 
-    let lracmd: Vec<LRACLR> = find_next_tor(&ncyls, &ntors,table,scale);
+    let lracmd: Vec<LRACLR> = find_next_tor(&ncyls, &ntors,table,scale,datum);
 
     //(ncyls, ntors)
     lracmd
 }
-fn find_next_tor(_cyls: &Vec<MainCylinder>, _tors: &Vec<(BendToro)>, table: &Table, scale: f64) -> Vec<LRACLR> {
+fn find_next_tor(_cyls: &Vec<MainCylinder>, _tors: &Vec<(BendToro)>, table: &Table, scale: f64, datum: Option<Vector3>) -> Vec<LRACLR> {
     let mut lracmds: Vec<LRACLR> = vec![];
     let mut cc = _cyls.clone();
     let mut tors_bank: HashMap<u64, BendToro> = HashMap::new();
@@ -3635,31 +3849,25 @@ fn find_next_tor(_cyls: &Vec<MainCylinder>, _tors: &Vec<(BendToro)>, table: &Tab
                     let a = Deg::from(bend_angle).0;
                     let r: Rad<f64> = {
                         if (counter == 0) {
-                            prev_plane = bend_plane;
-                            Rad(0.0)
-                        } else {
-                            let a = {
-                                //https://stackoverflow.com/questions/14066933/direct-way-of-computing-the-clockwise-angle-between-two-vectors
-                                //Plane embedded in 3D
-                                let dot = prev_plane.dot(bend_plane);
-                                //let det = prev.x*t.bend_plane_norm.y*t.ca.dir.z + t.bend_plane_norm.x*t.ca.dir.y*prev.z + t.ca.dir.x*prev.y*t.bend_plane_norm.z - prev.z*t.bend_plane_norm.y*t.ca.dir.x - t.bend_plane_norm.z*t.ca.dir.y*prev.x - t.ca.dir.z*prev.y*t.bend_plane_norm.x;
-                                let det = Matrix3::from_cols(
-                                    bend_plane,
-                                    prev_plane,
-                                    frwd,
-                                ).determinant();
-                                let angle = det.atan2(dot);
-                                let d = prev_plane.dot(bend_plane);
-                                //warn!("dot {:?}  {:?} {:?}",Deg::from(Rad(angle)),Deg::from(prev.angle(t.bend_plane_norm)),d_frwd);
-                                if (d == -1.0) {
-                                    //Rad(0.0)
-                                    Rad(-angle)
-                                } else {
-                                    Rad(angle)
+                            //without a datum the first bend plane is the one the tube is loaded in
+                            let a = match datum {
+                                None => Rad(0.0),
+                                Some(d) => {
+                                    let d_perp = d.sub(frwd.mul(d.dot(frwd)));
+                                    if (d_perp.magnitude() < DATUM_TOLE) {
+                                        warn!("DATUM IS ALONG THE FIRST STRAIGHT");
+                                        Rad(0.0)
+                                    } else {
+                                        plane_rotation(d_perp.normalize(), bend_plane, frwd)
+                                    }
                                 }
                             };
                             prev_plane = bend_plane;
                             a
+                        } else {
+                            let a = plane_rotation(prev_plane, bend_plane, frwd);
+                            prev_plane = bend_plane;
+                            a
                         }
                     };
                     let lra = LRACLR {
@@ -3696,6 +3904,26 @@ fn find_next_tor(_cyls: &Vec<MainCylinder>, _tors: &Vec<(BendToro)>, table: &Tab
     }
     lracmds
 }
+fn plane_rotation(prev_plane: Vector3, bend_plane: Vector3, frwd: Vector3) -> Rad<f64> {
+    //https://stackoverflow.com/questions/14066933/direct-way-of-computing-the-clockwise-angle-between-two-vectors
+    //Plane embedded in 3D
+    let dot = prev_plane.dot(bend_plane);
+    //let det = prev.x*t.bend_plane_norm.y*t.ca.dir.z + t.bend_plane_norm.x*t.ca.dir.y*prev.z + t.ca.dir.x*prev.y*t.bend_plane_norm.z - prev.z*t.bend_plane_norm.y*t.ca.dir.x - t.bend_plane_norm.z*t.ca.dir.y*prev.x - t.ca.dir.z*prev.y*t.bend_plane_norm.x;
+    let det = Matrix3::from_cols(
+        bend_plane,
+        prev_plane,
+        frwd,
+    ).determinant();
+    let angle = det.atan2(dot);
+    let d = prev_plane.dot(bend_plane);
+    //warn!("dot {:?}  {:?} {:?}",Deg::from(Rad(angle)),Deg::from(prev.angle(t.bend_plane_norm)),d_frwd);
+    if (d == -1.0) {
+        //Rad(0.0)
+        Rad(-angle)
+    } else {
+        Rad(angle)
+    }
+}
 fn gen_plane_points(s: &MainCylinder, b: &BendToro) -> (Point3, Point3, Point3,) {
     if (s.ca.loc.distance(b.ca.loc) < TOLE) {
         (s.cb.loc.clone(), s.ca.loc.clone(), b.cb.loc.clone())
@@ -0,0 +1,222 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::round_by_dec;
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+use std::fmt::{Display, Formatter};
+
+const BAR_LEN: f64 = 6000.0;
+const SAW_KERF: f64 = 3.0;
+const CLAMP_ALLOWANCE: f64 = 100.0;
+const END_TRIM: f64 = 5.0;
+
+#[derive(Debug, Clone)]
+pub struct NestingParameters {
+    pub bar_len: f64,
+    pub kerf: f64,
+    //bar end left in the feeder clamp, it can not be cut into parts
+    pub clamp_allowance: f64,
+    //squaring cut at the head of every bar
+    pub end_trim: f64,
+}
+impl NestingParameters {
+    pub fn default() -> Self {
+        Self {
+            bar_len: BAR_LEN,
+            kerf: SAW_KERF,
+            clamp_allowance: CLAMP_ALLOWANCE,
+            end_trim: END_TRIM,
+        }
+    }
+    //[bar_len, kerf, clamp_allowance, end_trim]
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => self.bar_len = v.clone() as f64,
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.kerf = v.clone() as f64,
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.clamp_allowance = v.clone() as f64,
+        }
+        match vec.get(3) {
+            None => {}
+            Some(v) => self.end_trim = v.clone() as f64,
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        Vec::from([self.bar_len as f32, self.kerf as f32, self.clamp_allowance as f32, self.end_trim as f32])
+    }
+    //Length of a bar left for parts after the head trim and the clamp remnant
+    pub fn usable_len(&self) -> f64 {
+        let trim = if (self.end_trim > 0.0) { self.end_trim + self.kerf } else { 0.0 };
+        self.bar_len - trim - self.clamp_allowance
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NestPart {
+    pub name: String,
+    pub len: f64,
+    pub od: f64,
+    pub wall: f64,
+    pub material: String,
+    pub quantity: usize,
+}
+impl NestPart {
+    pub fn from_lraclr(name: &str, lraclr_arr: &Vec<LRACLR>, wall: f64, material: &str, quantity: usize) -> NestPart {
+        let (len, pipe_radius) = LRACLR::total_len_out_d(lraclr_arr);
+        NestPart {
+            name: name.to_string(),
+            len: len,
+            od: pipe_radius * 2.0,
+            wall: wall,
+            material: material.to_string(),
+            quantity: quantity,
+        }
+    }
+    fn is_same_stock(&self, other: &NestPart) -> bool {
+        self.material == other.material && round_by_dec(self.od, 2) == round_by_dec(other.od, 2) && round_by_dec(self.wall, 2) == round_by_dec(other.wall, 2)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NestBar {
+    //indexes into the part list of the group, one entry per piece
+    pub cuts: Vec<usize>,
+    pub used: f64,
+    pub remnant: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NestGroup {
+    pub od: f64,
+    pub wall: f64,
+    pub material: String,
+    pub parts: Vec<NestPart>,
+    pub bars: Vec<NestBar>,
+    //parts longer than a usable bar
+    pub oversize: Vec<usize>,
+    pub utilisation: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CutPlan {
+    pub params: NestingParameters,
+    pub groups: Vec<NestGroup>,
+}
+impl CutPlan {
+    pub fn bar_count(&self) -> usize {
+        self.groups.iter().map(|g| g.bars.len()).sum()
+    }
+    //Part length over bought length over all groups
+    pub fn utilisation(&self) -> f64 {
+        let parts_len: f64 = self.groups.iter().map(|g| group_parts_len(g)).sum();
+        let bought = self.bar_count() as f64 * self.params.bar_len;
+        if (bought > 0.0) { parts_len / bought } else { 0.0 }
+    }
+    //Header fields 1 bars, 2 utilisation, 3 bar_len.
+    //Row fields 1 group, 2 bar, 3 od, 4 wall, 5 used, 6 remnant, 7 pieces, then 8 + i the part index of piece i
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::CutPlan);
+        msg.header.push(1, self.bar_count() as f64);
+        msg.header.push(2, self.utilisation());
+        msg.header.push(3, self.params.bar_len);
+        self.groups.iter().enumerate().for_each(|(gi, g)| {
+            g.bars.iter().enumerate().for_each(|(bi, b)| {
+                let mut row = WireRecord::new();
+                row.push(1, gi as f64);
+                row.push(2, bi as f64);
+                row.push(3, g.od);
+                row.push(4, g.wall);
+                row.push(5, b.used);
+                row.push(6, b.remnant);
+                row.push(7, b.cuts.len() as f64);
+                b.cuts.iter().enumerate().for_each(|(i, c)| {
+                    row.push(8 + i as u32, *c as f64);
+                });
+                msg.rows.push(row);
+            });
+        });
+        msg
+    }
+}
+impl Display for CutPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let _ = writeln!(f, "{} bars of {} utilisation {:.1}%", self.bar_count(), self.params.bar_len, self.utilisation() * 100.0);
+        self.groups.iter().for_each(|g| {
+            let _ = writeln!(f, "{} OD {} wall {}: {} bars utilisation {:.1}%", g.material, g.od, g.wall, g.bars.len(), g.utilisation * 100.0);
+            g.bars.iter().enumerate().for_each(|(i, b)| {
+                let names: Vec<String> = b.cuts.iter().map(|c| format!("{} {:.1}", g.parts[*c].name, g.parts[*c].len)).collect();
+                let _ = writeln!(f, "  bar {}: {} remnant {:.1}", i + 1, names.join(", "), b.remnant);
+            });
+            g.oversize.iter().for_each(|p| {
+                let _ = writeln!(f, "  {} {:.1} is longer than a bar", g.parts[*p].name, g.parts[*p].len);
+            });
+        });
+        Ok(())
+    }
+}
+
+fn group_parts_len(group: &NestGroup) -> f64 {
+    group.bars.iter().map(|b| b.cuts.iter().map(|c| group.parts[*c].len).sum::<f64>()).sum()
+}
+
+//Best fit decreasing per OD, wall and material. Every piece takes its length plus one kerf,
+//a bar holds pieces up to its usable length
+pub fn nest_parts(parts: &Vec<NestPart>, params: &NestingParameters) -> CutPlan {
+    let mut groups: Vec<NestGroup> = vec![];
+    parts.iter().for_each(|p| {
+        match groups.iter_mut().find(|g| g.parts[0].is_same_stock(p)) {
+            None => groups.push(NestGroup {
+                od: p.od,
+                wall: p.wall,
+                material: p.material.clone(),
+                parts: vec![p.clone()],
+                bars: vec![],
+                oversize: vec![],
+                utilisation: 0.0,
+            }),
+            Some(g) => g.parts.push(p.clone()),
+        }
+    });
+    let usable = params.usable_len();
+    groups.iter_mut().for_each(|g| {
+        let mut pieces: Vec<usize> = vec![];
+        g.parts.iter().enumerate().for_each(|(i, p)| {
+            if (p.len + params.kerf > usable) {
+                g.oversize.push(i);
+            } else {
+                for _ in 0..p.quantity {
+                    pieces.push(i);
+                }
+            }
+        });
+        pieces.sort_by(|a, b| g.parts[*b].len.total_cmp(&g.parts[*a].len));
+        let mut bars: Vec<NestBar> = vec![];
+        pieces.iter().for_each(|piece| {
+            let need = g.parts[*piece].len + params.kerf;
+            let best = bars
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| usable - b.used >= need)
+                .min_by(|(_, a), (_, b)| (usable - a.used).total_cmp(&(usable - b.used)))
+                .map(|(i, _)| i);
+            match best {
+                None => bars.push(NestBar { cuts: vec![*piece], used: need, remnant: 0.0 }),
+                Some(i) => {
+                    bars[i].cuts.push(*piece);
+                    bars[i].used = bars[i].used + need;
+                }
+            }
+        });
+        bars.iter_mut().for_each(|b| {
+            b.remnant = usable - b.used;
+        });
+        g.bars = bars;
+        let bought = g.bars.len() as f64 * params.bar_len;
+        g.utilisation = if (bought > 0.0) { group_parts_len(g) / bought } else { 0.0 };
+    });
+    CutPlan { params: params.clone(), groups: groups }
+}
@@ -0,0 +1,190 @@
+use crate::algo::cnc::{reverse_lraclr, LRACLR};
+use crate::algo::collision::{simulate_collisions, Collision};
+use crate::algo::cycle::cycle_time;
+use crate::algo::machine::{validate_lraclr, MachineProfile, Violation};
+use crate::algo::{P_UP, P_UP_REVERSE};
+use crate::device::graphics::BendParameters;
+use cgmath::num_traits::{abs, signum};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use truck_base::cgmath64::Vector3;
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+#[derive(Debug, Clone)]
+pub struct ProgramCandidate {
+    pub lraclr: Vec<LRACLR>,
+    pub is_reversed: bool,
+    pub v_up_orign: Vector3,
+    pub cycle_time: f64,
+    pub violations: Vec<Violation>,
+    pub collision: Option<Collision>,
+    pub is_collision_checked: bool,
+    pub reasons: Vec<String>,
+}
+impl ProgramCandidate {
+    pub fn is_feasible(&self) -> bool {
+        self.violations.is_empty() && self.is_collision_checked && self.collision.is_none()
+    }
+    //Row fields 1 is_reversed, 2 v_up_z, 3 cycle_time, 4 violations, 5 has_collision, 6 is_collision_checked, one row per candidate in rank order
+    pub fn to_wire(candidates: &Vec<ProgramCandidate>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Candidates);
+        candidates.iter().for_each(|c| {
+            let mut row = WireRecord::new();
+            row.push_bool(1, c.is_reversed);
+            row.push(2, c.v_up_orign.z);
+            row.push(3, c.cycle_time);
+            row.push(4, c.violations.len() as f64);
+            row.push_bool(5, c.collision.is_some());
+            row.push_bool(6, c.is_collision_checked);
+            msg.rows.push(row);
+        });
+        msg
+    }
+    pub fn report(candidates: &Vec<ProgramCandidate>) -> String {
+        let mut ret = String::new();
+        candidates.iter().enumerate().for_each(|(i, c)| {
+            ret.push_str(format!("{} {}\n", i + 1, c).as_str());
+        });
+        ret
+    }
+}
+impl Display for ProgramCandidate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "time {:.1}s {}", self.cycle_time, self.reasons.join("; "))
+    }
+}
+
+//Evaluates both load ends, both dorn directions and the rotation sense of every step,
+//returns all candidates ranked, feasible ones first and then by cycle time.
+//Collisions are simulated in rank order only until the first candidate free of them is found
+pub fn optimize_program(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile, bend_params: &BendParameters) -> Vec<ProgramCandidate> {
+    let mut ret: Vec<ProgramCandidate> = vec![];
+    if (lraclr_arr.is_empty()) {
+        return ret;
+    }
+    let loads: Vec<(bool, Vec<LRACLR>)> = vec![(false, lraclr_arr.clone()), (true, reverse_lraclr(lraclr_arr))];
+    loads.iter().for_each(|(is_reversed, program)| {
+        let mut variants: Vec<(Vec<LRACLR>, usize)> = vec![(program.clone(), 0)];
+        let (best_senses, changed) = optimize_rotation_sense(program, machine);
+        if (changed > 0) {
+            variants.push((best_senses, changed));
+        }
+        [P_UP_REVERSE, P_UP].iter().for_each(|v_up| {
+            variants.iter().for_each(|(lra, changed)| {
+                ret.push(evaluate(lra, *is_reversed, v_up, *changed, machine));
+            });
+        });
+    });
+    ret.sort_by(|a, b| {
+        b.violations.is_empty().cmp(&a.violations.is_empty()).then(a.cycle_time.total_cmp(&b.cycle_time))
+    });
+    let mut is_found = false;
+    ret.iter_mut().for_each(|c| {
+        if (c.violations.is_empty()) {
+            if (is_found) {
+                c.reasons.push("collisions not checked, a faster candidate is free of them".to_string());
+            } else {
+                c.collision = simulate_collisions(&c.lraclr, &c.v_up_orign, bend_params, &machine.obstacles);
+                c.is_collision_checked = true;
+                match &c.collision {
+                    None => is_found = true,
+                    Some(collision) => c.reasons.push(format!("collision {}", collision)),
+                }
+            }
+        }
+    });
+    ret.sort_by(|a, b| {
+        b.is_feasible().cmp(&a.is_feasible()).then(a.cycle_time.total_cmp(&b.cycle_time))
+    });
+    ret
+}
+
+fn evaluate(lraclr_arr: &Vec<LRACLR>, is_reversed: bool, v_up_orign: &Vector3, changed: usize, machine: &MachineProfile) -> ProgramCandidate {
+    let violations = validate_lraclr(lraclr_arr, machine);
+    let cycle_time = cycle_time(lraclr_arr, &machine.cycle).total;
+    let mut reasons: Vec<String> = vec![];
+    if (is_reversed) {
+        reasons.push("load from the other end".to_string());
+    } else {
+        reasons.push("load as designed".to_string());
+    }
+    if (signum(v_up_orign.z) > 0.0) {
+        reasons.push("dorn direction reversed".to_string());
+    } else {
+        reasons.push("dorn direction default".to_string());
+    }
+    if (changed > 0) {
+        reasons.push(format!("rotation sense changed at {} steps to stay in collet limits", changed));
+    }
+    violations.iter().for_each(|v| {
+        reasons.push(format!("violation {}", v));
+    });
+    ProgramCandidate {
+        lraclr: lraclr_arr.clone(),
+        is_reversed: is_reversed,
+        v_up_orign: v_up_orign.clone(),
+        cycle_time: cycle_time,
+        violations: violations,
+        collision: None,
+        is_collision_checked: false,
+        reasons: reasons,
+    }
+}
+
+//Every rotation can be done the short way or the long way round (r - 360 * sign(r)).
+//Picks the cheapest combination keeping the accumulated collet angle inside machine limits
+pub fn optimize_rotation_sense(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile) -> (Vec<LRACLR>, usize) {
+    //key is the number of full turns added to the accumulated rotation
+    let mut states: HashMap<i64, (f64, Vec<f64>)> = HashMap::new();
+    states.insert(0, (0.0, vec![]));
+    let mut base_rotation: f64 = 0.0;
+    lraclr_arr.iter().for_each(|lra| {
+        let mut next_states: HashMap<i64, (f64, Vec<f64>)> = HashMap::new();
+        let is_bend = lra.clr > 0.0;
+        if (is_bend) {
+            base_rotation = base_rotation + lra.r;
+        }
+        let alternatives: Vec<(f64, i64)> = {
+            if (is_bend && lra.r != 0.0) {
+                vec![(lra.r, 0), (lra.r - 360.0 * signum(lra.r), -(signum(lra.r) as i64))]
+            } else {
+                vec![(lra.r, 0)]
+            }
+        };
+        states.iter().for_each(|(k, (cost, rots))| {
+            alternatives.iter().for_each(|(r, dk)| {
+                let nk = k + dk;
+                let rotation = base_rotation + 360.0 * nk as f64;
+                let is_in_limits = !is_bend || (rotation >= machine.min_rotation && rotation <= machine.max_rotation);
+                if (is_in_limits) {
+                    let ncost = cost + abs(*r);
+                    let is_better = match next_states.get(&nk) {
+                        None => true,
+                        Some((c, _)) => ncost < *c,
+                    };
+                    if (is_better) {
+                        let mut nrots = rots.clone();
+                        nrots.push(*r);
+                        next_states.insert(nk, (ncost, nrots));
+                    }
+                }
+            });
+        });
+        states = next_states;
+    });
+    let mut ret: Vec<LRACLR> = lraclr_arr.clone();
+    let mut changed = 0;
+    let best = states.values().min_by(|a, b| a.0.total_cmp(&b.0));
+    match best {
+        None => {}
+        Some((_cost, rots)) => {
+            ret.iter_mut().zip(rots.iter()).for_each(|(lra, r)| {
+                if (lra.r != *r) {
+                    lra.r = *r;
+                    changed = changed + 1;
+                }
+            });
+        }
+    }
+    (ret, changed)
+}
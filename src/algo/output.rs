@@ -0,0 +1,216 @@
+use crate::algo::cnc::LRACLR;
+use cgmath::num_traits::{abs, signum};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotationMode {
+    //turn of the collet before every bend, the way the program is recognised
+    Incremental = 0,
+    //collet angle from the loading position, 0..360
+    Absolute = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedMode {
+    //straight before every bend
+    Incremental = 0,
+    //carriage position at the start of every bend, measured from the leading end of the tube
+    Absolute = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotationSense {
+    //positive rotations as in the recognised program
+    Ccw = 0,
+    Cw = 1,
+}
+
+//How a controller wants the L and R columns. The program itself always stays incremental and counter clockwise,
+//every exporter converts it with to_output and every importer reads it back with from_output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputMode {
+    pub rotation: RotationMode,
+    pub feed: FeedMode,
+    pub sense: RotationSense,
+}
+impl OutputMode {
+    pub fn default() -> Self {
+        Self {
+            rotation: RotationMode::Incremental,
+            feed: FeedMode::Incremental,
+            sense: RotationSense::Ccw,
+        }
+    }
+    pub fn is_default(&self) -> bool {
+        *self == OutputMode::default()
+    }
+    //[rotation, feed, sense], 0 incremental or CCW and 1 absolute or CW
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => self.rotation = if (v.round() as i32 == 1) { RotationMode::Absolute } else { RotationMode::Incremental },
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.feed = if (v.round() as i32 == 1) { FeedMode::Absolute } else { FeedMode::Incremental },
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.sense = if (v.round() as i32 == 1) { RotationSense::Cw } else { RotationSense::Ccw },
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        vec![self.rotation as i32 as f32, self.feed as i32 as f32, self.sense as i32 as f32]
+    }
+    //same in both directions, a zero stays positive so files do not get -0
+    fn with_sense(&self, r: f64) -> f64 {
+        match self.sense {
+            RotationSense::Ccw => r,
+            RotationSense::Cw => if (r == 0.0) { 0.0 } else { -r },
+        }
+    }
+    //Column names for tables and reports
+    pub fn feed_name(&self) -> &'static str {
+        match self.feed {
+            FeedMode::Incremental => "L",
+            FeedMode::Absolute => "Y",
+        }
+    }
+    pub fn rotation_name(&self) -> &'static str {
+        match self.rotation {
+            RotationMode::Incremental => "R",
+            RotationMode::Absolute => "C",
+        }
+    }
+}
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rotation {} feed {} {}",
+            match self.rotation {
+                RotationMode::Incremental => "incremental",
+                RotationMode::Absolute => "absolute",
+            },
+            match self.feed {
+                FeedMode::Incremental => "incremental",
+                FeedMode::Absolute => "absolute",
+            },
+            match self.sense {
+                RotationSense::Ccw => "ccw",
+                RotationSense::Cw => "cw",
+            }
+        )
+    }
+}
+
+//Shortest turn for the same collet position, -180..180
+pub fn wrap_rotation(r: f64) -> f64 {
+    let mut ret = r;
+    if (abs(ret) >= 360.0) {
+        let rounds = abs(ret as i64 / 360);
+        ret = (abs(ret) - 360.0 * rounds as f64) * signum(ret);
+    }
+    if (abs(ret) > 180.0) {
+        ret = -((360.0 - abs(ret)) * signum(ret));
+    }
+    ret
+}
+
+//Collet angle, 0..360
+pub fn normalize_angle(r: f64) -> f64 {
+    let ret = r % 360.0;
+    if (ret < 0.0) { ret + 360.0 } else { ret }
+}
+
+//Program rows with l and r as the controller wants them, everything else is copied
+pub fn to_output(lraclr_arr: &Vec<LRACLR>, mode: &OutputMode) -> Vec<LRACLR> {
+    let mut ret: Vec<LRACLR> = vec![];
+    let mut feed: f64 = 0.0;
+    let mut angle: f64 = 0.0;
+    lraclr_arr.iter().for_each(|lra| {
+        let mut row = lra.clone();
+        let r = mode.with_sense(lra.r);
+        angle = angle + r;
+        feed = feed + lra.l;
+        row.r = match mode.rotation {
+            RotationMode::Incremental => r,
+            RotationMode::Absolute => normalize_angle(angle),
+        };
+        row.l = match mode.feed {
+            FeedMode::Incremental => lra.l,
+            FeedMode::Absolute => feed,
+        };
+        feed = feed + lra.lt;
+        ret.push(row);
+    });
+    ret
+}
+
+//Reverse of to_output, rows read from a controller file become an incremental counter clockwise program
+pub fn from_output(lraclr_arr: &Vec<LRACLR>, mode: &OutputMode) -> Vec<LRACLR> {
+    let mut ret: Vec<LRACLR> = vec![];
+    let mut prev_feed: f64 = 0.0;
+    let mut prev_angle: f64 = 0.0;
+    lraclr_arr.iter().for_each(|row| {
+        let mut lra = row.clone();
+        lra.r = match mode.rotation {
+            RotationMode::Incremental => mode.with_sense(row.r),
+            RotationMode::Absolute => wrap_rotation(mode.with_sense(row.r - prev_angle)),
+        };
+        lra.l = match mode.feed {
+            FeedMode::Incremental => row.l,
+            FeedMode::Absolute => abs(row.l - prev_feed),
+        };
+        prev_angle = row.r;
+        prev_feed = match mode.feed {
+            FeedMode::Incremental => prev_feed + row.l + row.lt,
+            FeedMode::Absolute => row.l + row.lt,
+        };
+        ret.push(lra);
+    });
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Vec<LRACLR> {
+        let rows: [(f64, f64, f64); 4] = [(120.0, 35.5, 0.0), (80.0, 42.0, 90.0), (0.0, 28.0, -135.0), (210.5, 0.0, 179.0)];
+        rows.iter()
+            .enumerate()
+            .map(|(i, (l, lt, r))| {
+                let mut lra = LRACLR::default();
+                lra.id1 = (i * 2) as i32;
+                lra.id2 = (i * 2 + 1) as i32;
+                lra.l = *l;
+                lra.lt = *lt;
+                lra.r = *r;
+                lra.a = 45.0;
+                lra.clr = 60.0;
+                lra.pipe_radius = 10.0;
+                lra
+            })
+            .collect()
+    }
+
+    #[test]
+    fn output_round_trip() {
+        let lraclr_arr = program();
+        [RotationMode::Incremental, RotationMode::Absolute].iter().for_each(|rotation| {
+            [FeedMode::Incremental, FeedMode::Absolute].iter().for_each(|feed| {
+                [RotationSense::Ccw, RotationSense::Cw].iter().for_each(|sense| {
+                    let mode = OutputMode { rotation: *rotation, feed: *feed, sense: *sense };
+                    let back = from_output(&to_output(&lraclr_arr, &mode), &mode);
+                    assert_eq!(back.len(), lraclr_arr.len());
+                    lraclr_arr.iter().zip(back.iter()).for_each(|(a, b)| {
+                        assert!(abs(a.l - b.l) < 1e-9, "{} l {} != {}", mode, a.l, b.l);
+                        assert!(abs(a.r - b.r) < 1e-9, "{} r {} != {}", mode, a.r, b.r);
+                        assert_eq!((a.id1, a.id2, a.lt, a.a, a.clr), (b.id1, b.id2, b.lt, b.a, b.clr));
+                    });
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,308 @@
+use crate::algo::allowance::{Allowance, AllowanceParameters};
+use crate::algo::bendability::TubeStock;
+use crate::algo::cnc::LRACLR;
+use crate::algo::collision::Obstacle;
+use crate::algo::datum::RotationDatum;
+use crate::algo::machine::MachineProfile;
+use crate::algo::roll::RollParameters;
+use crate::algo::tools::ToolLibrary;
+use crate::algo::P_UP_REVERSE;
+use crate::utils::chunk::{fnv1a, ChunkReader, ChunkWriter};
+use std::fmt::{Display, Formatter};
+use truck_base::cgmath64::Vector3;
+
+pub const PROJECT_MAGIC: [u8; 4] = *b"FTWP";
+pub const PROJECT_VERSION: u32 = 1;
+pub const PROJECT_FILE: &str = "project.ftwp";
+
+const TAG_SOURCE_HASH: u32 = 1;
+const TAG_SOURCE_STP: u32 = 2;
+const TAG_RECOGNISED: u32 = 3;
+const TAG_PROGRAM: u32 = 4;
+const TAG_SPRINGBACK: u32 = 5;
+const TAG_VIEW: u32 = 6;
+const TAG_MACHINE: u32 = 7;
+const TAG_OBSTACLES: u32 = 8;
+const TAG_CYCLE: u32 = 9;
+const TAG_TOOLS: u32 = 10;
+const TAG_STOCK: u32 = 11;
+const TAG_ROLL: u32 = 12;
+const TAG_JOB: u32 = 13;
+const TAG_ALLOWANCE: u32 = 14;
+const TAG_DATUM: u32 = 15;
+const TAG_OUTPUT: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated(u32),
+    Io(String),
+}
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectError::BadMagic => write!(f, "not a project file"),
+            ProjectError::UnsupportedVersion(v) => write!(f, "project version {} is newer than {}", v, PROJECT_VERSION),
+            ProjectError::Truncated(tag) => write!(f, "project block {} is truncated", tag),
+            ProjectError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+//Everything about the job that is not a machine or tooling setting
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub source_hash: u64,
+    pub source_stp: Vec<u8>,
+    pub recognised: Vec<LRACLR>,
+    //program with the bend angles corrected for springback, empty when the job has none
+    pub springback: Vec<LRACLR>,
+    pub part_number: String,
+    pub notes: String,
+}
+impl JobInfo {
+    pub fn default() -> Self {
+        Self {
+            source_hash: 0,
+            source_stp: vec![],
+            recognised: vec![],
+            springback: vec![],
+            part_number: String::new(),
+            notes: String::new(),
+        }
+    }
+    pub fn from_stp(stp: &Vec<u8>, recognised: &Vec<LRACLR>) -> Self {
+        let mut ret = JobInfo::default();
+        ret.source_hash = fnv1a(stp.as_slice());
+        ret.source_stp = stp.clone();
+        ret.recognised = recognised.clone();
+        ret
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub job: JobInfo,
+    pub program: Vec<LRACLR>,
+    pub is_reversed: bool,
+    pub v_up_orign: Vector3,
+    pub machine: MachineProfile,
+    pub tools: ToolLibrary,
+    pub stock: TubeStock,
+    pub roll_params: RollParameters,
+    pub allowance_params: AllowanceParameters,
+    //stock already added to the program
+    pub allowance: Allowance,
+    pub datum: RotationDatum,
+}
+impl Project {
+    pub fn default() -> Self {
+        Self {
+            job: JobInfo::default(),
+            program: vec![],
+            is_reversed: false,
+            v_up_orign: P_UP_REVERSE,
+            machine: MachineProfile::default(),
+            tools: ToolLibrary::default(),
+            stock: TubeStock::default(),
+            roll_params: RollParameters::default(),
+            allowance_params: AllowanceParameters::default(),
+            allowance: Allowance::default(),
+            datum: RotationDatum::default(),
+        }
+    }
+    //When is_with_source is false only the hash of the STEP file is kept
+    pub fn to_bytes(&self, is_with_source: bool) -> Vec<u8> {
+        let mut w = ChunkWriter::new();
+        w.buf.extend_from_slice(&PROJECT_MAGIC);
+        w.write_u32(PROJECT_VERSION);
+        w.write_chunk(TAG_SOURCE_HASH, |c| c.write_u64(self.job.source_hash));
+        if (is_with_source && !self.job.source_stp.is_empty()) {
+            w.write_chunk(TAG_SOURCE_STP, |c| c.write_bytes(&self.job.source_stp));
+        }
+        w.write_chunk(TAG_RECOGNISED, |c| write_program(c, &self.job.recognised));
+        w.write_chunk(TAG_PROGRAM, |c| write_program(c, &self.program));
+        if (!self.job.springback.is_empty()) {
+            w.write_chunk(TAG_SPRINGBACK, |c| write_program(c, &self.job.springback));
+        }
+        w.write_chunk(TAG_VIEW, |c| {
+            c.write_u32(if (self.is_reversed) { 1 } else { 0 });
+            c.write_f64(self.v_up_orign.x);
+            c.write_f64(self.v_up_orign.y);
+            c.write_f64(self.v_up_orign.z);
+        });
+        w.write_chunk(TAG_MACHINE, |c| {
+            c.write_str(&self.machine.name);
+            c.write_f32vec(&self.machine.params_to_f32vec());
+        });
+        w.write_chunk(TAG_OBSTACLES, |c| c.write_f32vec(&Obstacle::to_f32vec(&self.machine.obstacles)));
+        w.write_chunk(TAG_CYCLE, |c| c.write_f32vec(&self.machine.cycle.params_to_f32vec()));
+        w.write_chunk(TAG_OUTPUT, |c| c.write_f32vec(&self.machine.output.params_to_f32vec()));
+        w.write_chunk(TAG_TOOLS, |c| c.write_f32vec(&self.tools.to_f32vec()));
+        w.write_chunk(TAG_STOCK, |c| c.write_f32vec(&self.stock.params_to_f32vec()));
+        w.write_chunk(TAG_ROLL, |c| c.write_f32vec(&self.roll_params.params_to_f32vec()));
+        w.write_chunk(TAG_ALLOWANCE, |c| {
+            c.write_f32vec(&self.allowance_params.params_to_f32vec());
+            c.write_f64(self.allowance.start);
+            c.write_f64(self.allowance.end);
+        });
+        w.write_chunk(TAG_DATUM, |c| c.write_f32vec(&self.datum.params_to_f32vec()));
+        w.write_chunk(TAG_JOB, |c| {
+            c.write_str(&self.job.part_number);
+            c.write_str(&self.job.notes);
+        });
+        w.buf
+    }
+    pub fn from_bytes(data: &[u8]) -> Result<Project, ProjectError> {
+        if (data.len() < 8 || data[0..4] != PROJECT_MAGIC) {
+            return Err(ProjectError::BadMagic);
+        }
+        let mut r = ChunkReader::new(&data[4..]);
+        let version = r.read_u32().ok_or(ProjectError::BadMagic)?;
+        if (version > PROJECT_VERSION) {
+            return Err(ProjectError::UnsupportedVersion(version));
+        }
+        let mut ret = Project::default();
+        while (!r.is_empty()) {
+            let (tag, mut c) = r.read_chunk().ok_or(ProjectError::Truncated(0))?;
+            let is_ok: Option<()> = (|| {
+                match tag {
+                    TAG_SOURCE_HASH => ret.job.source_hash = c.read_u64()?,
+                    TAG_SOURCE_STP => ret.job.source_stp = c.read_bytes()?.to_vec(),
+                    TAG_RECOGNISED => ret.job.recognised = read_program(&mut c)?,
+                    TAG_PROGRAM => ret.program = read_program(&mut c)?,
+                    TAG_SPRINGBACK => ret.job.springback = read_program(&mut c)?,
+                    TAG_VIEW => {
+                        ret.is_reversed = c.read_u32()? == 1;
+                        ret.v_up_orign = Vector3::new(c.read_f64()?, c.read_f64()?, c.read_f64()?);
+                    }
+                    TAG_MACHINE => {
+                        ret.machine.name = c.read_str()?;
+                        ret.machine.set_params_from_f32vec(&c.read_f32vec()?);
+                    }
+                    TAG_OBSTACLES => ret.machine.obstacles = Obstacle::from_f32vec(&c.read_f32vec()?),
+                    TAG_CYCLE => ret.machine.cycle.set_params_from_f32vec(&c.read_f32vec()?),
+                    TAG_OUTPUT => ret.machine.output.set_params_from_f32vec(&c.read_f32vec()?),
+                    TAG_TOOLS => ret.tools = ToolLibrary::from_f32vec(&c.read_f32vec()?),
+                    TAG_STOCK => ret.stock.set_params_from_f32vec(&c.read_f32vec()?),
+                    TAG_ROLL => ret.roll_params.set_params_from_f32vec(&c.read_f32vec()?),
+                    TAG_ALLOWANCE => {
+                        ret.allowance_params.set_params_from_f32vec(&c.read_f32vec()?);
+                        ret.allowance.start = c.read_f64()?;
+                        ret.allowance.end = c.read_f64()?;
+                    }
+                    TAG_DATUM => ret.datum.set_params_from_f32vec(&c.read_f32vec()?),
+                    TAG_JOB => {
+                        ret.job.part_number = c.read_str()?;
+                        ret.job.notes = c.read_str()?;
+                    }
+                    _ => {}
+                }
+                Some(())
+            })();
+            if (is_ok.is_none()) {
+                return Err(ProjectError::Truncated(tag));
+            }
+        }
+        Ok(ret)
+    }
+    pub fn is_source_matching(&self, stp: &Vec<u8>) -> bool {
+        self.job.source_hash == fnv1a(stp.as_slice())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &str) -> Result<(), ProjectError> {
+        std::fs::write(path, self.to_bytes(true)).map_err(|e| ProjectError::Io(e.to_string()))
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Project, ProjectError> {
+        let data = std::fs::read(path).map_err(|e| ProjectError::Io(e.to_string()))?;
+        Project::from_bytes(&data)
+    }
+}
+
+fn write_program(w: &mut ChunkWriter, lraclr_arr: &Vec<LRACLR>) {
+    w.write_u32(lraclr_arr.len() as u32);
+    lraclr_arr.iter().for_each(|lra| {
+        w.write_i32(lra.id1);
+        w.write_i32(lra.id2);
+        w.write_f64(lra.l);
+        w.write_f64(lra.lt);
+        w.write_f64(lra.r);
+        w.write_f64(lra.a);
+        w.write_f64(lra.clr);
+        w.write_f64(lra.pipe_radius);
+    });
+}
+
+fn read_program(r: &mut ChunkReader) -> Option<Vec<LRACLR>> {
+    let count = r.read_u32()? as usize;
+    let mut ret: Vec<LRACLR> = vec![];
+    for _ in 0..count {
+        ret.push(LRACLR {
+            id1: r.read_i32()?,
+            id2: r.read_i32()?,
+            l: r.read_f64()?,
+            lt: r.read_f64()?,
+            r: r.read_f64()?,
+            a: r.read_f64()?,
+            clr: r.read_f64()?,
+            pipe_radius: r.read_f64()?,
+        });
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(a: f64) -> Vec<LRACLR> {
+        (0..3)
+            .map(|i| {
+                let mut lra = LRACLR::default();
+                lra.id1 = i * 2;
+                lra.id2 = i * 2 + 1;
+                lra.l = 50.0 + i as f64;
+                lra.r = 30.0 * i as f64;
+                lra.a = if (i == 2) { 0.0 } else { a };
+                lra.clr = if (i == 2) { 0.0 } else { 40.0 };
+                lra.lt = if (i == 2) { 0.0 } else { a.to_radians() * 40.0 };
+                lra.pipe_radius = 12.5;
+                lra
+            })
+            .collect()
+    }
+
+    fn rows(lraclr_arr: &Vec<LRACLR>) -> Vec<(i32, i32, f64, f64, f64, f64, f64, f64)> {
+        lraclr_arr.iter().map(|lra| (lra.id1, lra.id2, lra.l, lra.lt, lra.r, lra.a, lra.clr, lra.pipe_radius)).collect()
+    }
+
+    #[test]
+    fn project_round_trip() {
+        let stp = b"ISO-10303-21;END-ISO-10303-21;".to_vec();
+        let mut project = Project::default();
+        project.job = JobInfo::from_stp(&stp, &program(90.0));
+        project.job.springback = program(93.5);
+        project.job.part_number = "A-100".to_string();
+        project.job.notes = "left hand".to_string();
+        project.program = program(90.0);
+        project.is_reversed = true;
+        project.allowance.start = 20.0;
+        project.allowance.end = 15.0;
+        [true, false].iter().for_each(|is_with_source| {
+            let loaded = Project::from_bytes(&project.to_bytes(*is_with_source)).unwrap();
+            assert_eq!(rows(&loaded.job.springback), rows(&project.job.springback));
+            assert_eq!(rows(&loaded.job.recognised), rows(&project.job.recognised));
+            assert_eq!(rows(&loaded.program), rows(&project.program));
+            assert_eq!(loaded.job.part_number, project.job.part_number);
+            assert_eq!(loaded.job.notes, project.job.notes);
+            assert_eq!(loaded.is_reversed, project.is_reversed);
+            assert_eq!((loaded.allowance.start, loaded.allowance.end), (20.0, 15.0));
+            assert!(loaded.is_source_matching(&stp));
+            assert_eq!(loaded.job.source_stp.is_empty(), !*is_with_source);
+        });
+        project.job.springback.clear();
+        assert!(Project::from_bytes(&project.to_bytes(false)).unwrap().job.springback.is_empty());
+    }
+}
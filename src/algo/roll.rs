@@ -0,0 +1,205 @@
+use crate::algo::cnc::LRACLR;
+use crate::algo::machine::MachineProfile;
+use cgmath::{Deg, Rad};
+use std::fmt::{Display, Formatter};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+const MAX_BUMP_ANGLE: f64 = 3.0;
+const ROLL_SPAN: f64 = 500.0;
+//largest rotary draw die assumed when neither the parameters nor the machine name one
+const DEFAULT_DIE_LIMIT: f64 = 300.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LargeBendMode {
+    RotaryDraw = 0,
+    Roll = 1,
+    Bump = 2,
+}
+
+#[derive(Debug, Clone)]
+pub struct RollParameters {
+    pub mode: LargeBendMode,
+    //bends above this clr are large, 0 takes the largest die of the machine
+    pub max_die_clr: f64,
+    //die used for bumping, 0 takes one outer diameter
+    pub bump_clr: f64,
+    pub max_bump_angle: f64,
+    //distance between the outer rolls of a three roll bender
+    pub roll_span: f64,
+}
+impl RollParameters {
+    pub fn default() -> Self {
+        Self {
+            mode: LargeBendMode::RotaryDraw,
+            max_die_clr: 0.0,
+            bump_clr: 0.0,
+            max_bump_angle: MAX_BUMP_ANGLE,
+            roll_span: ROLL_SPAN,
+        }
+    }
+    //[mode, max_die_clr, bump_clr, max_bump_angle, roll_span]
+    pub fn set_params_from_f32vec(&mut self, vec: &Vec<f32>) {
+        match vec.get(0) {
+            None => {}
+            Some(v) => {
+                self.mode = match *v as i32 {
+                    1 => LargeBendMode::Roll,
+                    2 => LargeBendMode::Bump,
+                    _ => LargeBendMode::RotaryDraw,
+                }
+            }
+        }
+        match vec.get(1) {
+            None => {}
+            Some(v) => self.max_die_clr = v.clone() as f64,
+        }
+        match vec.get(2) {
+            None => {}
+            Some(v) => self.bump_clr = v.clone() as f64,
+        }
+        match vec.get(3) {
+            None => {}
+            Some(v) => self.max_bump_angle = v.clone() as f64,
+        }
+        match vec.get(4) {
+            None => {}
+            Some(v) => self.roll_span = v.clone() as f64,
+        }
+    }
+    pub fn params_to_f32vec(&self) -> Vec<f32> {
+        Vec::from([
+            self.mode as i32 as f32,
+            self.max_die_clr as f32,
+            self.bump_clr as f32,
+            self.max_bump_angle as f32,
+            self.roll_span as f32,
+        ])
+    }
+    pub fn die_limit(&self, machine: &MachineProfile) -> f64 {
+        if (self.max_die_clr > 0.0) {
+            self.max_die_clr
+        } else if (!machine.clrs.is_empty()) {
+            machine.clrs.iter().cloned().fold(0.0, f64::max) + machine.clr_tole
+        } else {
+            DEFAULT_DIE_LIMIT
+        }
+    }
+    pub fn is_large(&self, lra: &LRACLR, machine: &MachineProfile) -> bool {
+        lra.clr > 0.0 && lra.clr > self.die_limit(machine)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RollInstruction {
+    pub id1: i32,
+    pub id2: i32,
+    pub feed: f64,
+    pub rotation: f64,
+    pub angle: f64,
+    pub clr: f64,
+    pub arc_len: f64,
+    //infeed of the middle roll from the line through the outer rolls
+    pub roll_offset: f64,
+}
+impl RollInstruction {
+    //Row fields 1 id1, 2 id2, 3 feed, 4 rotation, 5 angle, 6 clr, 7 arc_len, 8 roll_offset
+    pub fn to_wire(instructions: &Vec<RollInstruction>) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::RollInstructions);
+        instructions.iter().for_each(|ri| {
+            let mut row = WireRecord::new();
+            row.push(1, ri.id1 as f64);
+            row.push(2, ri.id2 as f64);
+            row.push(3, ri.feed);
+            row.push(4, ri.rotation);
+            row.push(5, ri.angle);
+            row.push(6, ri.clr);
+            row.push(7, ri.arc_len);
+            row.push(8, ri.roll_offset);
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for RollInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} ROLL feed {:.2} rot {:.2} arc {:.2} offset {:.3} (A {:.2} CLR {:.1})", self.id1, self.id2, self.feed, self.rotation, self.arc_len, self.roll_offset, self.angle, self.clr)
+    }
+}
+
+pub fn roll_offset(clr: f64, span: f64) -> f64 {
+    let half = span / 2.0;
+    if (half >= clr) {
+        clr
+    } else {
+        clr - (clr * clr - half * half).sqrt()
+    }
+}
+
+//Roll mode keeps the program and lists roll instructions for the large bends,
+//bump mode replaces every large bend with a chain of small bends on the bump die
+pub fn convert_large_bends(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile, params: &RollParameters) -> (Vec<LRACLR>, Vec<RollInstruction>) {
+    match params.mode {
+        LargeBendMode::RotaryDraw => (lraclr_arr.clone(), vec![]),
+        LargeBendMode::Roll => {
+            let mut instructions: Vec<RollInstruction> = vec![];
+            lraclr_arr.iter().for_each(|lra| {
+                if (params.is_large(lra, machine)) {
+                    instructions.push(RollInstruction {
+                        id1: lra.id1,
+                        id2: lra.id2,
+                        feed: lra.l,
+                        rotation: lra.r,
+                        angle: lra.a,
+                        clr: lra.clr,
+                        arc_len: Rad::from(Deg(lra.a)).0 * lra.clr,
+                        roll_offset: roll_offset(lra.clr, params.roll_span),
+                    });
+                }
+            });
+            (lraclr_arr.clone(), instructions)
+        }
+        LargeBendMode::Bump => (bump_lraclr(lraclr_arr, machine, params), vec![]),
+    }
+}
+
+//A bend of angle a and radius R becomes n bumps of a/n on a die of radius r_d.
+//Bump vertices lie on the tangent polygon of the arc, so the straights between bumps are
+//2(R - r_d)tan(a/2n) and the straights around the bend grow by (R - r_d)tan(a/2n)
+pub fn bump_lraclr(lraclr_arr: &Vec<LRACLR>, machine: &MachineProfile, params: &RollParameters) -> Vec<LRACLR> {
+    let mut rows: Vec<LRACLR> = vec![];
+    let mut extra_len: f64 = 0.0;
+    lraclr_arr.iter().for_each(|lra| {
+        let bump_clr = if (params.bump_clr > 0.0) { params.bump_clr } else { lra.pipe_radius * 2.0 };
+        if (params.is_large(lra, machine) && lra.clr > bump_clr && params.max_bump_angle > 0.0) {
+            let count = (lra.a / params.max_bump_angle).ceil().max(1.0) as usize;
+            let bump_angle = lra.a / count as f64;
+            let half_tan = Rad::from(Deg(bump_angle / 2.0)).0.tan();
+            let pitch = 2.0 * (lra.clr - bump_clr) * half_tan;
+            let ext = (lra.clr - bump_clr) * half_tan;
+            for i in 0..count {
+                let mut row = lra.clone();
+                row.a = bump_angle;
+                row.clr = bump_clr;
+                row.lt = Rad::from(Deg(bump_angle)).0 * bump_clr;
+                if (i == 0) {
+                    row.l = lra.l + extra_len + ext;
+                } else {
+                    row.l = pitch;
+                    row.r = 0.0;
+                }
+                rows.push(row);
+            }
+            extra_len = ext;
+        } else {
+            let mut row = lra.clone();
+            row.l = lra.l + extra_len;
+            rows.push(row);
+            extra_len = 0.0;
+        }
+    });
+    rows.iter_mut().enumerate().for_each(|(i, row)| {
+        row.id1 = (i * 2) as i32;
+        row.id2 = (i * 2 + 1) as i32;
+    });
+    rows
+}
@@ -0,0 +1,233 @@
+use crate::algo::cnc::{cnc_to_poly, cnc_to_poly_animate, reverse_lraclr, LRACLR};
+use crate::algo::output::normalize_angle;
+use crate::algo::{BendToro, MainCylinder};
+use crate::device::graphics::{AnimState, BendParameters};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+use cgmath::num_traits::abs;
+use cgmath::{Deg, Rad};
+use std::fmt::{Display, Formatter};
+use truck_base::cgmath64::Vector3;
+
+//Opcodes of AnimState, every program row is fed, turned and bent in this order
+pub const OP_FEED: usize = 0;
+pub const OP_ROTATE: usize = 1;
+const OP_BEND: usize = 2;
+const OP_DONE: usize = 4;
+const OPS_PER_ROW: i32 = 3;
+//op starts closer than this are one moment when stepping
+const SIM_TIME_TOLE: f64 = 1e-9;
+
+//One machine op of the bend sequence on the time line
+#[derive(Debug, Clone)]
+pub struct SimOp {
+    pub op_counter: i32,
+    pub row: usize,
+    pub opcode: usize,
+    pub start: f64,
+    pub duration: f64,
+    //length, turn or bend angle the op ends at
+    pub target: f64,
+    //tube gone through the bend head and collet angle when the op starts
+    pub fed_before: f64,
+    pub rotation_before: f64,
+}
+impl SimOp {
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+}
+
+//Where to move the time line to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimSeek {
+    Time(f64),
+    //next op for positive steps, previous op for negative ones
+    Step(i32),
+    //start of op N, the same counter change_bend_step reports
+    Op(i32),
+}
+impl SimSeek {
+    //[kind, value], kind 0 time in seconds, 1 relative step, 2 op number
+    pub fn from_f32vec(vec: &Vec<f32>) -> Option<SimSeek> {
+        let kind = vec.get(0)?.round() as i32;
+        let v = vec.get(1)?.clone();
+        match kind {
+            0 => Some(SimSeek::Time(v as f64)),
+            1 => Some(SimSeek::Step(v.round() as i32)),
+            2 => Some(SimSeek::Op(v.round() as i32)),
+            _ => None,
+        }
+    }
+}
+
+//The bend sequence as ops with fixed start times. Times follow from the lengths, angles and the animation speeds only,
+//so any moment of the sequence can be shown without running the frames before it
+#[derive(Debug, Clone)]
+pub struct SimTimeline {
+    pub ops: Vec<SimOp>,
+    pub total_len: f64,
+}
+impl SimTimeline {
+    pub fn new(lraclr_arr: &Vec<LRACLR>, bend_params: &BendParameters) -> Self {
+        let time = |v: f64, speed: f64| -> f64 { if (speed > 0.0) { abs(v) / speed } else { 0.0 } };
+        let mut ops: Vec<SimOp> = vec![];
+        let mut start: f64 = 0.0;
+        let mut fed: f64 = 0.0;
+        let mut rotation: f64 = 0.0;
+        let last = lraclr_arr.len() as i64 - 1;
+        lraclr_arr.iter().enumerate().for_each(|(row, lra)| {
+            //the end straight is only fed
+            let (r, a) = if (row as i64 == last) { (0.0, 0.0) } else { (lra.r, lra.a) };
+            let steps = [(OP_FEED, lra.l, time(lra.l, bend_params.stright_speed)), (OP_ROTATE, r, time(r, bend_params.rotate_speed)), (OP_BEND, a, time(a, bend_params.angle_speed))];
+            steps.iter().for_each(|(opcode, target, duration)| {
+                ops.push(SimOp {
+                    op_counter: row as i32 * OPS_PER_ROW + *opcode as i32,
+                    row: row,
+                    opcode: *opcode,
+                    start: start,
+                    duration: *duration,
+                    target: *target,
+                    fed_before: fed,
+                    rotation_before: rotation,
+                });
+                start = start + duration;
+                match *opcode {
+                    OP_FEED => fed = fed + target,
+                    OP_ROTATE => rotation = rotation + target,
+                    _ => fed = fed + lra.lt,
+                }
+            });
+        });
+        Self { ops: ops, total_len: fed }
+    }
+    pub fn duration(&self) -> f64 {
+        self.ops.last().map(|op| op.end()).unwrap_or(0.0)
+    }
+    pub fn op_count(&self) -> i32 {
+        self.ops.len() as i32
+    }
+    //Op running at t, ops that take no time are never running
+    pub fn op_at(&self, t: f64) -> Option<&SimOp> {
+        self.ops.iter().find(|op| op.duration > 0.0 && t >= op.start && t < op.end())
+    }
+    pub fn op_start(&self, op_counter: i32) -> f64 {
+        match self.ops.get(op_counter.max(0) as usize) {
+            None => self.duration(),
+            Some(op) => op.start,
+        }
+    }
+    //Steps go from op start to op start, a step back from inside an op goes to its own start first
+    pub fn seek(&self, t: f64, seek: &SimSeek) -> f64 {
+        let ret = match seek {
+            SimSeek::Time(time) => *time,
+            SimSeek::Op(n) => self.op_start(*n),
+            SimSeek::Step(n) => {
+                let mut ret = t;
+                (0..abs(*n)).for_each(|_i| {
+                    ret = if (*n > 0) {
+                        self.ops.iter().map(|op| op.start).find(|start| *start > ret + SIM_TIME_TOLE).unwrap_or(self.duration())
+                    } else {
+                        self.ops.iter().rev().map(|op| op.start).find(|start| *start < ret - SIM_TIME_TOLE).unwrap_or(0.0)
+                    };
+                });
+                ret
+            }
+        };
+        ret.max(0.0).min(self.duration())
+    }
+    //Animation stage at t, the same stage the frame by frame animation passes through at that moment
+    pub fn state_at(&self, lraclr_arr: &Vec<LRACLR>, t: f64) -> AnimState {
+        match self.op_at(t) {
+            None => AnimState::new(0, OP_DONE, 0.0, 0.0, LRACLR::default(), self.op_count()),
+            Some(op) => self.op_state(lraclr_arr, op, op.target * (t - op.start) / op.duration),
+        }
+    }
+    //Animation stage of op once it has got to value
+    pub fn op_state(&self, lraclr_arr: &Vec<LRACLR>, op: &SimOp, value: f64) -> AnimState {
+        let lra = lraclr_arr[op.row].clone();
+        let (id, fed) = match op.opcode {
+            OP_FEED => (op.row as i32 * 2, op.fed_before + value),
+            OP_ROTATE => (op.row as i32 * 2, op.fed_before),
+            _ => (op.row as i32 * 2 + 1, op.fed_before + Rad::from(Deg(value)).0 * lra.clr),
+        };
+        AnimState::new(id, op.opcode, value, self.total_len - fed, lra, op.op_counter)
+    }
+    //Pose of the machine at t, t is clamped to the sequence
+    pub fn pose_at(&self, lraclr_arr: &Vec<LRACLR>, t: f64) -> SimPose {
+        let t = t.max(0.0).min(self.duration());
+        let anim_state = self.state_at(lraclr_arr, t);
+        let rotation_before = match self.op_at(t) {
+            None => self.ops.last().map(|op| op.rotation_before).unwrap_or(0.0),
+            Some(op) => op.rotation_before,
+        };
+        let (feed, rotation, angle) = match anim_state.opcode {
+            OP_FEED => (self.total_len - anim_state.stright_len, rotation_before, 0.0),
+            OP_ROTATE => (self.total_len - anim_state.stright_len, rotation_before + anim_state.value, 0.0),
+            OP_BEND => (self.total_len - anim_state.stright_len, rotation_before, anim_state.value),
+            _ => (self.total_len, rotation_before, 0.0),
+        };
+        SimPose {
+            t: t,
+            duration: self.duration(),
+            anim_state: anim_state,
+            feed: feed,
+            rotation: normalize_angle(rotation),
+            angle: angle,
+        }
+    }
+}
+
+//Machine axes at one moment of the bend sequence
+#[derive(Debug)]
+pub struct SimPose {
+    pub t: f64,
+    pub duration: f64,
+    pub anim_state: AnimState,
+    //tube gone through the bend head
+    pub feed: f64,
+    //collet angle from the loading position, 0..360
+    pub rotation: f64,
+    //bend head angle of the bend in progress
+    pub angle: f64,
+}
+impl SimPose {
+    pub fn is_done(&self) -> bool {
+        self.anim_state.opcode == OP_DONE
+    }
+    //Row fields 1 time, 2 duration, 3 op counter, 4 opcode, 5 feed, 6 collet angle, 7 bend angle
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::Simulation);
+        let mut row = WireRecord::new();
+        row.push(1, self.t);
+        row.push(2, self.duration);
+        row.push(3, self.anim_state.op_counter as f64);
+        row.push(4, self.anim_state.opcode as f64);
+        row.push(5, self.feed);
+        row.push(6, self.rotation);
+        row.push(7, self.angle);
+        msg.rows.push(row);
+        msg
+    }
+}
+impl Display for SimPose {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "t {:.2} of {:.2} step {} feed {:.2} rotation {:.2} angle {:.2}", self.t, self.duration, self.anim_state.op_counter, self.feed, self.rotation, self.angle)
+    }
+}
+
+//Tube and machine at time t of the bend sequence. Nothing is carried from frame to frame, a slow frame
+//only skips a part of the sequence and going back in time shows the same tube as going forward
+pub fn simulate_at(lraclr_arr: &Vec<LRACLR>, v_up_orign: &Vector3, bend_params: &BendParameters, t: f64) -> (Vec<MainCylinder>, Vec<BendToro>, SimPose) {
+    let timeline = SimTimeline::new(lraclr_arr, bend_params);
+    let pose = timeline.pose_at(lraclr_arr, t);
+    if (lraclr_arr.is_empty()) {
+        (vec![], vec![], pose)
+    } else if (pose.is_done()) {
+        let (cyls, tors) = cnc_to_poly(&reverse_lraclr(lraclr_arr), v_up_orign);
+        (cyls, tors, pose)
+    } else {
+        //a zero step draws the stage as it is
+        let (cyls, tors, _next_stage) = cnc_to_poly_animate(lraclr_arr, &pose.anim_state, v_up_orign, 0.0, bend_params);
+        (cyls, tors, pose)
+    }
+}
@@ -0,0 +1,379 @@
+use crate::algo::{BendToro, MainCylinder};
+use cgmath::InnerSpace;
+use std::fmt;
+use std::ops::{Mul, Sub};
+use truck_base::cgmath64::{Point3, Vector3};
+use truck_stepio::r#in::Table;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+pub const STP_EXPORT_FILE: &str = "part.stp";
+const STRAIGHT_RGB: [f64; 3] = [0.27, 0.55, 0.75];
+const BEND_RGB: [f64; 3] = [0.85, 0.45, 0.35];
+
+#[derive(Debug, Clone)]
+pub struct StepExportParameters {
+    pub part_number: String,
+    //0 writes a solid rod
+    pub wall: f64,
+    pub is_with_centerline: bool,
+}
+impl StepExportParameters {
+    pub fn default() -> Self {
+        Self {
+            part_number: "tube".to_string(),
+            wall: 0.0,
+            is_with_centerline: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StepExportError {
+    //A straight or bend carries a NaN or infinite value, real() would write it as is
+    NotFinite(String),
+    //The written file does not parse back into the solids it was written with
+    ParseBack(String),
+}
+impl fmt::Display for StepExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepExportError::NotFinite(s) => write!(f, "not finite geometry: {}", s),
+            StepExportError::ParseBack(s) => write!(f, "written step does not parse back: {}", s),
+        }
+    }
+}
+
+//AP214 writer, every straight and bend is a manifold solid of its own with exact
+//cylindrical and toroidal faces, so the segments can be told apart and styled downstream
+struct StepWriter {
+    lines: Vec<String>,
+}
+impl StepWriter {
+    fn new() -> Self {
+        Self { lines: vec![] }
+    }
+    fn add(&mut self, entity: String) -> usize {
+        self.lines.push(entity);
+        self.lines.len()
+    }
+    fn point(&mut self, p: &Point3) -> usize {
+        self.add(format!("CARTESIAN_POINT('',({},{},{}))", real(p.x), real(p.y), real(p.z)))
+    }
+    fn direction(&mut self, v: &Vector3) -> usize {
+        let v = v.normalize();
+        self.add(format!("DIRECTION('',({},{},{}))", real(v.x), real(v.y), real(v.z)))
+    }
+    fn placement(&mut self, loc: &Point3, axis: &Vector3, ref_dir: &Vector3) -> usize {
+        let p = self.point(loc);
+        let a = self.direction(axis);
+        let r = self.direction(ref_dir);
+        self.add(format!("AXIS2_PLACEMENT_3D('',#{},#{},#{})", p, a, r))
+    }
+    fn vertex(&mut self, p: &Point3) -> usize {
+        let pt = self.point(p);
+        self.add(format!("VERTEX_POINT('',#{})", pt))
+    }
+    fn circle(&mut self, center: &Point3, axis: &Vector3, ref_dir: &Vector3, radius: f64) -> usize {
+        let pl = self.placement(center, axis, ref_dir);
+        self.add(format!("CIRCLE('',#{},{})", pl, real(radius)))
+    }
+    fn line(&mut self, sp: &Point3, ep: &Point3) -> usize {
+        let p = self.point(sp);
+        let d = self.direction(&ep.sub(*sp));
+        let v = self.add(format!("VECTOR('',#{},{})", d, real(ep.sub(*sp).magnitude())));
+        self.add(format!("LINE('',#{},#{})", p, v))
+    }
+    fn edge(&mut self, v0: usize, v1: usize, curve: usize) -> usize {
+        self.add(format!("EDGE_CURVE('',#{},#{},#{},.T.)", v0, v1, curve))
+    }
+    fn edge_loop(&mut self, edges: &[(usize, bool)]) -> usize {
+        let ids: Vec<String> = edges.iter().map(|(e, sense)| format!("#{}", self.add(format!("ORIENTED_EDGE('',*,*,#{},{})", e, logical(*sense))))).collect();
+        self.add(format!("EDGE_LOOP('',({}))", ids.join(",")))
+    }
+    fn face(&mut self, surface: usize, outer: &[(usize, bool)], hole: Option<&[(usize, bool)]>, same_sense: bool) -> usize {
+        let outer_loop = self.edge_loop(outer);
+        let mut bounds: Vec<String> = vec![format!("#{}", self.add(format!("FACE_OUTER_BOUND('',#{},.T.)", outer_loop)))];
+        match hole {
+            None => {}
+            Some(h) => {
+                let hole_loop = self.edge_loop(h);
+                bounds.push(format!("#{}", self.add(format!("FACE_BOUND('',#{},.T.)", hole_loop))));
+            }
+        }
+        self.add(format!("ADVANCED_FACE('',({}),#{},{})", bounds.join(","), surface, logical(same_sense)))
+    }
+    fn plane(&mut self, loc: &Point3, normal: &Vector3, ref_dir: &Vector3) -> usize {
+        let pl = self.placement(loc, normal, ref_dir);
+        self.add(format!("PLANE('',#{})", pl))
+    }
+    fn solid(&mut self, name: &str, faces: &Vec<usize>) -> usize {
+        let ids: Vec<String> = faces.iter().map(|f| format!("#{}", f)).collect();
+        let shell = self.add(format!("CLOSED_SHELL('',({}))", ids.join(",")));
+        self.add(format!("MANIFOLD_SOLID_BREP('{}',#{})", text(name), shell))
+    }
+    fn style(&mut self, rgb: &[f64; 3]) -> usize {
+        let colour = self.add(format!("COLOUR_RGB('',{},{},{})", real(rgb[0]), real(rgb[1]), real(rgb[2])));
+        let fill_colour = self.add(format!("FILL_AREA_STYLE_COLOUR('',#{})", colour));
+        let fill = self.add(format!("FILL_AREA_STYLE('',(#{}))", fill_colour));
+        let area = self.add(format!("SURFACE_STYLE_FILL_AREA(#{})", fill));
+        let side = self.add(format!("SURFACE_SIDE_STYLE('',(#{}))", area));
+        let usage = self.add(format!("SURFACE_STYLE_USAGE(.BOTH.,#{})", side));
+        self.add(format!("PRESENTATION_STYLE_ASSIGNMENT((#{}))", usage))
+    }
+    fn to_string(&self, part_number: &str) -> String {
+        let mut ret = String::new();
+        ret.push_str("ISO-10303-21;\nHEADER;\n");
+        ret.push_str("FILE_DESCRIPTION(('bent tube'),'2;1');\n");
+        ret.push_str(format!("FILE_NAME('{}.stp','{}',(''),(''),'freetubew','freetubew','');\n", text(part_number), timestamp()).as_str());
+        ret.push_str("FILE_SCHEMA(('AUTOMOTIVE_DESIGN { 1 0 10303 214 1 1 1 1 }'));\nENDSEC;\nDATA;\n");
+        self.lines.iter().enumerate().for_each(|(i, l)| {
+            ret.push_str(format!("#{}={};\n", i + 1, l).as_str());
+        });
+        ret.push_str("ENDSEC;\nEND-ISO-10303-21;\n");
+        ret
+    }
+}
+
+fn real(v: f64) -> String {
+    let s = format!("{}", if (v == 0.0) { 0.0 } else { v });
+    if (s.contains('.')) { s } else { format!("{}.", s) }
+}
+fn logical(v: bool) -> &'static str {
+    if (v) { ".T." } else { ".F." }
+}
+fn text(s: &str) -> String {
+    s.replace('\'', "''")
+}
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+    //civil date from days since 1970-01-01
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if (mp < 10) { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if (m <= 2) { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", y, m, d, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+//Straight along dir from sp, the seam runs along the reference direction
+fn straight_solid(w: &mut StepWriter, name: &str, c: &MainCylinder, inner_r: f64) -> usize {
+    let dir = c.ca.dir.normalize();
+    let rd = c.ca.radius_dir.normalize();
+    let sp = c.ca.loc;
+    let ep = c.ca.loc + dir.mul(c.h);
+    let mut faces: Vec<usize> = vec![];
+
+    let v0 = w.vertex(&(sp + rd.mul(c.r)));
+    let v1 = w.vertex(&(ep + rd.mul(c.r)));
+    let c0_curve = w.circle(&sp, &dir, &rd, c.r);
+    let c0 = w.edge(v0, v0, c0_curve);
+    let c1_curve = w.circle(&ep, &dir, &rd, c.r);
+    let c1 = w.edge(v1, v1, c1_curve);
+    let seam_curve = w.line(&(sp + rd.mul(c.r)), &(ep + rd.mul(c.r)));
+    let seam = w.edge(v0, v1, seam_curve);
+    let pl = w.placement(&sp, &dir, &rd);
+    let surface = w.add(format!("CYLINDRICAL_SURFACE('',#{},{})", pl, real(c.r)));
+    faces.push(w.face(surface, &[(c0, true), (seam, true), (c1, false), (seam, false)], None, true));
+
+    let (hole0, hole1) = if (inner_r > 0.0) {
+        let iv0 = w.vertex(&(sp + rd.mul(inner_r)));
+        let iv1 = w.vertex(&(ep + rd.mul(inner_r)));
+        let i0_curve = w.circle(&sp, &dir, &rd, inner_r);
+        let i0 = w.edge(iv0, iv0, i0_curve);
+        let i1_curve = w.circle(&ep, &dir, &rd, inner_r);
+        let i1 = w.edge(iv1, iv1, i1_curve);
+        let iseam_curve = w.line(&(sp + rd.mul(inner_r)), &(ep + rd.mul(inner_r)));
+        let iseam = w.edge(iv0, iv1, iseam_curve);
+        let ipl = w.placement(&sp, &dir, &rd);
+        let isurface = w.add(format!("CYLINDRICAL_SURFACE('',#{},{})", ipl, real(inner_r)));
+        faces.push(w.face(isurface, &[(iseam, true), (i1, true), (iseam, false), (i0, false)], None, false));
+        (Some([(i0, true)]), Some([(i1, false)]))
+    } else {
+        (None, None)
+    };
+    let p0 = w.plane(&sp, &(-dir), &rd);
+    faces.push(w.face(p0, &[(c0, false)], hole0.as_ref().map(|h| &h[..]), true));
+    let p1 = w.plane(&ep, &dir, &rd);
+    faces.push(w.face(p1, &[(c1, true)], hole1.as_ref().map(|h| &h[..]), true));
+    w.solid(name, &faces)
+}
+
+//Bend plane frame, x points from the bend center to the start of the bend
+fn bend_frame(t: &BendToro) -> (Vector3, Vector3, Vector3, f64) {
+    let sx = t.ca.loc.sub(t.bend_center_point).normalize();
+    let ex = t.cb.loc.sub(t.bend_center_point).normalize();
+    let plane_n = sx.cross(ex);
+    //a half turn leaves the plane to the start tangent
+    let n = if (plane_n.magnitude() > 1e-6) { plane_n.normalize() } else { sx.cross(t.ca.dir).normalize() };
+    let angle = sx.angle(ex).0;
+    (sx, ex, n, angle)
+}
+
+//Torus around the bend center, u runs along the bend and v around the tube from the outer side
+fn bend_solid(w: &mut StepWriter, name: &str, t: &BendToro, inner_r: f64) -> usize {
+    let (sx, ex, n, _angle) = bend_frame(t);
+    let sdir = n.cross(sx);
+    let edir = n.cross(ex);
+    let sp = t.bend_center_point + sx.mul(t.bend_radius);
+    let ep = t.bend_center_point + ex.mul(t.bend_radius);
+    let mut faces: Vec<usize> = vec![];
+
+    let ring = |w: &mut StepWriter, r: f64| -> (usize, usize, usize, usize) {
+        let v0 = w.vertex(&(sp + sx.mul(r)));
+        let v1 = w.vertex(&(ep + ex.mul(r)));
+        let e0_curve = w.circle(&sp, &(-sdir), &sx, r);
+        let e0 = w.edge(v0, v0, e0_curve);
+        let e1_curve = w.circle(&ep, &(-edir), &ex, r);
+        let e1 = w.edge(v1, v1, e1_curve);
+        let seam_curve = w.circle(&t.bend_center_point, &n, &sx, t.bend_radius + r);
+        let seam = w.edge(v0, v1, seam_curve);
+        let pl = w.placement(&t.bend_center_point, &n, &sx);
+        let surface = w.add(format!("TOROIDAL_SURFACE('',#{},{},{})", pl, real(t.bend_radius), real(r)));
+        (e0, e1, seam, surface)
+    };
+    let (e0, e1, seam, surface) = ring(w, t.r);
+    faces.push(w.face(surface, &[(seam, true), (e1, true), (seam, false), (e0, false)], None, true));
+    let (hole0, hole1) = if (inner_r > 0.0) {
+        let (i0, i1, iseam, isurface) = ring(w, inner_r);
+        faces.push(w.face(isurface, &[(i0, true), (iseam, true), (i1, false), (iseam, false)], None, false));
+        (Some([(i0, false)]), Some([(i1, true)]))
+    } else {
+        (None, None)
+    };
+    let p0 = w.plane(&sp, &(-sdir), &sx);
+    faces.push(w.face(p0, &[(e0, true)], hole0.as_ref().map(|h| &h[..]), true));
+    let p1 = w.plane(&ep, &edir, &ex);
+    faces.push(w.face(p1, &[(e1, false)], hole1.as_ref().map(|h| &h[..]), true));
+    w.solid(name, &faces)
+}
+
+fn centerline(w: &mut StepWriter, cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> usize {
+    let mut curves: Vec<String> = vec![];
+    cyls.iter().for_each(|c| {
+        let sp = w.point(&c.ca.loc);
+        let ep = w.point(&(c.ca.loc + c.ca.dir.normalize().mul(c.h)));
+        curves.push(format!("#{}", w.add(format!("POLYLINE('',(#{},#{}))", sp, ep))));
+    });
+    tors.iter().for_each(|t| {
+        let (sx, _ex, n, angle) = bend_frame(t);
+        let circle = w.circle(&t.bend_center_point, &n, &sx, t.bend_radius);
+        curves.push(format!(
+            "#{}",
+            w.add(format!("TRIMMED_CURVE('',#{},(PARAMETER_VALUE(0.)),(PARAMETER_VALUE({})),.T.,.PARAMETER.)", circle, real(angle)))
+        ));
+    });
+    w.add(format!("GEOMETRIC_CURVE_SET('centerline',({}))", curves.join(",")))
+}
+
+fn is_finite_point(p: &Point3) -> bool {
+    p.x.is_finite() && p.y.is_finite() && p.z.is_finite()
+}
+fn is_finite_vector(v: &Vector3) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+fn check_finite(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>) -> Result<(), StepExportError> {
+    for c in cyls {
+        let is_finite = c.h.is_finite()
+            && c.r.is_finite()
+            && is_finite_point(&c.ca.loc)
+            && is_finite_vector(&c.ca.dir.normalize())
+            && is_finite_vector(&c.ca.radius_dir.normalize());
+        if (!is_finite) {
+            return Err(StepExportError::NotFinite(format!("straight {}", c.id)));
+        }
+    }
+    for t in tors {
+        //a bend collapsed onto its center has no frame to write
+        let (sx, ex, n, angle) = bend_frame(t);
+        let is_finite = t.r.is_finite()
+            && t.bend_radius.is_finite()
+            && angle.is_finite()
+            && is_finite_point(&t.bend_center_point)
+            && is_finite_vector(&sx)
+            && is_finite_vector(&ex)
+            && is_finite_vector(&n);
+        if (!is_finite) {
+            return Err(StepExportError::NotFinite(format!("bend {}", t.id)));
+        }
+    }
+    Ok(())
+}
+//Sanity check, the file has to read back with the same parser the import uses
+fn parse_back(stp: &str, solids: usize) -> Result<(), StepExportError> {
+    let exchange = ruststep::parser::parse(stp).map_err(|e| StepExportError::ParseBack(format!("{:?}", e)))?;
+    match exchange.data.first() {
+        None => Err(StepExportError::ParseBack("no data section".to_string())),
+        Some(data) => {
+            let table: Table = Table::from_data_section(data);
+            if (table.shell.len() == solids) {
+                Ok(())
+            } else {
+                Err(StepExportError::ParseBack(format!("{} shells read back from {} solids", table.shell.len(), solids)))
+            }
+        }
+    }
+}
+
+pub fn tube_to_stp(cyls: &Vec<MainCylinder>, tors: &Vec<BendToro>, params: &StepExportParameters) -> Result<Vec<u8>, StepExportError> {
+    check_finite(cyls, tors)?;
+    let mut w = StepWriter::new();
+    let app = w.add("APPLICATION_CONTEXT('core data for automotive mechanical design processes')".to_string());
+    w.add(format!("APPLICATION_PROTOCOL_DEFINITION('international standard','automotive_design',2000,#{})", app));
+    let pc = w.add(format!("PRODUCT_CONTEXT('',#{},'mechanical')", app));
+    let pn = text(&params.part_number);
+    let product = w.add(format!("PRODUCT('{}','{}','bent tube',(#{}))", pn, pn, pc));
+    w.add(format!("PRODUCT_RELATED_PRODUCT_CATEGORY('part',$,(#{}))", product));
+    let pdf = w.add(format!("PRODUCT_DEFINITION_FORMATION('','',#{})", product));
+    let pdc = w.add(format!("PRODUCT_DEFINITION_CONTEXT('part definition',#{},'design')", app));
+    let pd = w.add(format!("PRODUCT_DEFINITION('design','',#{},#{})", pdf, pdc));
+    let pds = w.add(format!("PRODUCT_DEFINITION_SHAPE('','',#{})", pd));
+
+    let len_unit = w.add("(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT(.MILLI.,.METRE.))".to_string());
+    let angle_unit = w.add("(NAMED_UNIT(*)PLANE_ANGLE_UNIT()SI_UNIT($,.RADIAN.))".to_string());
+    let solid_angle_unit = w.add("(NAMED_UNIT(*)SI_UNIT($,.STERADIAN.)SOLID_ANGLE_UNIT())".to_string());
+    let uncertainty = w.add(format!("UNCERTAINTY_MEASURE_WITH_UNIT(LENGTH_MEASURE(1.E-06),#{},'distance_accuracy_value','confusion accuracy')", len_unit));
+    let ctx = w.add(format!(
+        "(GEOMETRIC_REPRESENTATION_CONTEXT(3)GLOBAL_UNCERTAINTY_ASSIGNED_CONTEXT((#{}))GLOBAL_UNIT_ASSIGNED_CONTEXT((#{},#{},#{}))REPRESENTATION_CONTEXT('Context #1','3D Context with UNIT and UNCERTAINTY'))",
+        uncertainty, len_unit, angle_unit, solid_angle_unit
+    ));
+
+    let inner_r = |r: f64| -> f64 { if (params.wall > 0.0 && params.wall < r) { r - params.wall } else { 0.0 } };
+    let straight_style = w.style(&STRAIGHT_RGB);
+    let bend_style = w.style(&BEND_RGB);
+    let mut solids: Vec<usize> = vec![];
+    let mut styled: Vec<usize> = vec![];
+    cyls.iter().for_each(|c| {
+        if (c.h > 0.0) {
+            let s = straight_solid(&mut w, format!("straight {}", c.id).as_str(), c, inner_r(c.r));
+            solids.push(s);
+            styled.push(w.add(format!("STYLED_ITEM('',(#{}),#{})", straight_style, s)));
+        }
+    });
+    tors.iter().for_each(|t| {
+        let s = bend_solid(&mut w, format!("bend {}", t.id).as_str(), t, inner_r(t.r));
+        solids.push(s);
+        styled.push(w.add(format!("STYLED_ITEM('',(#{}),#{})", bend_style, s)));
+    });
+
+    let origin = w.placement(&Point3::new(0.0, 0.0, 0.0), &Vector3::new(0.0, 0.0, 1.0), &Vector3::new(1.0, 0.0, 0.0));
+    let mut items: Vec<String> = solids.iter().map(|s| format!("#{}", s)).collect();
+    items.push(format!("#{}", origin));
+    let rep = w.add(format!("ADVANCED_BREP_SHAPE_REPRESENTATION('{}',({}),#{})", pn, items.join(","), ctx));
+    w.add(format!("SHAPE_DEFINITION_REPRESENTATION(#{},#{})", pds, rep));
+    let styled_ids: Vec<String> = styled.iter().map(|s| format!("#{}", s)).collect();
+    w.add(format!("MECHANICAL_DESIGN_GEOMETRIC_PRESENTATION_REPRESENTATION('',({}),#{})", styled_ids.join(","), ctx));
+    if (params.is_with_centerline) {
+        let curve_set = centerline(&mut w, cyls, tors);
+        let wire_rep = w.add(format!("GEOMETRICALLY_BOUNDED_WIREFRAME_SHAPE_REPRESENTATION('centerline',(#{},#{}),#{})", curve_set, origin, ctx));
+        w.add(format!("SHAPE_REPRESENTATION_RELATIONSHIP('','centerline',#{},#{})", rep, wire_rep));
+    }
+    let stp = w.to_string(&params.part_number);
+    parse_back(&stp, solids.len())?;
+    Ok(stp.into_bytes())
+}
@@ -0,0 +1,263 @@
+use crate::algo::cnc::LRACLR;
+use cgmath::num_traits::abs;
+use std::fmt::{Display, Formatter};
+use crate::utils::wire::{WireKind, WireMessage, WireRecord};
+
+const OD_TOLE: f64 = 0.1;
+const CLR_TOLE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolKind {
+    BendDie = 0,
+    ClampDie = 1,
+    PressureDie = 2,
+    Mandrel = 3,
+    WiperDie = 4,
+}
+impl ToolKind {
+    pub fn from_f32(v: f32) -> Option<ToolKind> {
+        match v as i32 {
+            0 => Some(ToolKind::BendDie),
+            1 => Some(ToolKind::ClampDie),
+            2 => Some(ToolKind::PressureDie),
+            3 => Some(ToolKind::Mandrel),
+            4 => Some(ToolKind::WiperDie),
+            _ => None,
+        }
+    }
+}
+
+//clr is used by bend dies and wiper dies only, len is the stack height for bend dies
+//and the working length for clamp dies, pressure dies and mandrels
+#[derive(Debug, Clone)]
+pub struct Tool {
+    pub id: i32,
+    pub kind: ToolKind,
+    pub od: f64,
+    pub clr: f64,
+    pub len: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolLibrary {
+    pub tools: Vec<Tool>,
+    pub od_tole: f64,
+    pub clr_tole: f64,
+}
+impl ToolLibrary {
+    pub fn default() -> Self {
+        Self {
+            tools: vec![],
+            od_tole: OD_TOLE,
+            clr_tole: CLR_TOLE,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+    //[kind, id, od, clr, len] per tool
+    pub fn from_f32vec(vec: &Vec<f32>) -> Self {
+        let mut ret = ToolLibrary::default();
+        vec.chunks(5).for_each(|c| {
+            if (c.len() == 5) {
+                match ToolKind::from_f32(c[0]) {
+                    None => {}
+                    Some(kind) => ret.tools.push(Tool {
+                        id: c[1] as i32,
+                        kind: kind,
+                        od: c[2] as f64,
+                        clr: c[3] as f64,
+                        len: c[4] as f64,
+                    }),
+                }
+            }
+        });
+        ret
+    }
+    pub fn to_f32vec(&self) -> Vec<f32> {
+        let mut ret: Vec<f32> = vec![];
+        self.tools.iter().for_each(|t| {
+            ret.push(t.kind as i32 as f32);
+            ret.push(t.id as f32);
+            ret.push(t.od as f32);
+            ret.push(t.clr as f32);
+            ret.push(t.len as f32);
+        });
+        ret
+    }
+    fn find_by_od(&self, kind: ToolKind, od: f64) -> Option<Tool> {
+        self.tools
+            .iter()
+            .filter(|t| t.kind == kind && abs(t.od - od) <= self.od_tole)
+            .min_by(|a, b| abs(a.od - od).total_cmp(&abs(b.od - od)))
+            .cloned()
+    }
+    fn find_by_od_clr(&self, kind: ToolKind, od: f64, clr: f64) -> Option<Tool> {
+        self.tools
+            .iter()
+            .filter(|t| t.kind == kind && abs(t.od - od) <= self.od_tole && abs(t.clr - clr) <= self.clr_tole)
+            .min_by(|a, b| abs(a.clr - clr).total_cmp(&abs(b.clr - clr)))
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolStack {
+    pub level: usize,
+    pub height: f64,
+    pub bend_die: Tool,
+    pub clamp_die: Tool,
+    pub pressure_die: Tool,
+    pub mandrel: Option<Tool>,
+    pub wiper_die: Option<Tool>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ToolOp {
+    Change(usize),
+    Bend { id1: i32, id2: i32, stack: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolPlan {
+    pub stacks: Vec<ToolStack>,
+    pub ops: Vec<ToolOp>,
+}
+impl ToolPlan {
+    pub fn changes_count(&self) -> usize {
+        self.ops.iter().filter(|op| matches!(op, ToolOp::Change(_))).count()
+    }
+    //Row fields 1 opcode (0 tool change, 1 bend), 2 stack, 3 id1, 4 id2, 5 bend_die_id, ids are -1 on a tool change
+    pub fn to_wire(&self) -> WireMessage {
+        let mut msg = WireMessage::new(WireKind::ToolPlan);
+        self.ops.iter().for_each(|op| {
+            let mut row = WireRecord::new();
+            match op {
+                ToolOp::Change(stack) => {
+                    row.push(1, 0.0);
+                    row.push(2, *stack as f64);
+                    row.push(3, -1.0);
+                    row.push(4, -1.0);
+                    row.push(5, self.stacks[*stack].bend_die.id as f64);
+                }
+                ToolOp::Bend { id1, id2, stack } => {
+                    row.push(1, 1.0);
+                    row.push(2, *stack as f64);
+                    row.push(3, *id1 as f64);
+                    row.push(4, *id2 as f64);
+                    row.push(5, self.stacks[*stack].bend_die.id as f64);
+                }
+            }
+            msg.rows.push(row);
+        });
+        msg
+    }
+}
+impl Display for ToolPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.stacks.iter().for_each(|s| {
+            let _ = writeln!(
+                f,
+                "stack {} height {} bend die {} clr {} clamp die {} pressure die {} mandrel {:?} wiper {:?}",
+                s.level,
+                s.height,
+                s.bend_die.id,
+                s.bend_die.clr,
+                s.clamp_die.id,
+                s.pressure_die.id,
+                s.mandrel.as_ref().map(|t| t.id),
+                s.wiper_die.as_ref().map(|t| t.id)
+            );
+        });
+        self.ops.iter().for_each(|op| match op {
+            ToolOp::Change(stack) => {
+                let _ = writeln!(f, "TOOL CHANGE stack {}", stack);
+            }
+            ToolOp::Bend { id1, id2, stack } => {
+                let _ = writeln!(f, "BEND {}/{} stack {}", id1, id2, stack);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolError {
+    NoBendDie { id1: i32, id2: i32, od: f64, clr: f64 },
+    NoClampDie { id1: i32, id2: i32, od: f64 },
+    NoPressureDie { id1: i32, id2: i32, od: f64 },
+}
+impl ToolError {
+    pub fn mesh_id(&self) -> i32 {
+        match self {
+            ToolError::NoBendDie { id2, .. } => *id2,
+            ToolError::NoClampDie { id1, .. } => *id1,
+            ToolError::NoPressureDie { id1, .. } => *id1,
+        }
+    }
+}
+impl Display for ToolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::NoBendDie { id1, id2, od, clr } => write!(f, "{}/{} no bend die for OD {} CLR {}", id1, id2, od, clr),
+            ToolError::NoClampDie { id1, id2, od } => write!(f, "{}/{} no clamp die for OD {}", id1, id2, od),
+            ToolError::NoPressureDie { id1, id2, od } => write!(f, "{}/{} no pressure die for OD {}", id1, id2, od),
+        }
+    }
+}
+
+//Every distinct bend die gets its own stack level, stacks are ordered by first use
+//so the tool changes follow the bending sequence
+pub fn assign_tools(lraclr_arr: &Vec<LRACLR>, library: &ToolLibrary) -> Result<ToolPlan, Vec<ToolError>> {
+    let mut errors: Vec<ToolError> = vec![];
+    let mut plan = ToolPlan { stacks: vec![], ops: vec![] };
+    let mut curr_stack: Option<usize> = None;
+    lraclr_arr.iter().for_each(|lra| {
+        if (lra.clr > 0.0) {
+            let od = lra.pipe_radius * 2.0;
+            let bend_die = library.find_by_od_clr(ToolKind::BendDie, od, lra.clr);
+            let clamp_die = library.find_by_od(ToolKind::ClampDie, od);
+            let pressure_die = library.find_by_od(ToolKind::PressureDie, od);
+            if (bend_die.is_none()) {
+                errors.push(ToolError::NoBendDie { id1: lra.id1, id2: lra.id2, od: od, clr: lra.clr });
+            }
+            if (clamp_die.is_none()) {
+                errors.push(ToolError::NoClampDie { id1: lra.id1, id2: lra.id2, od: od });
+            }
+            if (pressure_die.is_none()) {
+                errors.push(ToolError::NoPressureDie { id1: lra.id1, id2: lra.id2, od: od });
+            }
+            match (bend_die, clamp_die, pressure_die) {
+                (Some(bend_die), Some(clamp_die), Some(pressure_die)) => {
+                    let stack = match plan.stacks.iter().position(|s| s.bend_die.id == bend_die.id) {
+                        Some(level) => level,
+                        None => {
+                            let level = plan.stacks.len();
+                            plan.stacks.push(ToolStack {
+                                level: level,
+                                height: bend_die.len,
+                                mandrel: library.find_by_od(ToolKind::Mandrel, od),
+                                wiper_die: library.find_by_od_clr(ToolKind::WiperDie, od, bend_die.clr),
+                                bend_die: bend_die,
+                                clamp_die: clamp_die,
+                                pressure_die: pressure_die,
+                            });
+                            level
+                        }
+                    };
+                    if (curr_stack != Some(stack)) {
+                        plan.ops.push(ToolOp::Change(stack));
+                        curr_stack = Some(stack);
+                    }
+                    plan.ops.push(ToolOp::Bend { id1: lra.id1, id2: lra.id2, stack: stack });
+                }
+                _ => {}
+            }
+        }
+    });
+    if (errors.is_empty()) {
+        Ok(plan)
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,443 @@
+use crate::algo::allowance::{apply_allowances, strip_allowances};
+use crate::algo::bendability::assess_lraclr;
+use crate::algo::cnc::{cnc_to_poly, LRACLR};
+use crate::algo::collision::simulate_collisions;
+use crate::algo::cycle::cycle_time;
+use crate::algo::diff::ProgramDiff;
+use crate::algo::deviation::{analyze_stp_verified, DeviationReport, MAX_DEVIATION};
+use crate::algo::drawing::{program_drawing, DrawingInfo};
+use crate::algo::machine::validate_lraclr;
+use crate::algo::mesh_export::{export_mesh, MeshFormat};
+use crate::algo::output::{from_output, to_output, FeedMode, RotationMode, RotationSense};
+use crate::algo::project::Project;
+use crate::algo::step_export::{tube_to_stp, StepExportParameters};
+use crate::device::graphics::BendParameters;
+use crate::utils::wire::{WireKind, WireMessage};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+//Headless entry point. Nothing here touches winit or wgpu, so it runs on servers without a GPU.
+//Data goes to stdout or --out, diagnostics to stderr
+const COMMANDS: [&str; 6] = ["analyze", "export", "validate", "batch", "diff", "help"];
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+const USAGE: &str = "usage: freetubew <command> [options]
+  analyze <part.stp> [--out program.csv|json] [--format csv|json] [--tolerance mm] [--settings project.ftwp] [--datum x,y,z|face:id]
+      recognise the program and check it against the STEP faces, machine limits, bendability and cycle time
+  export <input> --out <file> [--format stp|stl|obj|glb|svg|dxf|csv|json|wire] [--settings project.ftwp]
+      input is a STEP file, a project, a program CSV or a wire program
+  validate <input> [--settings project.ftwp]
+      machine limits, collisions with the machine obstacles and bendability, exit code 1 on any finding
+  batch <dir> [--out dir] [--format csv|json] [--report summary.csv] [--tolerance mm] [--settings project.ftwp]
+      analyze every STEP file of a directory
+  diff <a> <b> [--out diff.wire] [--settings project.ftwp]
+      matched, changed, added and removed bends of revision b against revision a, exit code 1 when they differ
+  --datum sets what points up at loading for STEP input, a model direction or a planar or cylindrical face
+  --rotation inc|abs, --feed inc|abs and --sense ccw|cw set how program files are written and read,
+      absolute feed is the carriage position from the leading end of the tube
+  without a command the viewer is started";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    Usage(String),
+    Io(String),
+    Input(String),
+}
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(e) => write!(f, "{}\n{}", e, USAGE),
+            CliError::Io(e) => write!(f, "{}", e),
+            CliError::Input(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+struct CliArgs {
+    command: String,
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+impl CliArgs {
+    fn parse(args: &Vec<String>) -> Result<CliArgs, CliError> {
+        let mut ret = CliArgs { command: args[0].clone(), positional: vec![], options: vec![] };
+        let mut i = 1;
+        while (i < args.len()) {
+            let a = &args[i];
+            if (a.starts_with("--")) {
+                match args.get(i + 1) {
+                    None => return Err(CliError::Usage(format!("{} needs a value", a))),
+                    Some(v) => ret.options.push((a.trim_start_matches("--").to_string(), v.clone())),
+                }
+                i = i + 2;
+            } else {
+                ret.positional.push(a.clone());
+                i = i + 1;
+            }
+        }
+        Ok(ret)
+    }
+    fn get(&self, key: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+    fn input(&self) -> Result<&str, CliError> {
+        match self.positional.first() {
+            None => Err(CliError::Usage(format!("{} needs an input file", self.command))),
+            Some(p) => Ok(p.as_str()),
+        }
+    }
+    fn tolerance(&self) -> Result<f64, CliError> {
+        match self.get("tolerance") {
+            None => Ok(MAX_DEVIATION),
+            Some(v) => v.parse::<f64>().map_err(|_| CliError::Usage(format!("bad tolerance {}", v))),
+        }
+    }
+    //Machine, stock and allowance settings come from a project file, the defaults otherwise
+    fn settings(&self) -> Result<Project, CliError> {
+        let mut ret = match self.get("settings") {
+            None => Project::default(),
+            Some(path) => Project::load(path).map_err(|e| CliError::Input(format!("{}: {}", path, e)))?,
+        };
+        match self.get("datum") {
+            None => {}
+            Some(v) => {
+                let params: Vec<f32> = if (v == "none") {
+                    vec![0.0]
+                } else if (v.starts_with("face:")) {
+                    let id = v.trim_start_matches("face:").parse::<f32>().map_err(|_| CliError::Usage(format!("bad datum {}", v)))?;
+                    vec![2.0, id]
+                } else {
+                    let xyz: Vec<f32> = v.split(',').filter_map(|c| c.trim().parse::<f32>().ok()).collect();
+                    if (xyz.len() != 3) {
+                        return Err(CliError::Usage(format!("bad datum {}", v)));
+                    }
+                    vec![1.0, xyz[0], xyz[1], xyz[2]]
+                };
+                ret.datum.set_params_from_f32vec(&params);
+            }
+        }
+        match self.get("rotation") {
+            None => {}
+            Some("inc") => ret.machine.output.rotation = RotationMode::Incremental,
+            Some("abs") => ret.machine.output.rotation = RotationMode::Absolute,
+            Some(v) => return Err(CliError::Usage(format!("bad rotation {}", v))),
+        }
+        match self.get("feed") {
+            None => {}
+            Some("inc") => ret.machine.output.feed = FeedMode::Incremental,
+            Some("abs") => ret.machine.output.feed = FeedMode::Absolute,
+            Some(v) => return Err(CliError::Usage(format!("bad feed {}", v))),
+        }
+        match self.get("sense") {
+            None => {}
+            Some("ccw") => ret.machine.output.sense = RotationSense::Ccw,
+            Some("cw") => ret.machine.output.sense = RotationSense::Cw,
+            Some(v) => return Err(CliError::Usage(format!("bad sense {}", v))),
+        }
+        Ok(ret)
+    }
+}
+
+//A part loaded from any input, program is the finished part and machine_program has the allowances on it.
+//A csv or wire input is bent as it is, both are the same then
+struct CliJob {
+    name: String,
+    settings: Project,
+    program: Vec<LRACLR>,
+    machine_program: Vec<LRACLR>,
+    deviation: Option<DeviationReport>,
+}
+impl CliJob {
+    fn load(path: &str, settings: &Project, tolerance: f64) -> Result<CliJob, CliError> {
+        let data = std::fs::read(path).map_err(|e| CliError::Io(format!("{}: {}", path, e)))?;
+        let name = Path::new(path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("tube".to_string());
+        let mut settings = settings.clone();
+        let ext = extension(path);
+        //csv and wire files are machine programs, the allowances are on them already
+        let (program, deviation, is_machine_program) = if (ext == "stp" || ext == "step") {
+            let (program, report) = analyze_stp_verified(&data, tolerance, &settings.datum).map_err(|e| CliError::Input(format!("{}: {}", path, e)))?;
+            if (program.is_empty()) {
+                return Err(CliError::Input(format!("{}: no tube recognised", path)));
+            }
+            (program, Some(report), false)
+        } else if (ext == "ftwp") {
+            let project = Project::from_bytes(&data).map_err(|e| CliError::Input(format!("{}: {}", path, e)))?;
+            let program = strip_allowances(&project.program, &project.allowance);
+            //an output mode from the command line or --settings wins over the one saved with the project
+            let output = settings.machine.output.clone();
+            settings = project;
+            if (!output.is_default()) {
+                settings.machine.output = output;
+            }
+            (program, None, false)
+        } else if (ext == "csv") {
+            let program = LRACLR::from_csv(String::from_utf8_lossy(&data).as_ref()).map_err(|e| CliError::Input(format!("{}: {}", path, e)))?;
+            (from_output(&program, &settings.machine.output), None, true)
+        } else {
+            let msg = WireMessage::from_bytes(data.as_slice()).and_then(|msg| msg.expect(WireKind::Program)).map_err(|e| CliError::Input(format!("{}: {}", path, e)))?;
+            (from_output(&LRACLR::from_wire(&msg), &settings.machine.output), None, true)
+        };
+        if (program.is_empty()) {
+            return Err(CliError::Input(format!("{}: the program is empty", path)));
+        }
+        let machine_program = if (is_machine_program) { program.clone() } else { apply_allowances(&program, &settings.machine, &settings.allowance_params).0 };
+        if (settings.job.part_number.is_empty()) {
+            settings.job.part_number = name.clone();
+        }
+        Ok(CliJob { name: name, settings: settings, program: program, machine_program: machine_program, deviation: deviation })
+    }
+    fn program_file(&self, format: &str) -> Result<Vec<u8>, CliError> {
+        let program = to_output(&self.machine_program, &self.settings.machine.output);
+        match format {
+            "csv" => Ok(LRACLR::to_csv(&program).into_bytes()),
+            "json" => Ok(LRACLR::to_json(&program, self.settings.job.part_number.as_str()).into_bytes()),
+            "wire" => Ok(LRACLR::to_wire(&program).to_bytes()),
+            _ => Err(CliError::Usage(format!("unknown program format {}", format))),
+        }
+    }
+    //Findings that stop the part from being bent, one line each
+    fn findings(&self) -> Vec<String> {
+        let mut ret: Vec<String> = vec![];
+        match &self.deviation {
+            Some(d) if d.is_failed() => ret.push(format!("DEVIATION {}", d).trim_end().to_string()),
+            _ => {}
+        }
+        validate_lraclr(&self.machine_program, &self.settings.machine).iter().for_each(|v| ret.push(format!("MACHINE LIMIT {}", v)));
+        if (!self.settings.machine.obstacles.is_empty()) {
+            match simulate_collisions(&self.machine_program, &self.settings.v_up_orign, &BendParameters::default(), &self.settings.machine.obstacles) {
+                None => {}
+                Some(c) => ret.push(format!("COLLISION {}", c)),
+            }
+        }
+        assess_lraclr(&self.machine_program, &self.settings.stock).iter().filter(|a| a.is_risky()).for_each(|a| ret.push(format!("BEND RISK {}", a)));
+        ret
+    }
+    fn summary(&self) -> String {
+        let (tl, outd) = LRACLR::total_len_out_d(&self.machine_program);
+        let bends = self.machine_program.iter().filter(|lra| lra.clr > 0.0).count();
+        let cycle = cycle_time(&self.machine_program, &self.settings.machine.cycle);
+        format!("{} OD {:.2} cut length {:.1} bends {} {}", self.name, outd * 2.0, tl, bends, cycle)
+    }
+}
+
+pub fn is_command(args: &Vec<String>) -> bool {
+    match args.first() {
+        None => false,
+        Some(a) => COMMANDS.contains(&a.as_str()) || a == "--help" || a == "-h",
+    }
+}
+
+//Exit code 0 when everything passed, 1 when a part has findings, 2 on bad arguments or files
+pub fn run(args: &Vec<String>) -> i32 {
+    let result = CliArgs::parse(args).and_then(|args| match args.command.as_str() {
+        "analyze" => analyze(&args),
+        "export" => export(&args),
+        "validate" => validate(&args),
+        "batch" => batch(&args),
+        "diff" => diff(&args),
+        _ => {
+            println!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+    });
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn analyze(args: &CliArgs) -> Result<i32, CliError> {
+    let job = CliJob::load(args.input()?, &args.settings()?, args.tolerance()?)?;
+    let format = args.get("format").map(|f| f.to_string()).unwrap_or(args.get("out").map(|o| extension(o)).unwrap_or("csv".to_string()));
+    let file = job.program_file(format.as_str())?;
+    write_output(args.get("out"), &file)?;
+    eprintln!("{}", job.summary());
+    match &job.deviation {
+        None => {}
+        Some(d) => eprint!("DEVIATION {}", d),
+    }
+    let findings = job.findings();
+    findings.iter().for_each(|f| eprintln!("{}", f));
+    Ok(if (findings.is_empty()) { EXIT_OK } else { EXIT_FAILED })
+}
+
+fn export(args: &CliArgs) -> Result<i32, CliError> {
+    let out = match args.get("out") {
+        None => return Err(CliError::Usage("export needs --out".to_string())),
+        Some(o) => o,
+    };
+    let job = CliJob::load(args.input()?, &args.settings()?, args.tolerance()?)?;
+    let format = args.get("format").map(|f| f.to_string()).unwrap_or(extension(out));
+    let name = job.settings.job.part_number.clone();
+    let file = match format.as_str() {
+        "stp" | "step" => {
+            let (cyls, tors) = cnc_to_poly(&job.program, &job.settings.v_up_orign);
+            let mut params = StepExportParameters::default();
+            params.part_number = name;
+            params.wall = job.settings.stock.wall;
+            tube_to_stp(&cyls, &tors, &params).map_err(|e| CliError::Input(format!("{}: {}", out, e)))?
+        }
+        "stl" | "obj" | "glb" => {
+            let format = match format.as_str() {
+                "stl" => MeshFormat::Stl,
+                "obj" => MeshFormat::Obj,
+                _ => MeshFormat::Gltf,
+            };
+            let (cyls, tors) = cnc_to_poly(&job.program, &job.settings.v_up_orign);
+            export_mesh(&cyls, &tors, format, name.as_str())
+        }
+        "svg" | "dxf" => {
+            let info = DrawingInfo {
+                part_number: name,
+                material: job.settings.stock.material.name.clone(),
+                wall: job.settings.stock.wall,
+                notes: job.settings.job.notes.clone(),
+                output: job.settings.machine.output.clone(),
+            };
+            let drawing = program_drawing(&job.machine_program, &job.settings.v_up_orign, &info);
+            if (format == "svg") { drawing.to_svg().into_bytes() } else { drawing.to_dxf().into_bytes() }
+        }
+        _ => job.program_file(format.as_str())?,
+    };
+    write_output(Some(out), &file)?;
+    eprintln!("{}", job.summary());
+    Ok(EXIT_OK)
+}
+
+fn validate(args: &CliArgs) -> Result<i32, CliError> {
+    let job = CliJob::load(args.input()?, &args.settings()?, args.tolerance()?)?;
+    println!("{}", job.summary());
+    let findings = job.findings();
+    findings.iter().for_each(|f| println!("{}", f));
+    if (findings.is_empty()) {
+        println!("PASSED");
+        Ok(EXIT_OK)
+    } else {
+        println!("FAILED {} findings", findings.len());
+        Ok(EXIT_FAILED)
+    }
+}
+
+//Both inputs are read with the same settings, the report goes to stdout and the rows to --out as wire
+fn diff(args: &CliArgs) -> Result<i32, CliError> {
+    let (path_a, path_b) = match (args.positional.get(0), args.positional.get(1)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(CliError::Usage("diff needs two input files".to_string())),
+    };
+    let settings = args.settings()?;
+    let tolerance = args.tolerance()?;
+    let job_a = CliJob::load(path_a, &settings, tolerance)?;
+    let job_b = CliJob::load(path_b, &settings, tolerance)?;
+    let diff = ProgramDiff::compare(&job_a.program, &job_b.program, &job_a.settings.v_up_orign);
+    match args.get("out") {
+        None => {}
+        Some(out) => write_output(Some(out), &diff.to_wire().to_bytes())?,
+    }
+    print!("{}", diff);
+    if (diff.is_same()) {
+        println!("SAME");
+        Ok(EXIT_OK)
+    } else {
+        Ok(EXIT_FAILED)
+    }
+}
+
+//One summary row per STEP file, a file that can not be read is reported and the batch goes on
+fn batch(args: &CliArgs) -> Result<i32, CliError> {
+    let dir = args.input()?;
+    let settings = args.settings()?;
+    let tolerance = args.tolerance()?;
+    let format = args.get("format").unwrap_or("csv");
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+        .map_err(|e| CliError::Io(format!("{}: {}", dir, e)))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_string_lossy().to_string())
+        .filter(|p| {
+            let ext = extension(p);
+            ext == "stp" || ext == "step"
+        })
+        .collect();
+    files.sort();
+    let mut report = String::from("file,status,od,cut_length,bends,max_deviation,cycle_time,findings\n");
+    let mut failed: usize = 0;
+    files.iter().for_each(|path| {
+        let file_name = Path::new(path).file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or(path.clone());
+        match CliJob::load(path, &settings, tolerance) {
+            Ok(job) => {
+                let findings = job.findings();
+                let (tl, outd) = LRACLR::total_len_out_d(&job.machine_program);
+                let bends = job.machine_program.iter().filter(|lra| lra.clr > 0.0).count();
+                let deviation = job.deviation.as_ref().map(|d| d.max).unwrap_or(0.0);
+                let cycle = cycle_time(&job.machine_program, &job.settings.machine.cycle);
+                let status = if (findings.is_empty()) { "OK" } else { "FAILED" };
+                if (!findings.is_empty()) {
+                    failed = failed + 1;
+                }
+                report.push_str(
+                    format!("{},{},{:.2},{:.1},{},{:.3},{:.1},\"{}\"\n", file_name, status, outd * 2.0, tl, bends, deviation, cycle.total, findings.join("; ").replace('"', "'")).as_str(),
+                );
+                match args.get("out") {
+                    None => {}
+                    Some(out_dir) => match job.program_file(format) {
+                        Ok(file) => {
+                            let out = Path::new(out_dir).join(format!("{}.{}", job.name, format));
+                            match std::fs::write(&out, &file) {
+                                Ok(_) => {}
+                                Err(e) => eprintln!("{}: {}", out.to_string_lossy(), e),
+                            }
+                        }
+                        Err(e) => eprintln!("{}", e),
+                    },
+                }
+                eprintln!("{} {}", status, job.summary());
+            }
+            Err(e) => {
+                failed = failed + 1;
+                report.push_str(format!("{},ERROR,,,,,,\"{}\"\n", file_name, e.to_string().replace('"', "'")).as_str());
+                eprintln!("ERROR {}", e);
+            }
+        }
+    });
+    write_output(args.get("report"), &report.into_bytes())?;
+    eprintln!("{} files, {} failed", files.len(), failed);
+    Ok(if (failed == 0) { EXIT_OK } else { EXIT_FAILED })
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or(String::new())
+}
+
+fn write_output(out: Option<&str>, data: &Vec<u8>) -> Result<(), CliError> {
+    match out {
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(data).map_err(|e| CliError::Io(e.to_string()))
+        }
+        Some(path) => std::fs::write(path, data).map_err(|e| CliError::Io(format!("{}: {}", path, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_skips_unreadable_step_files() {
+        let dir = std::env::temp_dir().join(format!("cli_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.stp"), [0xff_u8, 0xfe, 0x00, 0x13, 0x37, 0x80, 0x81]).unwrap();
+        std::fs::write(dir.join("b.step"), "ISO-10303-21;\nDATA;\n#1=CARTESIAN_POINT(".as_bytes()).unwrap();
+        let report = dir.join("report.csv");
+        let args: Vec<String> = vec!["batch".to_string(), dir.to_string_lossy().to_string(), "--report".to_string(), report.to_string_lossy().to_string()];
+        let code = run(&args);
+        let rows = std::fs::read_to_string(&report).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(code, EXIT_FAILED);
+        assert!(rows.contains("a.stp,ERROR"), "{}", rows);
+        assert!(rows.contains("b.step,ERROR"), "{}", rows);
+    }
+}
@@ -15,7 +15,7 @@ use crate::device::background_pipleine::BackGroundPipeLine;
 use crate::device::camera::{camera_zoom, update_camera_by_mouse, Camera};
 #[cfg(target_arch = "wasm32")]
 use crate::device::graphics::check_remote;
-use crate::device::graphics::{init_graphics, key_frame, mouse_left_pressed, mouse_move, on_keyboard, render, render_selection, resize_window, set_right_mouse_pressed, unset_right_mouse_pressed, GlobalState, Graphics};
+use crate::device::graphics::{init_graphics, key_frame, mouse_left_pressed, mouse_move, on_keyboard, open_file, render, render_selection, resize_window, set_right_mouse_pressed, unset_right_mouse_pressed, GlobalState, Graphics};
 use crate::device::mesh_pipeline::MeshPipeLine;
 use crate::device::txt_pipeline::TxtPipeLine;
 use winit::application::ApplicationHandler;
@@ -29,15 +29,18 @@ pub struct App {
     pub world: World,
     pub event_loop_proxy: Arc<EventLoopProxy<Graphics>>,
     pub is_world_up: bool,
+    //file given on the command line, opened once the scene is up
+    pub open_on_start: Option<String>,
 }
 
 impl App {
-    pub fn new(el: &EventLoop<Graphics>) -> Self {
+    pub fn new(el: &EventLoop<Graphics>, open_on_start: Option<String>) -> Self {
         let event_loop_proxy: EventLoopProxy<Graphics> = el.create_proxy();
         Self {
             world: World::new(),
             event_loop_proxy: Arc::new(event_loop_proxy),
             is_world_up: false,
+            open_on_start: open_on_start,
         }
     }
 }
@@ -65,6 +68,10 @@ impl ApplicationHandler<Graphics> for App {
         self.is_world_up = true;
         let w = event.window.clone();
         init_graphics(&self.world, event);
+        match self.open_on_start.take() {
+            None => {}
+            Some(path) => self.world.run_with_data(open_file, path),
+        }
         w.request_redraw();
 
         //self.world.add_unique(event);
@@ -96,7 +103,9 @@ impl ApplicationHandler<Graphics> for App {
                 }
                 WindowEvent::Moved(_) => {}
                 WindowEvent::Destroyed => {}
-                WindowEvent::DroppedFile(_) => {}
+                WindowEvent::DroppedFile(path) => {
+                    self.world.run_with_data(open_file, path.to_string_lossy().to_string());
+                }
                 WindowEvent::HoveredFile(_) => {}
                 WindowEvent::HoveredFileCancelled => {}
                 WindowEvent::Focused(_) => {}
@@ -1,19 +1,44 @@
-use crate::algo::cnc::{all_to_one, all_to_stp, cnc_to_poly, LRACLR};
-use crate::algo::{analyze_stp, cnc, BendToro, MainCylinder, P_UP, P_UP_REVERSE};
+use crate::algo::allowance::{apply_allowances, strip_allowances, Allowance, AllowanceParameters};
+use crate::algo::cnc::{all_to_one, cnc_to_poly, split_allowances, LRACLR};
+use crate::algo::{cnc, BendToro, MainCylinder, P_UP, P_UP_REVERSE, TOLE};
+use crate::algo::anim_export::bending_to_glb;
+use crate::algo::datum::RotationDatum;
+use crate::algo::diff::{align_first_rotation, DiffKind, ProgramDiff};
+use crate::algo::drawing::{program_drawing, DrawingFormat, DrawingInfo};
+use crate::algo::edit::{apply_edit, EditError, EditOp, ProgramEditor};
+use crate::algo::marking::MarkingLayout;
+use crate::algo::mirror::{mirror_deviation, mirror_lraclr, MirrorPlane};
+use crate::algo::mesh_export::{export_mesh, MeshFormat, MESH_EXPORT_FILE};
+use crate::algo::machine::{validate_lraclr, MachineProfile, Violation};
+use crate::algo::collision::{simulate_collisions, simulate_to_step, Collision, Obstacle};
+use crate::algo::optimizer::{optimize_program, ProgramCandidate};
+use crate::algo::output::{from_output, to_output};
+use crate::algo::cycle::{cycle_time, CycleTime};
+use crate::algo::deviation::{analyze_stp_verified, MAX_DEVIATION};
+use crate::algo::bendability::{assess_lraclr, BendAssessment, TubeStock};
+use crate::algo::nesting::{nest_parts, CutPlan, NestPart, NestingParameters};
+use crate::algo::project::{JobInfo, Project, PROJECT_FILE};
+use crate::algo::roll::{convert_large_bends, LargeBendMode, RollInstruction, RollParameters};
+use crate::algo::simulation::{simulate_at, SimSeek, SimTimeline};
+use crate::algo::step_export::{tube_to_stp, StepExportError, StepExportParameters, STP_EXPORT_FILE};
+use crate::algo::tools::{assign_tools, ToolError, ToolLibrary, ToolOp, ToolPlan};
 use crate::device::background_pipleine::BackGroundPipeLine;
 use crate::device::camera::Camera;
-use crate::device::graphics::States::{ChangeDornDir, Dismiss, FullAnimate, LoadLRA, NewBendParams, ReadyToLoad, ReverseLRACLR, SelectFromWeb};
+use crate::device::graphics::States::{AddToCutList, ChangeDornDir, CheckCollisions, ClearCutList, CompareLRA, CompareProgram, Dismiss, EditProgram, ExportAnimation, ExportDrawing, ExportMarking, ExportMesh, ExportProgramStp, ExportStp, FullAnimate, LoadLRA, MirrorProgram, NewOutputMode, NewRotationDatum, OpenFile, ShowBothHands, LoadProgram, LoadProject, LoadStp, NewBendParams, NewJobInfo, NewMachineProfile, ConvertLargeBends, NewAllowanceParams, NewCycleParams, NewNestingParams, NewObstacles, NewRollParams, NewToolLibrary, NewTubeStock, OptimizeProgram, PauseSimulation, PlaySimulation, ReadyToLoad, RedoEdit, ReverseLRACLR, SaveProject, SeekSimulation, SelectFromWeb, UndoEdit};
 use crate::device::mesh_pipeline::MeshPipeLine;
 use crate::device::txt_pipeline::TxtPipeLine;
 use crate::device::MeshVertex;
 #[cfg(target_arch = "wasm32")]
 use crate::remote::in_state::bend_settings;
 #[cfg(target_arch = "wasm32")]
+use crate::remote::in_state::{cycle_settings, allowance_settings, machine_settings, nesting_settings, pipe_allowance, pipe_collision, pipe_cycle_time, pipe_deviation, pipe_diff, pipe_optimizer_results, pipe_bendability, pipe_cut_plan, pipe_drawing, pipe_edit_state, pipe_marking, pipe_mesh_file, pipe_project, pipe_roll_instructions, pipe_simulation, pipe_stp_file, pipe_tool_plan, roll_settings, tube_stock_settings, pipe_violations};
+#[cfg(target_arch = "wasm32")]
 use crate::remote::in_state::change_bend_step;
 #[cfg(target_arch = "wasm32")]
-use crate::remote::in_state::{pipe_bend_ops, InCmd};
+use crate::remote::in_state::{pipe_bend_ops, pipe_bend_ops_wire, InCmd};
 use crate::utils::dim::{DimB, DimX, DimZ};
 use crate::utils::dorn::Dorn;
+use crate::utils::wire::{WireKind, WireMessage, P_ID1, P_ID2, P_TOOL_STACK, P_WALL};
 use cgmath::num_traits::{abs, signum};
 use cgmath::{Deg, Point3, Rad};
 use log::warn;
@@ -26,9 +51,8 @@ use std::ops::{Mul, Range};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{iter, mem};
+use std::path::Path;
 use std::f64::consts::PI;
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use is_odd::IsOdd;
 use truck_base::bounding_box::BoundingBox;
 use truck_base::cgmath64::Vector3;
@@ -36,7 +60,7 @@ use truck_modeling::Curve;
 use truck_stepio::out;
 use truck_stepio::out::StepModel;
 use truck_topology::Solid;
-use web_sys::js_sys::Float32Array;
+use web_sys::js_sys::{Float32Array, Int32Array, Uint8Array};
 use web_sys::HtmlCanvasElement;
 use web_time::{Instant, SystemTime};
 use wgpu::util::DeviceExt;
@@ -52,9 +76,21 @@ use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 use crate::utils::materials::{Material, MATERIALS_COUNT};
 
-const METADATA_COUNT: usize = 256;
+//straights and bends take the ids below PROGRAM_ID_COUNT
+const PROGRAM_ID_COUNT: usize = 256;
+//grip and trim stock are drawn as meshes of their own after the program ids
+const ALLOWANCE_START_ID: u64 = PROGRAM_ID_COUNT as u64;
+const ALLOWANCE_END_ID: u64 = PROGRAM_ID_COUNT as u64 + 1;
+const METADATA_COUNT: usize = PROGRAM_ID_COUNT + 2;
 const STRIGHT_COLOR: u32 = 76;
 const BEND_COLOR: u32 = 37;
+const VIOLATION_COLOR: u32 = 97;
+const RISK_COLOR: u32 = 117;
+const ALLOWANCE_COLOR: u32 = 134;
+//the other revision of a comparison is drawn with its ids moved above the loaded one, see diff_id_offset
+const DIFF_CHANGED_COLOR: u32 = RISK_COLOR;
+const DIFF_ADDED_COLOR: u32 = ALLOWANCE_COLOR;
+const DIFF_REMOVED_COLOR: u32 = VIOLATION_COLOR;
 pub static IS_OFFSCREEN_BUFFER_MAPPED: AtomicBool = AtomicBool::new(false);
 pub const OFFSCREEN_TEXEL_SIZE: u32 = 16;
 const MESH_BUFFER_LIMIT: usize = 2000000;
@@ -81,8 +117,45 @@ pub enum States {
     ReverseLRACLR,
     ChangeDornDir,
     LoadLRA(Vec<f32>),
+    LoadProgram(Vec<u8>),
     NewBendParams(Vec<f32>),
     SelectFromWeb(i32),
+    NewMachineProfile(Vec<f32>),
+    NewObstacles(Vec<f32>),
+    CheckCollisions,
+    OptimizeProgram,
+    NewCycleParams(Vec<f32>),
+    NewToolLibrary(Vec<f32>),
+    NewRollParams(Vec<f32>),
+    ConvertLargeBends,
+    NewTubeStock(Vec<f32>),
+    LoadStp(Vec<u8>),
+    LoadProject(Vec<u8>),
+    SaveProject,
+    NewJobInfo((String, String)),
+    AddToCutList(i32),
+    ClearCutList,
+    NewNestingParams(Vec<f32>),
+    NewAllowanceParams(Vec<f32>),
+    ExportStp,
+    ExportProgramStp(Vec<LRACLR>),
+    ExportMesh((i32, i32)),
+    ExportAnimation,
+    ExportDrawing(i32),
+    ExportMarking(i32),
+    OpenFile(String),
+    NewRotationDatum(Vec<f32>),
+    NewOutputMode(Vec<f32>),
+    ShowBothHands(i32),
+    MirrorProgram(i32),
+    EditProgram(EditOp),
+    UndoEdit,
+    RedoEdit,
+    CompareProgram(Vec<u8>),
+    CompareLRA(Vec<LRACLR>),
+    PlaySimulation,
+    PauseSimulation,
+    SeekSimulation(SimSeek),
 }
 pub struct AnimState {
     pub id: i32,
@@ -175,12 +248,42 @@ pub struct GlobalState {
     pub lraclr_arr_reversed: Vec<LRACLR>,
     pub idmaps: HashMap<u64, EntityId>,
     pub anim_state: AnimState,
+    //seconds into the bend sequence, the animation shows the tube at this time
+    pub sim_time: f64,
     pub v_up_orign: Vector3,
     pub instant: Instant,
     pub dt: f64,
     pub is_next_frame_ready: bool,
     pub smaa_target: SmaaTarget,
     pub is_reversed: bool,
+    pub machine: MachineProfile,
+    pub violations: Vec<Violation>,
+    pub collision: Option<Collision>,
+    pub candidates: Vec<ProgramCandidate>,
+    pub cycle: CycleTime,
+    pub tools: ToolLibrary,
+    pub tool_plan: Option<ToolPlan>,
+    pub tool_errors: Vec<ToolError>,
+    pub roll_params: RollParameters,
+    pub large_bend_source: Option<Vec<LRACLR>>,
+    pub stock: TubeStock,
+    pub assessments: Vec<BendAssessment>,
+    pub job: JobInfo,
+    pub is_reverse_pending: bool,
+    pub nesting: NestingParameters,
+    pub cut_list: Vec<NestPart>,
+    pub cut_plan: Option<CutPlan>,
+    pub allowance_params: AllowanceParameters,
+    //stock added to lraclr_arr, the reversed program has it the other way round
+    pub allowance: Allowance,
+    //file opened in the native viewer and the folder native saves and exports go to, empty is the working folder
+    pub file_path: String,
+    pub export_dir: String,
+    pub datum: RotationDatum,
+    //undo and redo of the edits made to lraclr_arr, cleared when another part is loaded
+    pub editor: ProgramEditor,
+    //last comparison of the finished part with another revision
+    pub program_diff: Option<ProgramDiff>,
 }
 impl GlobalState {
     pub fn check_framerate(&mut self) {
@@ -189,13 +292,7 @@ impl GlobalState {
         self.instant = Instant::now();
     }
     pub fn calculate_unbend_bbx(&self) -> BoundingBox<Point3<f64>> {
-        let mut tot_x: f64 = 0.0;
-        let mut pipe_radius: f64 = 0.0;
-        self.lraclr_arr.iter().for_each(|lracl| {
-            tot_x = tot_x + lracl.l;
-            tot_x = tot_x + lracl.lt;
-            pipe_radius = lracl.pipe_radius;
-        });
+        let (tot_x, _pipe_radius) = LRACLR::total_len_out_d(&self.lraclr_arr);
         let half = tot_x / 2.0;
         let mut bbx: BoundingBox<cgmath::Point3<f64>> = BoundingBox::new();
         bbx.push(cgmath::Point3::new(0.0, half, half));
@@ -203,11 +300,7 @@ impl GlobalState {
         bbx
     }
     pub fn calculate_total_len(&self) -> f64 {
-        let mut tot_x: f64 = 0.0;
-        self.lraclr_arr.iter().for_each(|lracl| {
-            tot_x = tot_x + lracl.l;
-            tot_x = tot_x + lracl.lt;
-        });
+        let (tot_x, _pipe_radius) = LRACLR::total_len_out_d(&self.lraclr_arr);
         tot_x
     }
 
@@ -220,6 +313,414 @@ impl GlobalState {
         self.state = self.prev_state.clone();
         self.state.clone()
     }
+    pub fn current_lraclr(&self) -> &Vec<LRACLR> {
+        if (self.is_reversed) {
+            &self.lraclr_arr_reversed
+        } else {
+            &self.lraclr_arr
+        }
+    }
+    pub fn validate_machine_limits(&mut self) {
+        self.violations = validate_lraclr(self.current_lraclr(), &self.machine);
+        self.violations.iter().for_each(|v| {
+            warn!("MACHINE LIMIT {}", v);
+        });
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_violations(Uint8Array::from(Violation::to_wire(&self.violations).to_bytes().as_slice()));
+        }
+    }
+    pub fn estimate_cycle_time(&mut self) {
+        self.cycle = cycle_time(self.current_lraclr(), &self.machine.cycle);
+        warn!("CYCLE TIME {}", self.cycle);
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_cycle_time(Uint8Array::from(self.cycle.to_wire().to_bytes().as_slice()), format!("{}", self.cycle).as_str());
+        }
+    }
+    //Program rows in the output mode of the machine, extended with the wall and the tool stack of every bend when known
+    pub fn program_to_wire(&self, lraclr_arr: &Vec<LRACLR>) -> WireMessage {
+        let mut msg = LRACLR::to_wire(&to_output(lraclr_arr, &self.machine.output));
+        msg.rows.iter_mut().for_each(|row| {
+            row.push(P_WALL, self.stock.wall);
+        });
+        match &self.tool_plan {
+            None => {}
+            Some(plan) => {
+                plan.ops.iter().for_each(|op| match op {
+                    ToolOp::Change(_) => {}
+                    ToolOp::Bend { id1, id2, stack } => {
+                        match msg.rows.iter_mut().find(|row| row.get(P_ID1) == Some(*id1 as f64) && row.get(P_ID2) == Some(*id2 as f64)) {
+                            None => {}
+                            Some(row) => row.push(P_TOOL_STACK, *stack as f64),
+                        }
+                    }
+                });
+            }
+        }
+        msg
+    }
+    #[cfg(target_arch = "wasm32")]
+    pub fn pipe_program(&self, lraclr_arr: &Vec<LRACLR>) {
+        //the table stays recognised, edits and selections refer to its rows
+        pipe_bend_ops(Int32Array::from(LRACLR::to_array(lraclr_arr).as_slice()));
+        pipe_bend_ops_wire(Uint8Array::from(self.program_to_wire(lraclr_arr).to_bytes().as_slice()));
+    }
+    //Program read from the operator or another application, a fresh start for the editor
+    pub fn load_program(&mut self, lra_cmds: Vec<LRACLR>) -> States {
+        self.large_bend_source = None;
+        self.editor.clear();
+        self.report_edit("");
+        if (lra_cmds.is_empty()) {
+            self.change_state(States::Dismiss)
+        } else {
+            let program = self.with_allowances(&lra_cmds);
+            self.change_state(ReadyToLoad((program, false)))
+        }
+    }
+    //Applies the allowances to a freshly loaded program
+    pub fn with_allowances(&mut self, lraclr_arr: &Vec<LRACLR>) -> Vec<LRACLR> {
+        let (ret, allowance) = apply_allowances(lraclr_arr, &self.machine, &self.allowance_params);
+        self.allowance = allowance;
+        self.report_allowance();
+        ret
+    }
+    pub fn report_allowance(&self) {
+        warn!("ALLOWANCE {}", self.allowance);
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_allowance(Uint8Array::from(self.allowance.to_wire().to_bytes().as_slice()), format!("{}", self.allowance).as_str());
+        }
+    }
+    pub fn program_poly(&self, is_reversed: bool) -> (Vec<MainCylinder>, Vec<BendToro>) {
+        let (lraclr_arr, allowance) = if (is_reversed) { (&self.lraclr_arr_reversed, self.allowance.reversed()) } else { (&self.lraclr_arr, self.allowance.clone()) };
+        let (mut cyls, tors) = cnc_to_poly(lraclr_arr, &self.v_up_orign);
+        split_allowances(&mut cyls, allowance.start, allowance.end, ALLOWANCE_START_ID, ALLOWANCE_END_ID);
+        (cyls, tors)
+    }
+    //Allowances of the tube in the middle of the bend sequence. The free start is the far end of the bent part,
+    //the end is the far end of the stock still behind the bend head
+    pub fn split_anim_allowances(&self, cyls: &mut Vec<MainCylinder>, anim_state: &AnimState) {
+        let allowance = if (self.is_reversed) { self.allowance.reversed() } else { self.allowance.clone() };
+        let stock_count = if (anim_state.stright_len > 0.0 && cyls.len() > 1) { 1 } else { 0 };
+        let mut stock: Vec<MainCylinder> = cyls.split_off(cyls.len() - stock_count);
+        split_allowances(cyls, 0.0, allowance.start, ALLOWANCE_START_ID, ALLOWANCE_START_ID);
+        split_allowances(&mut stock, 0.0, allowance.end, ALLOWANCE_END_ID, ALLOWANCE_END_ID);
+        cyls.extend(stock);
+    }
+    //The part and its opposite hand next to each other, the mirrored copy keeps the ids so a selection shows on both.
+    //The mirrored program is checked against the mirrored centerline of the part
+    pub fn both_hands_mesh(&self, plane: MirrorPlane) -> (Vec<MeshVertex>, Vec<i32>, BoundingBox<Point3<f64>>) {
+        let (cyls, tors) = self.program_poly(false);
+        let mirrored = mirror_lraclr(&self.lraclr_arr, plane);
+        let deviation = mirror_deviation(&self.lraclr_arr, &mirrored, plane, &self.v_up_orign);
+        if (deviation > TOLE) {
+            warn!("MIRROR {} OFF THE CENTERLINE BY {:.3}", plane, deviation);
+        }
+        let (mut m_cyls, m_tors) = cnc_to_poly(&mirrored, &self.v_up_orign);
+        split_allowances(&mut m_cyls, self.allowance.start, self.allowance.end, ALLOWANCE_START_ID, ALLOWANCE_END_ID);
+        let mut bbx: BoundingBox<Point3<f64>> = Default::default();
+        cyls.iter().for_each(|cyl| bbx += cyl.bbx.clone());
+        tors.iter().for_each(|tor| bbx += tor.bbx.clone());
+        let mut m_bbx: BoundingBox<Point3<f64>> = Default::default();
+        m_cyls.iter().for_each(|cyl| m_bbx += cyl.bbx.clone());
+        m_tors.iter().for_each(|tor| m_bbx += tor.bbx.clone());
+        let (_tl, pipe_radius) = LRACLR::total_len_out_d(&self.lraclr_arr);
+        let offset = bbx.max().y - m_bbx.min().y + (bbx.max().y - bbx.min().y) * 0.2 + pipe_radius * 4.0;
+        let (mut v, _i) = all_to_one(&cyls, &tors);
+        let (m_v, _m_i) = all_to_one(&m_cyls, &m_tors);
+        m_v.iter().for_each(|mv| {
+            let mut mv = mv.clone();
+            mv.position[1] = mv.position[1] + offset as f32;
+            v.push(mv);
+        });
+        bbx.push(Point3::new(m_bbx.min().x, m_bbx.min().y + offset, m_bbx.min().z));
+        bbx.push(Point3::new(m_bbx.max().x, m_bbx.max().y + offset, m_bbx.max().z));
+        let i: Vec<i32> = (0..v.len() as i32).collect();
+        (v, i, bbx)
+    }
+    //Edits go through ReadyToLoad like every other program change, the reversed program, the meshes
+    //and the checks follow from there. A reversed view is reversed again after the edit
+    //Rows are indexed as the web shows them, a reversed program is edited reversed and turned back.
+    //Undo keeps the programs as they are loaded
+    pub fn edit_program(&mut self, op: &EditOp) -> States {
+        let result = if (self.is_reversed) {
+            apply_edit(&self.lraclr_arr_reversed, op).map(|edited| cnc::reverse_lraclr(&edited))
+        } else {
+            apply_edit(&self.lraclr_arr, op)
+        };
+        if (result.is_ok()) {
+            self.editor.push(&self.lraclr_arr);
+        }
+        self.load_edit(result, format!("{}", op).as_str())
+    }
+    pub fn undo_edit(&mut self) -> States {
+        let program = self.lraclr_arr.clone();
+        let result = self.editor.undo(&program);
+        self.load_edit(result, "undo")
+    }
+    pub fn redo_edit(&mut self) -> States {
+        let program = self.lraclr_arr.clone();
+        let result = self.editor.redo(&program);
+        self.load_edit(result, "redo")
+    }
+    fn load_edit(&mut self, result: Result<Vec<LRACLR>, EditError>, name: &str) -> States {
+        match result {
+            Ok(program) => {
+                warn!("EDIT {}", name);
+                self.report_edit("");
+                self.is_reverse_pending = self.is_reversed;
+                self.change_state(ReadyToLoad((program, false)))
+            }
+            Err(e) => {
+                self.report_edit(format!("{} {}", name, e).as_str());
+                self.change_state(States::Dismiss)
+            }
+        }
+    }
+    pub fn report_edit(&self, error: &str) {
+        if (!error.is_empty()) {
+            warn!("EDIT {}", error);
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_edit_state(self.editor.can_undo(), self.editor.can_redo(), error);
+        }
+    }
+    //The finished part is compared, a revision sent by the customer has no grip or trim stock
+    pub fn compare_program(&mut self, other: &Vec<LRACLR>) {
+        let finished = strip_allowances(&self.lraclr_arr, &self.allowance);
+        let diff = ProgramDiff::compare(&finished, other, &self.v_up_orign);
+        warn!("DIFF {}", diff);
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_diff(Uint8Array::from(diff.to_wire().to_bytes().as_slice()), format!("{}", diff).as_str());
+        }
+        self.program_diff = Some(diff);
+    }
+    //Ids of the other revision start right after the loaded one, even so straights and bends keep their colors.
+    //None when both do not fit below the allowance ids
+    pub fn diff_id_offset(&self, other: &Vec<LRACLR>) -> Option<i32> {
+        //meshes are numbered one after the other, the rows may carry their own ids
+        let max_id = |lraclr_arr: &Vec<LRACLR>| -> i32 { lraclr_arr.iter().map(|lra| lra.id1.max(lra.id2)).fold(lraclr_arr.len() as i32 * 2 - 1, |a, b| a.max(b)) };
+        let finished = strip_allowances(&self.lraclr_arr, &self.allowance);
+        let offset = (max_id(&finished) + 2) / 2 * 2;
+        if (offset + max_id(other) < PROGRAM_ID_COUNT as i32) { Some(offset) } else { None }
+    }
+    //Both revisions on top of each other from the same start, the other one with its ids moved by the offset
+    pub fn diff_mesh(&self, other: &Vec<LRACLR>, offset: i32) -> (Vec<MeshVertex>, Vec<i32>, BoundingBox<Point3<f64>>) {
+        let finished = strip_allowances(&self.lraclr_arr, &self.allowance);
+        let (cyls, tors) = cnc_to_poly(&finished, &self.v_up_orign);
+        let (o_cyls, o_tors) = cnc_to_poly(&align_first_rotation(other, &finished), &self.v_up_orign);
+        let mut bbx: BoundingBox<Point3<f64>> = Default::default();
+        cyls.iter().for_each(|cyl| bbx += cyl.bbx.clone());
+        tors.iter().for_each(|tor| bbx += tor.bbx.clone());
+        o_cyls.iter().for_each(|cyl| bbx += cyl.bbx.clone());
+        o_tors.iter().for_each(|tor| bbx += tor.bbx.clone());
+        let (mut v, _i) = all_to_one(&cyls, &tors);
+        let (o_v, _o_i) = all_to_one(&o_cyls, &o_tors);
+        o_v.iter().for_each(|ov| {
+            let mut ov = ov.clone();
+            ov.id = ov.id + offset;
+            v.push(ov);
+        });
+        let i: Vec<i32> = (0..v.len() as i32).collect();
+        (v, i, bbx)
+    }
+    pub fn add_to_cut_list(&mut self, quantity: usize) {
+        if (self.lraclr_arr.is_empty() || quantity == 0) {
+            return;
+        }
+        let name = if (self.job.part_number.is_empty()) { format!("part {}", self.cut_list.len() + 1) } else { self.job.part_number.clone() };
+        self.cut_list.push(NestPart::from_lraclr(name.as_str(), &self.lraclr_arr, self.stock.wall, self.stock.material.name.as_str(), quantity));
+    }
+    pub fn nest_cut_list(&mut self) {
+        if (self.cut_list.is_empty()) {
+            self.cut_plan = None;
+            return;
+        }
+        let plan = nest_parts(&self.cut_list, &self.nesting);
+        warn!("CUT PLAN\n{}", plan);
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_cut_plan(Uint8Array::from(plan.to_wire().to_bytes().as_slice()), format!("{}", plan).as_str());
+        }
+        self.cut_plan = Some(plan);
+    }
+    pub fn export_path(&self, file_name: &str) -> String {
+        Path::new(self.export_dir.as_str()).join(file_name).to_string_lossy().to_string()
+    }
+    //F2 switches the destination between the folder of the opened file and the working folder
+    pub fn toggle_export_dir(&mut self) {
+        let source_dir = Path::new(self.file_path.as_str()).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or(String::new());
+        self.export_dir = if (self.export_dir.is_empty()) { source_dir } else { String::new() };
+        warn!("EXPORT TO {}", if (self.export_dir.is_empty()) { "." } else { self.export_dir.as_str() });
+    }
+    //Native counterpart of the web bridge, every kind of file goes through the same LoadStp, LoadProject
+    //and LoadProgram states and ends in ReadyToLoad. A folder becomes the export destination.
+    //Csv and wire rows are in the output mode of the machine, LoadProgram reads them back with from_output
+    pub fn open_file(&mut self, path: &str) -> States {
+        let p = Path::new(path);
+        if (p.is_dir()) {
+            self.export_dir = path.to_string();
+            warn!("EXPORT TO {}", path);
+            return self.change_state(States::Dismiss);
+        }
+        let data = match std::fs::read(p) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("OPEN {} {}", path, e);
+                return self.change_state(States::Dismiss);
+            }
+        };
+        self.file_path = path.to_string();
+        let ext = p.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or(String::new());
+        if (ext == "stp" || ext == "step") {
+            self.change_state(LoadStp(data))
+        } else if (ext == "ftwp") {
+            self.change_state(LoadProject(data))
+        } else if (ext == "csv") {
+            match LRACLR::from_csv(String::from_utf8_lossy(&data).as_ref()) {
+                Ok(lraclr_arr) => self.change_state(LoadProgram(LRACLR::to_wire(&lraclr_arr).to_bytes())),
+                Err(e) => {
+                    warn!("OPEN {} {}", path, e);
+                    self.change_state(States::Dismiss)
+                }
+            }
+        } else {
+            self.change_state(LoadProgram(data))
+        }
+    }
+    pub fn export_name(&self) -> String {
+        if (self.job.part_number.is_empty()) { "tube".to_string() } else { self.job.part_number.clone() }
+    }
+    //The finished part, grip and trim stock is not exported
+    pub fn to_stp(&self) -> Result<Vec<u8>, StepExportError> {
+        self.program_to_stp(&strip_allowances(&self.lraclr_arr, &self.allowance))
+    }
+    //Any program with the part number and wall of the job
+    pub fn program_to_stp(&self, lraclr_arr: &Vec<LRACLR>) -> Result<Vec<u8>, StepExportError> {
+        let (cyls, tors) = cnc_to_poly(lraclr_arr, &self.v_up_orign);
+        let mut params = StepExportParameters::default();
+        params.part_number = self.export_name();
+        params.wall = self.stock.wall;
+        tube_to_stp(&cyls, &tors, &params)
+    }
+    fn store_stp(&self, file: Result<Vec<u8>, StepExportError>) {
+        match file {
+            Ok(file) => {
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_stp_file(Uint8Array::from(file.as_slice()));
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let path = self.export_path(STP_EXPORT_FILE);
+                    match std::fs::write(&path, &file) {
+                        Ok(_) => warn!("STP STORED {}", path),
+                        Err(e) => warn!("STP {}", e),
+                    }
+                }
+            }
+            Err(e) => warn!("STP {}", e),
+        }
+    }
+    //Machine program as it is bent, with the cut length of the stock
+    pub fn to_drawing(&self, format: DrawingFormat) -> Vec<u8> {
+        let info = DrawingInfo {
+            part_number: self.export_name(),
+            material: self.stock.material.name.clone(),
+            wall: self.stock.wall,
+            notes: self.job.notes.clone(),
+            output: self.machine.output.clone(),
+        };
+        let drawing = program_drawing(self.current_lraclr(), &self.v_up_orign, &info);
+        match format {
+            DrawingFormat::Svg => drawing.to_svg().into_bytes(),
+            DrawingFormat::Dxf => drawing.to_dxf().into_bytes(),
+        }
+    }
+    pub fn to_project(&self) -> Project {
+        Project {
+            job: self.job.clone(),
+            program: self.lraclr_arr.clone(),
+            is_reversed: self.is_reversed,
+            v_up_orign: self.v_up_orign.clone(),
+            machine: self.machine.clone(),
+            tools: self.tools.clone(),
+            stock: self.stock.clone(),
+            roll_params: self.roll_params.clone(),
+            allowance_params: self.allowance_params.clone(),
+            allowance: self.allowance.clone(),
+            datum: self.datum.clone(),
+        }
+    }
+    //Takes over the settings, the returned program is loaded as usual and reversed afterwards if needed
+    //A project saved without its STEP file keeps the one already loaded when it is the same file
+    pub fn apply_project(&mut self, project: Project) -> Vec<LRACLR> {
+        let source = if (project.job.source_stp.is_empty()) { self.job.source_stp.clone() } else { project.job.source_stp.clone() };
+        let is_source_matching = !source.is_empty() && project.is_source_matching(&source);
+        if (!source.is_empty() && !is_source_matching) {
+            warn!("PROJECT STEP SOURCE CHANGED");
+        }
+        self.job = project.job;
+        if (is_source_matching) {
+            self.job.source_stp = source;
+        }
+        self.v_up_orign = project.v_up_orign;
+        self.is_reverse_pending = project.is_reversed;
+        self.machine = project.machine;
+        self.tools = project.tools;
+        self.stock = project.stock;
+        self.roll_params = project.roll_params;
+        self.allowance_params = project.allowance_params;
+        self.allowance = project.allowance;
+        self.datum = project.datum;
+        project.program
+    }
+    pub fn assess_bendability(&mut self) {
+        self.assessments = assess_lraclr(self.current_lraclr(), &self.stock);
+        self.assessments.iter().filter(|a| a.is_risky()).for_each(|a| {
+            warn!("BEND RISK {}", a);
+        });
+        #[cfg(target_arch = "wasm32")]
+        {
+            pipe_bendability(Uint8Array::from(BendAssessment::to_wire(&self.assessments).to_bytes().as_slice()));
+        }
+    }
+    pub fn assign_tools(&mut self) {
+        if (self.tools.is_empty()) {
+            self.tool_plan = None;
+            self.tool_errors = vec![];
+            return;
+        }
+        let report: String = match assign_tools(self.current_lraclr(), &self.tools) {
+            Ok(plan) => {
+                let report = format!("{}", plan);
+                self.tool_plan = Some(plan);
+                self.tool_errors = vec![];
+                report
+            }
+            Err(errors) => {
+                let mut report = String::new();
+                errors.iter().for_each(|e| {
+                    report.push_str(format!("TOOLING {}\n", e).as_str());
+                });
+                self.tool_plan = None;
+                self.tool_errors = errors;
+                report
+            }
+        };
+        warn!("TOOLS\n{}", report);
+        #[cfg(target_arch = "wasm32")]
+        {
+            let msg: WireMessage = match &self.tool_plan {
+                None => WireMessage::new(WireKind::ToolPlan),
+                Some(plan) => plan.to_wire(),
+            };
+            pipe_tool_plan(Uint8Array::from(msg.to_bytes().as_slice()), report.as_str());
+        }
+    }
 }
 unsafe impl Send for GlobalState {}
 unsafe impl Sync for GlobalState {}
@@ -271,7 +772,9 @@ impl GlobalScene {
         });
         let mut metadata_default: Vec<[i32; 4]> = vec![];
         for i in 0..METADATA_COUNT {
-            if (!i.is_odd()) {
+            if (i >= ALLOWANCE_START_ID as usize) {
+                metadata_default.push([ALLOWANCE_COLOR as i32, 0, 0, 0]);
+            } else if (!i.is_odd()) {
                 metadata_default.push([STRIGHT_COLOR as i32, 0, 0, 0]);
             } else {
                 metadata_default.push([BEND_COLOR as i32, 0, 0, 0]);
@@ -347,6 +850,43 @@ impl GlobalScene {
 
         IS_OFFSCREEN_BUFFER_MAPPED.store(false, Ordering::Relaxed);
     }
+    pub fn highlight_ids(&mut self, queue: &Queue, ids: &Vec<i32>, color: u32) {
+        ids.iter().for_each(|id| {
+            if (*id >= 0 && (*id as usize) < METADATA_COUNT) {
+                self.metadata[*id as usize][0] = color as i32;
+            }
+        });
+        self.update_meta_data(queue);
+    }
+    pub fn reset_colors(&mut self, queue: &Queue) {
+        self.metadata.iter_mut().enumerate().for_each(|(i, md)| {
+            if (i >= ALLOWANCE_START_ID as usize) {
+                md[0] = ALLOWANCE_COLOR as i32;
+            } else if (!i.is_odd()) {
+                md[0] = STRIGHT_COLOR as i32;
+            } else {
+                md[0] = BEND_COLOR as i32;
+            }
+        });
+        self.update_meta_data(queue);
+    }
+    pub fn show_violations(&mut self, queue: &Queue, violations: &Vec<Violation>) {
+        self.reset_colors(queue);
+        let ids: Vec<i32> = violations.iter().map(|v| v.mesh_id()).collect();
+        self.highlight_ids(queue, &ids, VIOLATION_COLOR);
+    }
+    pub fn show_bend_risks(&mut self, queue: &Queue, assessments: &Vec<BendAssessment>) {
+        let ids: Vec<i32> = assessments
+            .iter()
+            .filter(|a| a.is_risky() && a.id2 >= 0 && (a.id2 as usize) < METADATA_COUNT && self.metadata[a.id2 as usize][0] != VIOLATION_COLOR as i32)
+            .map(|a| a.id2)
+            .collect();
+        self.highlight_ids(queue, &ids, RISK_COLOR);
+    }
+    pub fn show_tool_errors(&mut self, queue: &Queue, errors: &Vec<ToolError>) {
+        let ids: Vec<i32> = errors.iter().map(|e| e.mesh_id()).collect();
+        self.highlight_ids(queue, &ids, VIOLATION_COLOR);
+    }
     pub fn unselect_all(&mut self, queue: &Queue) {
         self.metadata.iter_mut().for_each(|md| { md[1] = 0; });
         self.select_by_id(queue, -1);
@@ -357,7 +897,7 @@ impl GlobalScene {
         }
     }
     pub fn select_by_id(&mut self, queue: &Queue, id: i32) {
-        if (id < 0) {
+        if (id < 0 || id as usize >= METADATA_COUNT) {
             self.update_meta_data(queue);
         } else {
             self.metadata.iter_mut().for_each(|md| { md[1] = 0; });
@@ -421,6 +961,7 @@ pub fn init_graphics(world: &World, gr: Graphics) {
         lraclr_arr_reversed: vec![],
         idmaps: HashMap::new(),
         anim_state: AnimState::default(),
+        sim_time: 0.0,
         v_up_orign: P_UP_REVERSE,
         instant: Instant::now(),
         dt: 0.0,
@@ -428,6 +969,30 @@ pub fn init_graphics(world: &World, gr: Graphics) {
 
         smaa_target: smaa_target,
         is_reversed: false,
+        machine: MachineProfile::default(),
+        violations: vec![],
+        collision: None,
+        candidates: vec![],
+        cycle: CycleTime::default(),
+        tools: ToolLibrary::default(),
+        tool_plan: None,
+        tool_errors: vec![],
+        roll_params: RollParameters::default(),
+        large_bend_source: None,
+        stock: TubeStock::default(),
+        assessments: vec![],
+        job: JobInfo::default(),
+        is_reverse_pending: false,
+        nesting: NestingParameters::default(),
+        cut_list: vec![],
+        cut_plan: None,
+        allowance_params: AllowanceParameters::default(),
+        allowance: Allowance::default(),
+        file_path: String::new(),
+        export_dir: String::new(),
+        datum: RotationDatum::default(),
+        editor: ProgramEditor::default(),
+        program_diff: None,
     };
     let g_scene = GlobalScene::new(
         &gr.device,
@@ -442,6 +1007,24 @@ pub fn init_graphics(world: &World, gr: Graphics) {
         bend_settings(Float32Array::from(
             g_scene.bend_params.params_to_f32vec().as_slice(),
         ));
+        machine_settings(Float32Array::from(
+            gs.machine.params_to_f32vec().as_slice(),
+        ));
+        cycle_settings(Float32Array::from(
+            gs.machine.cycle.params_to_f32vec().as_slice(),
+        ));
+        roll_settings(Float32Array::from(
+            gs.roll_params.params_to_f32vec().as_slice(),
+        ));
+        allowance_settings(Float32Array::from(
+            gs.allowance_params.params_to_f32vec().as_slice(),
+        ));
+        nesting_settings(Float32Array::from(
+            gs.nesting.params_to_f32vec().as_slice(),
+        ));
+        tube_stock_settings(Float32Array::from(
+            gs.stock.params_to_f32vec().as_slice(),
+        ));
         let in_cmd = InCmd::new();
         world.add_unique(in_cmd);
     }
@@ -523,8 +1106,15 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
             ReadyToLoad((lraclr, is_reset_camera)) => {
                 let resetcamera = is_reset_camera.clone();
                 gs.lraclr_arr = lraclr.clone();
+                gs.program_diff = None;
+                gs.sim_time = 0.0;
+                if (resetcamera) {
+                    gs.large_bend_source = None;
+                    gs.editor.clear();
+                    gs.report_edit("");
+                }
                 gs.lraclr_arr_reversed = cnc::reverse_lraclr(&gs.lraclr_arr);
-                let (cyls, tors) = cnc_to_poly(&gs.lraclr_arr, &gs.v_up_orign);
+                let (cyls, tors) = gs.program_poly(false);
                 let (v, i) = all_to_one(&cyls, &tors);
                 g_scene.mesh_size = i.len();
                 graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&MESH_ZEROS_I));
@@ -544,15 +1134,229 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                     graphics.camera.set_up_dir(&gs.v_up_orign);
                     graphics.camera.move_camera_to_bbx_limits();
                 }
+                gs.is_reversed = false;
+                check_program(&graphics, &mut gs, &mut g_scene);
 
                 #[cfg(target_arch = "wasm32")]
                 {
-                    let lraclr_arr_i32 = LRACLR::to_array(&gs.lraclr_arr);
-                    pipe_bend_ops(wasm_bindgen_futures::js_sys::Int32Array::from(
-                        lraclr_arr_i32.as_slice(),
-                    ))
+                    gs.pipe_program(&gs.lraclr_arr);
+                }
+                if (gs.is_reverse_pending) {
+                    gs.is_reverse_pending = false;
+                    gs.change_state(ReverseLRACLR)
+                } else {
+                    gs.change_state(States::Dismiss)
+                }
+            }
+            LoadStp(stp) => {
+                let stp = stp.clone();
+                match analyze_stp_verified(&stp, MAX_DEVIATION, &gs.datum) {
+                    Err(e) => {
+                        warn!("STP {}", e);
+                        gs.change_state(States::Dismiss)
+                    }
+                    Ok((lraclr, report)) => {
+                        warn!("DEVIATION {}", report);
+                        #[cfg(target_arch = "wasm32")]
+                        {
+                            pipe_deviation(Uint8Array::from(report.to_wire().to_bytes().as_slice()), format!("{}", report).as_str());
+                        }
+                        if (lraclr.is_empty() || report.is_failed()) {
+                            warn!("RECOGNITION FAILED {}", report);
+                            gs.change_state(States::Dismiss)
+                        } else {
+                            gs.v_up_orign = P_UP_REVERSE;
+                            gs.job = JobInfo::from_stp(&stp, &lraclr);
+                            let program = gs.with_allowances(&lraclr);
+                            gs.change_state(ReadyToLoad((program, true)))
+                        }
+                    }
+                }
+            }
+            NewRotationDatum(params) => {
+                let params = params.clone();
+                gs.datum.set_params_from_f32vec(&params);
+                warn!("DATUM {}", gs.datum);
+                if (gs.lraclr_arr.is_empty() || gs.job.source_stp.is_empty()) {
+                    gs.revert_state()
+                } else {
+                    //only the first rotation depends on the datum, the allowances stay as they are
+                    let mut program = gs.lraclr_arr.clone();
+                    if (gs.datum.is_set()) {
+                        match analyze_stp_verified(&gs.job.source_stp, MAX_DEVIATION, &gs.datum) {
+                            Ok((with_datum, _report)) if with_datum.len() == program.len() => program[0].r = with_datum[0].r,
+                            Ok(_) => warn!("DATUM {} NOT APPLIED", gs.datum),
+                            Err(e) => warn!("DATUM {} {}", gs.datum, e),
+                        }
+                    } else {
+                        program[0].r = 0.0;
+                    }
+                    gs.change_state(ReadyToLoad((program, false)))
+                }
+            }
+            OpenFile(path) => {
+                let path = path.clone();
+                g_scene.bend_step = 1;
+                gs.open_file(path.as_str())
+            }
+            LoadProject(data) => {
+                match Project::from_bytes(data.as_slice()) {
+                    Ok(project) if project.program.is_empty() => {
+                        warn!("PROJECT has no program");
+                        gs.change_state(States::Dismiss)
+                    }
+                    Ok(project) => {
+                        let program = gs.apply_project(project);
+                        g_scene.bend_step = 1;
+                        gs.change_state(ReadyToLoad((program, true)))
+                    }
+                    Err(e) => {
+                        warn!("PROJECT {}", e);
+                        gs.change_state(States::Dismiss)
+                    }
                 }
-                gs.change_state(States::Dismiss)
+            }
+            SaveProject => {
+                let project = gs.to_project();
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let path = gs.export_path(PROJECT_FILE);
+                    match project.save(path.as_str()) {
+                        Ok(_) => warn!("PROJECT STORED {}", path),
+                        Err(e) => warn!("PROJECT {}", e),
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_project(Uint8Array::from(project.to_bytes(true).as_slice()));
+                }
+                gs.revert_state()
+            }
+            AddToCutList(quantity) => {
+                let quantity = quantity.clone().max(0) as usize;
+                gs.add_to_cut_list(quantity);
+                gs.nest_cut_list();
+                gs.revert_state()
+            }
+            ClearCutList => {
+                gs.cut_list = vec![];
+                gs.nest_cut_list();
+                gs.revert_state()
+            }
+            NewAllowanceParams(params) => {
+                let params = params.clone();
+                let base = strip_allowances(&gs.lraclr_arr, &gs.allowance);
+                let source = gs.large_bend_source.take().map(|src| strip_allowances(&src, &gs.allowance));
+                gs.allowance_params.set_params_from_f32vec(&params);
+                if (base.is_empty()) {
+                    gs.revert_state()
+                } else {
+                    gs.large_bend_source = source.map(|src| apply_allowances(&src, &gs.machine, &gs.allowance_params).0);
+                    let program = gs.with_allowances(&base);
+                    g_scene.bend_step = 1;
+                    gs.change_state(ReadyToLoad((program, false)))
+                }
+            }
+            ExportStp => {
+                gs.store_stp(gs.to_stp());
+                gs.revert_state()
+            }
+            ExportProgramStp(lraclr_arr) => {
+                let lraclr_arr = lraclr_arr.clone();
+                gs.store_stp(gs.program_to_stp(&lraclr_arr));
+                gs.revert_state()
+            }
+            ExportMesh((format, step)) => {
+                let (format, step) = (MeshFormat::from_i32(*format), *step);
+                let (cyls, tors) = if (step < 0) {
+                    gs.program_poly(gs.is_reversed)
+                } else {
+                    simulate_to_step(gs.current_lraclr(), &gs.v_up_orign, &g_scene.bend_params, step)
+                };
+                if (!cyls.is_empty() || !tors.is_empty()) {
+                    let file = export_mesh(&cyls, &tors, format, gs.export_name().as_str());
+                    #[cfg(target_arch = "wasm32")]
+                    {
+                        pipe_mesh_file(Uint8Array::from(file.as_slice()), format.extension());
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        let path = gs.export_path(format!("{}.{}", MESH_EXPORT_FILE, format.extension()).as_str());
+                        match std::fs::write(&path, &file) {
+                            Ok(_) => warn!("MESH STORED {}", path),
+                            Err(e) => warn!("MESH {}", e),
+                        }
+                    }
+                }
+                gs.revert_state()
+            }
+            ExportAnimation => {
+                let file = bending_to_glb(gs.current_lraclr(), &gs.v_up_orign, &g_scene.bend_params, gs.export_name().as_str());
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_mesh_file(Uint8Array::from(file.as_slice()), "glb");
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let path = gs.export_path(format!("{}_bending.glb", MESH_EXPORT_FILE).as_str());
+                    match std::fs::write(&path, &file) {
+                        Ok(_) => warn!("ANIMATION STORED {}", path),
+                        Err(e) => warn!("ANIMATION {}", e),
+                    }
+                }
+                gs.revert_state()
+            }
+            ExportDrawing(format) => {
+                let format = DrawingFormat::from_i32(*format);
+                let file = gs.to_drawing(format);
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_drawing(Uint8Array::from(file.as_slice()), format.extension());
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let path = gs.export_path(format!("{}.{}", MESH_EXPORT_FILE, format.extension()).as_str());
+                    match std::fs::write(&path, &file) {
+                        Ok(_) => warn!("DRAWING STORED {}", path),
+                        Err(e) => warn!("DRAWING {}", e),
+                    }
+                }
+                gs.revert_state()
+            }
+            ExportMarking(format) => {
+                let format = DrawingFormat::from_i32(*format);
+                let layout = MarkingLayout::from_lraclr(gs.current_lraclr());
+                let drawing = layout.to_drawing(gs.export_name().as_str());
+                let file = match format {
+                    DrawingFormat::Svg => drawing.to_svg().into_bytes(),
+                    DrawingFormat::Dxf => drawing.to_dxf().into_bytes(),
+                };
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_marking(Uint8Array::from(file.as_slice()), format.extension(), format!("{}", layout).as_str());
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    warn!("{}", layout);
+                    let path = gs.export_path(format!("{}_marking.{}", MESH_EXPORT_FILE, format.extension()).as_str());
+                    match std::fs::write(&path, &file) {
+                        Ok(_) => warn!("MARKING STORED {}", path),
+                        Err(e) => warn!("MARKING {}", e),
+                    }
+                }
+                gs.revert_state()
+            }
+            NewNestingParams(params) => {
+                let params = params.clone();
+                gs.nesting.set_params_from_f32vec(&params);
+                gs.nest_cut_list();
+                gs.revert_state()
+            }
+            NewJobInfo((part_number, notes)) => {
+                let (part_number, notes) = (part_number.clone(), notes.clone());
+                gs.job.part_number = part_number;
+                gs.job.notes = notes;
+                gs.revert_state()
             }
             ReverseLRACLR => {
                 let (cyls, tors) = {
@@ -560,22 +1364,16 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                         gs.is_reversed = false;
                         #[cfg(target_arch = "wasm32")]
                         {
-                            let lraclr_arr_i32 = LRACLR::to_array(&gs.lraclr_arr);
-                            pipe_bend_ops(wasm_bindgen_futures::js_sys::Int32Array::from(
-                                lraclr_arr_i32.as_slice(),
-                            ))
+                            gs.pipe_program(&gs.lraclr_arr);
                         }
-                        cnc_to_poly(&gs.lraclr_arr, &gs.v_up_orign)
+                        gs.program_poly(false)
                     } else {
                         gs.is_reversed = true;
                         #[cfg(target_arch = "wasm32")]
                         {
-                            let lraclr_arr_i32 = LRACLR::to_array(&gs.lraclr_arr_reversed);
-                            pipe_bend_ops(wasm_bindgen_futures::js_sys::Int32Array::from(
-                                lraclr_arr_i32.as_slice(),
-                            ))
+                            gs.pipe_program(&gs.lraclr_arr_reversed);
                         }
-                        cnc_to_poly(&gs.lraclr_arr_reversed, &gs.v_up_orign)
+                        gs.program_poly(true)
                     }
                 };
 
@@ -597,118 +1395,34 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                     bbx += (tor.bbx.clone());
                 });
                 graphics.camera.set_up_dir(&gs.v_up_orign);
+                check_program(&graphics, &mut gs, &mut g_scene);
                 gs.change_state(States::Dismiss)
             }
+            //the time goes on with the frame time, the tube is taken from the time so a slow frame does not change it
             FullAnimate => {
-                let (cyls, tors, next_stage) = {
-                    if (gs.is_reversed) {
-                        cnc::cnc_to_poly_animate(&gs.lraclr_arr_reversed, &gs.anim_state, &gs.v_up_orign, gs.dt, &g_scene.bend_params,)
-                    } else {
-                        cnc::cnc_to_poly_animate(&gs.lraclr_arr, &gs.anim_state, &gs.v_up_orign, gs.dt, &g_scene.bend_params, )
-                    }
-                };
-               // if(!tors.is_empty()){
-                    let (v, i) = all_to_one(&cyls, &tors);
-                    g_scene.mesh_size = i.len();
-                    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&MESH_ZEROS_I));
-                    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&i));
-                    graphics.queue.write_buffer(&g_scene.v_buffer_mesh, 0, bytemuck::cast_slice(&v));
-              // }
-
-
-
-                #[cfg(target_arch = "wasm32")]
-                if (gs.anim_state.op_counter != next_stage.op_counter) {
-                    change_bend_step(next_stage.op_counter);
+                gs.sim_time = gs.sim_time + gs.dt;
+                if (show_simulation(&graphics, &mut gs, &mut g_scene)) {
+                    gs.change_state(States::Dismiss)
+                } else {
+                    gs.change_state(States::FullAnimate)
                 }
-                match gs.anim_state.opcode {
-                    0 => {
-                        g_scene.dim_x.is_active = true;
-                        g_scene.dim_z.is_active = false;
-                        g_scene.dim_b.is_active = false;
-                        g_scene.dim_x.set_scale(next_stage.lra.pipe_radius);
-                        g_scene.dim_x.set_y(next_stage.lra.pipe_radius);
-                        g_scene.dim_x.set_x(next_stage.value);
-                        g_scene.dim_x.set_pipe_radius(next_stage.lra.pipe_radius);
-                        g_scene.dorn.dorn_action(&next_stage, &gs.v_up_orign);
-                        gs.anim_state = next_stage;
-                        gs.change_state(States::FullAnimate)
-                    }
-                    1 => {
-                        g_scene.dim_x.is_active = false;
-                        g_scene.dim_b.is_active = false;
-                        g_scene.dim_z.is_active = true;
-                        g_scene.dim_z.set_scale(next_stage.lra.pipe_radius);
-                        g_scene.dim_z.set_z(next_stage.lra.pipe_radius);
-                        g_scene.dim_z.set_r(next_stage.value);
-                        g_scene.dim_z.set_pipe_radius(next_stage.lra.pipe_radius);
-                        g_scene.dorn.dorn_action(&next_stage, &gs.v_up_orign);
-                        gs.anim_state = next_stage;
-                        gs.change_state(States::FullAnimate)
-                    }
-                    2 => {
-                        g_scene.dim_b.is_active = true;
-                        g_scene.dim_x.is_active = false;
-                        g_scene.dim_z.is_active = false;
-                        g_scene.dim_b.set_scale(next_stage.lra.pipe_radius);
-                        g_scene.dim_b.set_y(next_stage.lra.clr);
-                        g_scene.dim_b.set_angle(next_stage.value);
-                        g_scene.dim_b.set_pipe_radius(next_stage.lra.pipe_radius);
-                        g_scene.dorn.dorn_action(&next_stage, &gs.v_up_orign);
-                        gs.anim_state = next_stage;
-                        gs.change_state(States::FullAnimate)
-                    }
-                    4 => {
-                        g_scene.dim_x.is_active = false;
-                        g_scene.dim_z.is_active = false;
-                        g_scene.dim_b.is_active = false;
-                        gs.anim_state = AnimState::default();
-                        g_scene.dorn.set_dorn_park(&gs.v_up_orign);
-                        let (cyls, tors) = cnc_to_poly(&gs.lraclr_arr_reversed, &gs.v_up_orign);
-
-                        let (v, i) = all_to_one(&cyls, &tors);
-                        g_scene.mesh_size = i.len();
-                        graphics.queue.write_buffer(
-                            &g_scene.i_buffer_mesh,
-                            0,
-                            bytemuck::cast_slice(&MESH_ZEROS_I),
-                        );
-                        graphics.queue.write_buffer(
-                            &g_scene.i_buffer_mesh,
-                            0,
-                            bytemuck::cast_slice(&i),
-                        );
-                        graphics.queue.write_buffer(&g_scene.v_buffer_mesh, 0,
-                                                    bytemuck::cast_slice(&v),
-                        );
-
-                        /*                        let mut bbx: BoundingBox<cgmath::Point3<f64>> = Default::default();
-                                                cyls.iter().for_each(|cyl| {
-                                                    bbx += (cyl.bbx.clone());
-                                                });
-                                                tors.iter().for_each(|tor| {
-                                                    bbx += (tor.bbx.clone());
-                                                });
-                                                graphics.camera.set_tot_bbx(bbx);
-                                                graphics.camera.set_up_dir(&gs.v_up_orign);
-                                                graphics.camera.move_camera_to_bbx_limits();*/
-
-                        gs.change_state(States::Dismiss)
-                    }
-                    5 => {
-                        gs.anim_state.opcode = 0;
-                        //graphics.camera.move_to_anim_pos(gs.calculate_total_len(), &gs.v_up_orign);
-                        #[cfg(target_arch = "wasm32")]
-                        change_bend_step(0);
-                        gs.change_state(States::FullAnimate)
-                    }
-                    _ => {
-                        g_scene.dorn.dorn_action(&next_stage, &gs.v_up_orign);
-                        gs.anim_state = next_stage;
-                        gs.change_state(States::FullAnimate)
-                    }
+            }
+            //goes on from a pause, a finished sequence starts again
+            PlaySimulation => {
+                if (gs.sim_time >= SimTimeline::new(gs.current_lraclr(), &g_scene.bend_params).duration()) {
+                    gs.sim_time = 0.0;
+                    gs.anim_state = AnimState::default();
                 }
-
+                gs.change_state(States::FullAnimate)
+            }
+            PauseSimulation => gs.change_state(States::Dismiss),
+            //a seek shows the tube and leaves the sequence paused there
+            SeekSimulation(seek) => {
+                let seek = seek.clone();
+                let timeline = SimTimeline::new(gs.current_lraclr(), &g_scene.bend_params);
+                gs.sim_time = timeline.seek(gs.sim_time, &seek);
+                show_simulation(&graphics, &mut gs, &mut g_scene);
+                gs.change_state(States::Dismiss)
             }
             ChangeDornDir => {
                 if (signum(gs.v_up_orign.z) < 0.0) {
@@ -721,21 +1435,15 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                     if (!gs.is_reversed) {
                         #[cfg(target_arch = "wasm32")]
                         {
-                            let lraclr_arr_i32 = LRACLR::to_array(&gs.lraclr_arr);
-                            pipe_bend_ops(wasm_bindgen_futures::js_sys::Int32Array::from(
-                                lraclr_arr_i32.as_slice(),
-                            ))
+                            gs.pipe_program(&gs.lraclr_arr);
                         }
-                        cnc_to_poly(&gs.lraclr_arr, &gs.v_up_orign)
+                        gs.program_poly(false)
                     } else {
                         #[cfg(target_arch = "wasm32")]
                         {
-                            let lraclr_arr_i32 = LRACLR::to_array(&gs.lraclr_arr_reversed);
-                            pipe_bend_ops(wasm_bindgen_futures::js_sys::Int32Array::from(
-                                lraclr_arr_i32.as_slice(),
-                            ))
+                            gs.pipe_program(&gs.lraclr_arr_reversed);
                         }
-                        cnc_to_poly(&gs.lraclr_arr_reversed, &gs.v_up_orign)
+                        gs.program_poly(true)
                     }
                 };
                 let (v, i) = all_to_one(&cyls, &tors);
@@ -781,12 +1489,27 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                         lra_cmds.push(lra_cmd);
                     });
                 }
-
-                if (lra_cmds.is_empty()) {
-                    gs.change_state(States::Dismiss)
-                } else {
-                    lra_cmds[0].r = 0.0;
-                    gs.change_state(ReadyToLoad((lra_cmds, false)))
+                //the web sends the recognised program, its first rotation only turns the whole part
+                //unless a datum fixes it
+                if (!gs.datum.is_set()) {
+                    match lra_cmds.first_mut() {
+                        None => {}
+                        Some(first) => first.r = 0.0,
+                    }
+                }
+                gs.load_program(lra_cmds)
+            }
+            LoadProgram(data) => {
+                match WireMessage::from_bytes(data.as_slice()).and_then(|msg| msg.expect(WireKind::Program)) {
+                    //files and controllers write the rows in the output mode of the machine
+                    Ok(msg) => {
+                        let lra_cmds = from_output(&LRACLR::from_wire(&msg), &gs.machine.output);
+                        gs.load_program(lra_cmds)
+                    }
+                    Err(e) => {
+                        warn!("PROGRAM {}", e);
+                        gs.change_state(States::Dismiss)
+                    }
                 }
             }
             States::NewBendParams(params) => {
@@ -797,10 +1520,271 @@ pub fn key_frame(mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<Gl
                 g_scene.select_by_id(&graphics.queue, id.clone());
                 gs.revert_state()
             }
+            States::NewMachineProfile(params) => {
+                let params = params.clone();
+                gs.machine.set_params_from_f32vec(&params);
+                check_program(&graphics, &mut gs, &mut g_scene);
+                gs.revert_state()
+            }
+            States::NewObstacles(params) => {
+                let params = params.clone();
+                gs.machine.obstacles = Obstacle::from_f32vec(&params);
+                gs.revert_state()
+            }
+            States::NewCycleParams(params) => {
+                let params = params.clone();
+                gs.machine.cycle.set_params_from_f32vec(&params);
+                gs.estimate_cycle_time();
+                graphics.window.set_title(format!("{}", gs.cycle).as_str());
+                gs.revert_state()
+            }
+            States::NewRollParams(params) => {
+                let params = params.clone();
+                gs.roll_params.set_params_from_f32vec(&params);
+                gs.revert_state()
+            }
+            NewOutputMode(params) => {
+                let params = params.clone();
+                gs.machine.output.set_params_from_f32vec(&params);
+                warn!("OUTPUT {}", gs.machine.output);
+                #[cfg(target_arch = "wasm32")]
+                {
+                    if (!gs.current_lraclr().is_empty()) {
+                        gs.pipe_program(gs.current_lraclr());
+                    }
+                }
+                gs.revert_state()
+            }
+            ShowBothHands(plane) => {
+                let plane = MirrorPlane::from_i32(plane.clone());
+                if (!gs.lraclr_arr.is_empty()) {
+                    let (v, i, bbx) = gs.both_hands_mesh(plane);
+                    g_scene.mesh_size = i.len();
+                    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&MESH_ZEROS_I));
+                    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&i));
+                    graphics.queue.write_buffer(&g_scene.v_buffer_mesh, 0, bytemuck::cast_slice(&v));
+                    graphics.camera.set_tot_bbx(bbx);
+                    graphics.camera.move_camera_to_bbx_limits();
+                    warn!("BOTH HANDS {}", plane);
+                }
+                gs.change_state(States::Dismiss)
+            }
+            EditProgram(op) => {
+                let op = op.clone();
+                gs.edit_program(&op)
+            }
+            UndoEdit => gs.undo_edit(),
+            RedoEdit => gs.redo_edit(),
+            //the opposite hand becomes the program, the allowances keep their lengths
+            MirrorProgram(plane) => {
+                let plane = MirrorPlane::from_i32(plane.clone());
+                if (gs.lraclr_arr.is_empty()) {
+                    gs.change_state(States::Dismiss)
+                } else {
+                    let mirrored = mirror_lraclr(&gs.lraclr_arr, plane);
+                    warn!("MIRROR {}", plane);
+                    gs.change_state(ReadyToLoad((mirrored, false)))
+                }
+            }
+            //the other revision is a wire in the output mode of the machine, as for LoadProgram
+            CompareProgram(data) => {
+                match WireMessage::from_bytes(data.as_slice()).and_then(|msg| msg.expect(WireKind::Program)) {
+                    Ok(msg) => {
+                        let other = from_output(&LRACLR::from_wire(&msg), &gs.machine.output);
+                        if (other.is_empty()) { gs.change_state(States::Dismiss) } else { gs.change_state(CompareLRA(other)) }
+                    }
+                    Err(e) => {
+                        warn!("COMPARE {}", e);
+                        gs.change_state(States::Dismiss)
+                    }
+                }
+            }
+            //the loaded part stays the program, the other revision is only drawn over it
+            CompareLRA(other) => {
+                let other = other.clone();
+                if (!gs.lraclr_arr.is_empty() && !other.is_empty()) {
+                    gs.compare_program(&other);
+                    match gs.diff_id_offset(&other) {
+                        None => warn!("DIFF NOT DRAWN, {} AND {} ROWS DO NOT FIT THE VIEW", gs.lraclr_arr.len(), other.len()),
+                        Some(offset) => {
+                            let (v, i, bbx) = gs.diff_mesh(&other, offset);
+                            g_scene.mesh_size = i.len();
+                            graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&MESH_ZEROS_I));
+                            graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&i));
+                            graphics.queue.write_buffer(&g_scene.v_buffer_mesh, 0, bytemuck::cast_slice(&v));
+                            graphics.camera.set_tot_bbx(bbx);
+                            graphics.camera.move_camera_to_bbx_limits();
+                            g_scene.reset_colors(&graphics.queue);
+                            match &gs.program_diff {
+                                None => {}
+                                Some(diff) => {
+                                    let other_ids = |kind: DiffKind| -> Vec<i32> { diff.mesh_ids(kind, true).iter().map(|id| id + offset).collect() };
+                                    g_scene.highlight_ids(&graphics.queue, &diff.mesh_ids(DiffKind::Changed, false), DIFF_CHANGED_COLOR);
+                                    g_scene.highlight_ids(&graphics.queue, &diff.mesh_ids(DiffKind::Removed, false), DIFF_REMOVED_COLOR);
+                                    g_scene.highlight_ids(&graphics.queue, &other_ids(DiffKind::Changed), DIFF_CHANGED_COLOR);
+                                    g_scene.highlight_ids(&graphics.queue, &other_ids(DiffKind::Added), DIFF_ADDED_COLOR);
+                                }
+                            }
+                        }
+                    }
+                }
+                gs.change_state(States::Dismiss)
+            }
+            ConvertLargeBends => {
+                match gs.large_bend_source.take() {
+                    Some(source) => gs.change_state(ReadyToLoad((source, false))),
+                    None => {
+                        let (converted, instructions) = convert_large_bends(&gs.lraclr_arr, &gs.machine, &gs.roll_params);
+                        let mut report = String::new();
+                        instructions.iter().for_each(|ri| {
+                            report.push_str(format!("{}\n", ri).as_str());
+                        });
+                        warn!("LARGE BENDS {:?}\n{}", gs.roll_params.mode, report);
+                        #[cfg(target_arch = "wasm32")]
+                        {
+                            pipe_roll_instructions(Uint8Array::from(RollInstruction::to_wire(&instructions).to_bytes().as_slice()), report.as_str());
+                        }
+                        if (gs.roll_params.mode == LargeBendMode::Bump && converted.len() != gs.lraclr_arr.len()) {
+                            gs.large_bend_source = Some(gs.lraclr_arr.clone());
+                            g_scene.bend_step = 1;
+                            gs.change_state(ReadyToLoad((converted, false)))
+                        } else {
+                            gs.change_state(States::Dismiss)
+                        }
+                    }
+                }
+            }
+            States::NewToolLibrary(params) => {
+                let params = params.clone();
+                gs.tools = ToolLibrary::from_f32vec(&params);
+                check_program(&graphics, &mut gs, &mut g_scene);
+                gs.revert_state()
+            }
+            States::NewTubeStock(params) => {
+                let params = params.clone();
+                gs.stock.set_params_from_f32vec(&params);
+                check_program(&graphics, &mut gs, &mut g_scene);
+                gs.revert_state()
+            }
+            CheckCollisions => {
+                let collision: Option<Collision> = simulate_collisions(gs.current_lraclr(), &gs.v_up_orign, &g_scene.bend_params, &gs.machine.obstacles);
+                g_scene.show_violations(&graphics.queue, &gs.violations);
+                match &collision {
+                    None => {
+                        warn!("NO COLLISIONS");
+                    }
+                    Some(c) => {
+                        warn!("COLLISION {}", c);
+                        g_scene.highlight_ids(&graphics.queue, &vec![c.anim_id], VIOLATION_COLOR);
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_collision(Uint8Array::from(Collision::to_wire(&collision).to_bytes().as_slice()));
+                }
+                gs.collision = collision;
+                gs.change_state(States::Dismiss)
+            }
+            OptimizeProgram => {
+                let candidates: Vec<ProgramCandidate> = optimize_program(&gs.lraclr_arr, &gs.machine, &g_scene.bend_params);
+                let report = ProgramCandidate::report(&candidates);
+                warn!("OPTIMIZER\n{}", report);
+                #[cfg(target_arch = "wasm32")]
+                {
+                    pipe_optimizer_results(Uint8Array::from(ProgramCandidate::to_wire(&candidates).to_bytes().as_slice()), report.as_str());
+                }
+                let best: Option<ProgramCandidate> = candidates.first().cloned();
+                gs.candidates = candidates;
+                match best {
+                    None => gs.change_state(States::Dismiss),
+                    Some(best) => {
+                        gs.v_up_orign = best.v_up_orign;
+                        if (best.is_reversed) {
+                            gs.allowance = gs.allowance.reversed();
+                        }
+                        gs.change_state(ReadyToLoad((best.lraclr, false)))
+                    }
+                }
+            }
         }
     };
     gs.state = next_state;
 }
+//Machine limits, tooling, bendability and cycle time of the loaded program, shown in the view
+fn check_program(graphics: &Graphics, gs: &mut GlobalState, g_scene: &mut GlobalScene) {
+    gs.validate_machine_limits();
+    gs.assign_tools();
+    gs.assess_bendability();
+    gs.estimate_cycle_time();
+    g_scene.show_violations(&graphics.queue, &gs.violations);
+    g_scene.show_tool_errors(&graphics.queue, &gs.tool_errors);
+    g_scene.show_bend_risks(&graphics.queue, &gs.assessments);
+    graphics.window.set_title(format!("{}", gs.cycle).as_str());
+}
+//Draws the bend sequence at gs.sim_time with the dims and the dorn of the op running then, true once it is finished
+fn show_simulation(graphics: &Graphics, gs: &mut GlobalState, g_scene: &mut GlobalScene) -> bool {
+    let (cyls, tors, pose) = simulate_at(gs.current_lraclr(), &gs.v_up_orign, &g_scene.bend_params, gs.sim_time);
+    gs.sim_time = pose.t;
+    let (cyls, tors) = if (pose.is_done()) {
+        g_scene.dim_x.is_active = false;
+        g_scene.dim_z.is_active = false;
+        g_scene.dim_b.is_active = false;
+        g_scene.dorn.set_dorn_park(&gs.v_up_orign);
+        //the animated program ends as the other one
+        gs.program_poly(!gs.is_reversed)
+    } else {
+        let stage = &pose.anim_state;
+        match stage.opcode {
+            0 => {
+                g_scene.dim_x.is_active = true;
+                g_scene.dim_z.is_active = false;
+                g_scene.dim_b.is_active = false;
+                g_scene.dim_x.set_scale(stage.lra.pipe_radius);
+                g_scene.dim_x.set_y(stage.lra.pipe_radius);
+                g_scene.dim_x.set_x(stage.value);
+                g_scene.dim_x.set_pipe_radius(stage.lra.pipe_radius);
+            }
+            1 => {
+                g_scene.dim_x.is_active = false;
+                g_scene.dim_b.is_active = false;
+                g_scene.dim_z.is_active = true;
+                g_scene.dim_z.set_scale(stage.lra.pipe_radius);
+                g_scene.dim_z.set_z(stage.lra.pipe_radius);
+                g_scene.dim_z.set_r(stage.value);
+                g_scene.dim_z.set_pipe_radius(stage.lra.pipe_radius);
+            }
+            _ => {
+                g_scene.dim_b.is_active = true;
+                g_scene.dim_x.is_active = false;
+                g_scene.dim_z.is_active = false;
+                g_scene.dim_b.set_scale(stage.lra.pipe_radius);
+                g_scene.dim_b.set_y(stage.lra.clr);
+                g_scene.dim_b.set_angle(stage.value);
+                g_scene.dim_b.set_pipe_radius(stage.lra.pipe_radius);
+            }
+        }
+        g_scene.dorn.dorn_action(stage, &gs.v_up_orign);
+        let mut cyls = cyls;
+        gs.split_anim_allowances(&mut cyls, stage);
+        (cyls, tors)
+    };
+    let (v, i) = all_to_one(&cyls, &tors);
+    g_scene.mesh_size = i.len();
+    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&MESH_ZEROS_I));
+    graphics.queue.write_buffer(&g_scene.i_buffer_mesh, 0, bytemuck::cast_slice(&i));
+    graphics.queue.write_buffer(&g_scene.v_buffer_mesh, 0, bytemuck::cast_slice(&v));
+    #[cfg(target_arch = "wasm32")]
+    {
+        //a stage not started yet reports the first step too
+        if (gs.anim_state.opcode == 5 || gs.anim_state.op_counter != pose.anim_state.op_counter) {
+            change_bend_step(pose.anim_state.op_counter);
+        }
+        pipe_simulation(Uint8Array::from(pose.to_wire().to_bytes().as_slice()));
+    }
+    let is_done = pose.is_done();
+    gs.anim_state = pose.anim_state;
+    is_done
+}
 #[inline]
 pub fn render(mut graphics: UniqueViewMut<Graphics>, mut g_scene: UniqueViewMut<GlobalScene>, mut gs: UniqueViewMut<GlobalState>) {
     match graphics.surface.get_current_texture() {
@@ -1557,9 +2541,28 @@ pub fn check_remote(mut g_scene: UniqueViewMut<GlobalScene>, mut gs: UniqueViewM
         }
         FullAnimate => {
             gs.anim_state = AnimState::default();
+            gs.sim_time = 0.0;
             g_scene.bend_step = 1;
             gs.state = FullAnimate
         }
+        PlaySimulation => {
+            if (!gs.lraclr_arr.is_empty()) {
+                g_scene.bend_step = 1;
+                gs.state = PlaySimulation;
+            }
+        }
+        PauseSimulation => {
+            match gs.state {
+                FullAnimate => gs.state = PauseSimulation,
+                _ => {}
+            }
+        }
+        SeekSimulation(seek) => {
+            if (!gs.lraclr_arr.is_empty()) {
+                g_scene.bend_step = 1;
+                gs.state = SeekSimulation(seek);
+            }
+        }
         ReverseLRACLR => {
             if (!gs.lraclr_arr_reversed.is_empty()) {
                 g_scene.bend_step = 1;
@@ -1574,114 +2577,275 @@ pub fn check_remote(mut g_scene: UniqueViewMut<GlobalScene>, mut gs: UniqueViewM
             g_scene.bend_step = 1;
             gs.state = LoadLRA(v);
         }
+        LoadProgram(v) => {
+            g_scene.bend_step = 1;
+            gs.state = LoadProgram(v);
+        }
         States::NewBendParams(v) => {
             gs.state = NewBendParams(v);
         }
         States::SelectFromWeb(id) => {
             gs.state = SelectFromWeb(id);
         }
+        States::NewMachineProfile(v) => {
+            gs.state = NewMachineProfile(v);
+        }
+        States::NewObstacles(v) => {
+            gs.state = NewObstacles(v);
+        }
+        States::NewCycleParams(v) => {
+            gs.state = NewCycleParams(v);
+        }
+        States::NewToolLibrary(v) => {
+            gs.state = NewToolLibrary(v);
+        }
+        States::NewRollParams(v) => {
+            gs.state = NewRollParams(v);
+        }
+        States::NewTubeStock(v) => {
+            gs.state = NewTubeStock(v);
+        }
+        LoadStp(v) => {
+            gs.v_up_orign = P_UP_REVERSE;
+            g_scene.bend_step = 1;
+            gs.state = LoadStp(v);
+        }
+        LoadProject(v) => {
+            gs.state = LoadProject(v);
+        }
+        SaveProject => {
+            gs.state = SaveProject;
+        }
+        NewJobInfo(v) => {
+            gs.state = NewJobInfo(v);
+        }
+        AddToCutList(v) => {
+            gs.state = AddToCutList(v);
+        }
+        ClearCutList => {
+            gs.state = ClearCutList;
+        }
+        NewNestingParams(v) => {
+            gs.state = NewNestingParams(v);
+        }
+        NewAllowanceParams(v) => {
+            gs.state = NewAllowanceParams(v);
+        }
+        ExportStp => {
+            gs.state = ExportStp;
+        }
+        ExportProgramStp(lraclr_arr) => {
+            gs.state = ExportProgramStp(lraclr_arr);
+        }
+        ExportMesh(v) => {
+            gs.state = ExportMesh(v);
+        }
+        ExportAnimation => {
+            gs.state = ExportAnimation;
+        }
+        ExportDrawing(v) => {
+            gs.state = ExportDrawing(v);
+        }
+        ExportMarking(v) => {
+            gs.state = ExportMarking(v);
+        }
+        OpenFile(path) => {
+            gs.state = OpenFile(path);
+        }
+        NewRotationDatum(v) => {
+            gs.state = NewRotationDatum(v);
+        }
+        NewOutputMode(v) => {
+            gs.state = NewOutputMode(v);
+        }
+        ShowBothHands(v) => {
+            if (!gs.lraclr_arr.is_empty()) {
+                gs.state = ShowBothHands(v);
+            }
+        }
+        MirrorProgram(v) => {
+            if (!gs.lraclr_arr.is_empty()) {
+                g_scene.bend_step = 1;
+                gs.state = MirrorProgram(v);
+            }
+        }
+        EditProgram(op) => {
+            g_scene.bend_step = 1;
+            gs.state = EditProgram(op);
+        }
+        UndoEdit => {
+            g_scene.bend_step = 1;
+            gs.state = UndoEdit;
+        }
+        RedoEdit => {
+            g_scene.bend_step = 1;
+            gs.state = RedoEdit;
+        }
+        CompareProgram(v) => {
+            if (!gs.lraclr_arr.is_empty()) {
+                gs.state = CompareProgram(v);
+            }
+        }
+        CompareLRA(v) => {
+            if (!gs.lraclr_arr.is_empty()) {
+                gs.state = CompareLRA(v);
+            }
+        }
+        ConvertLargeBends => {
+            if (!gs.lraclr_arr.is_empty()) {
+                gs.state = ConvertLargeBends;
+            }
+        }
+        CheckCollisions => {
+            if (!gs.lraclr_arr.is_empty()) {
+                gs.state = CheckCollisions;
+            }
+        }
+        OptimizeProgram => {
+            if (!gs.lraclr_arr.is_empty()) {
+                g_scene.bend_step = 1;
+                gs.state = OptimizeProgram;
+            }
+        }
     }
 }
+pub fn open_file(path: String, mut gs: UniqueViewMut<GlobalState>) {
+    gs.state = OpenFile(path);
+}
 pub fn on_keyboard(event: KeyEvent, mut graphics: UniqueViewMut<Graphics>, mut gs: UniqueViewMut<GlobalState>, mut g_scene: UniqueViewMut<GlobalScene>) {
     match event.physical_key {
+        PhysicalKey::Code(KeyCode::F1) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = ShowBothHands(MirrorPlane::Side as i32);
+                }
+            }
+        },
+        //Z and Y undo and redo the last program edit
+        PhysicalKey::Code(KeyCode::KeyZ) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                g_scene.bend_step = 1;
+                gs.state = UndoEdit;
+            }
+        },
+        PhysicalKey::Code(KeyCode::KeyY) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                g_scene.bend_step = 1;
+                gs.state = RedoEdit;
+            }
+        },
         PhysicalKey::Code(KeyCode::F2) => match event.state {
             ElementState::Pressed => {}
-            ElementState::Released => {}
+            ElementState::Released => {
+                gs.toggle_export_dir();
+            }
         },
-        PhysicalKey::Code(KeyCode::F3) => {
-            match event.state {
-                ElementState::Pressed => {}
-                ElementState::Released => {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    {
-                        let stp: Vec<u8> = Vec::from((include_bytes!("../files/2.stp")).as_slice());
+        //reload the opened file
+        PhysicalKey::Code(KeyCode::F3) | PhysicalKey::Code(KeyCode::F4) | PhysicalKey::Code(KeyCode::F6) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    if (gs.file_path.is_empty()) {
+                        warn!("NO FILE OPENED");
+                    } else {
                         g_scene.bend_step = 1;
-                        let (lraclr_arr) = analyze_stp(&stp);
-                        //let lraclr_arr_i32 = LRACLR::to_array(&lraclr_arr);
-                        gs.state = ReadyToLoad((lraclr_arr, true));
-                        gs.v_up_orign = P_UP_REVERSE;
-                        //let obj_file = ops.all_to_one_obj_bin();
-                        //warn!("FILE ANALYZED C {:?}",prerender.steps_data.len());
-
+                        gs.state = OpenFile(gs.file_path.clone());
                     }
                 }
             }
-        }
-        PhysicalKey::Code(KeyCode::F4) => {
-            match event.state {
-                ElementState::Pressed => {}
-                ElementState::Released => {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    {
-                        g_scene.bend_step = 1;
-                        let stp: Vec<u8> = Vec::from((include_bytes!("../files/2.stp")).as_slice());
-                        let lraclr_arr = analyze_stp(&stp);
-                        let lraclr_arr_reversed: Vec<LRACLR> = cnc::reverse_lraclr(&lraclr_arr);
-                        gs.state = ReadyToLoad((lraclr_arr, true));
-                        gs.v_up_orign = P_UP_REVERSE;
-
-                        //gs.state = ReadyToLoad((prerender,lraclr_arr_reversed));
-                        //let obj_file = ops.all_to_one_obj_bin();
-                        //warn!("FILE ANALYZED C {:?}",prerender.steps_data.len());
-
-                    }
+        },
+        PhysicalKey::Code(KeyCode::F5) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = PlaySimulation;
                 }
             }
-        }
-        PhysicalKey::Code(KeyCode::F5) => match event.state {
+        },
+        //space pauses and plays the bend sequence, the arrows step it one op back or forward
+        PhysicalKey::Code(KeyCode::Space) => match event.state {
             ElementState::Pressed => {}
             ElementState::Released => {
-                gs.state = FullAnimate;
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = match gs.state {
+                        FullAnimate => PauseSimulation,
+                        _ => PlaySimulation,
+                    };
+                }
             }
         },
-        PhysicalKey::Code(KeyCode::F6) => match event.state {
+        PhysicalKey::Code(KeyCode::ArrowLeft) => match event.state {
             ElementState::Pressed => {}
             ElementState::Released => {
-                g_scene.bend_step = 1;
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/1.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/13.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/12.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/10.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/9.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/3.stp")).as_slice());
-                let stp: Vec<u8> = Vec::from((include_bytes!("../files/2.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/1.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/16.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/a.step")).as_slice());
-                let lraclr_arr = analyze_stp(&stp);
-                let lraclr_arr_reversed: Vec<LRACLR> = cnc::reverse_lraclr(&lraclr_arr);
-                gs.state = ReadyToLoad((lraclr_arr, true));
-                gs.v_up_orign = P_UP_REVERSE;
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = SeekSimulation(SimSeek::Step(-1));
+                }
             }
         },
-        PhysicalKey::Code(KeyCode::F7) => match event.state {
+        PhysicalKey::Code(KeyCode::ArrowRight) => match event.state {
             ElementState::Pressed => {}
             ElementState::Released => {
-                g_scene.bend_step = 1;
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/1.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/13.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/12.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/10.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/9.stp")).as_slice());
-                let stp: Vec<u8> = Vec::from((include_bytes!("../files/2.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/2.stp")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/D3.step")).as_slice());
-                //let stp: Vec<u8> = Vec::from((include_bytes!("../files/a.step")).as_slice());
-                let lraclr_arr = analyze_stp(&stp);
-                let (cyls, tors) = cnc_to_poly(&lraclr_arr, &gs.v_up_orign);
-                let file_stp = all_to_stp(&cyls, &tors);
-                let path = format!("d:\\pipe_project\\teat.stp");
-                match File::create(path) {
-                    Ok(file) => {
-                        let mut writer = BufWriter::new(file);
-                        writer.write_all(&file_stp);
-                        writer.flush();
-                        warn!("FILE STORED")
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = SeekSimulation(SimSeek::Step(1));
+                }
+            }
+        },
+        PhysicalKey::Code(KeyCode::F8) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                if (!gs.lraclr_arr.is_empty()) {
+                    gs.state = CheckCollisions;
+                }
+            }
+        },
+        PhysicalKey::Code(KeyCode::F9) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                if (!gs.lraclr_arr.is_empty()) {
+                    g_scene.bend_step = 1;
+                    gs.state = OptimizeProgram;
+                }
+            }
+        },
+        PhysicalKey::Code(KeyCode::F10) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                if (!gs.lraclr_arr.is_empty()) {
+                    if (gs.roll_params.mode == LargeBendMode::RotaryDraw) {
+                        gs.roll_params.mode = LargeBendMode::Bump;
+                    }
+                    gs.state = ConvertLargeBends;
+                }
+            }
+        },
+        PhysicalKey::Code(KeyCode::F11) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                gs.state = SaveProject;
+            }
+        },
+        PhysicalKey::Code(KeyCode::F12) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    match std::fs::read(gs.export_path(PROJECT_FILE)) {
+                        Ok(data) => gs.state = LoadProject(data),
+                        Err(e) => warn!("PROJECT {}", e),
                     }
-                    Err(_) => {}
                 }
             }
         },
+        PhysicalKey::Code(KeyCode::F7) => match event.state {
+            ElementState::Pressed => {}
+            ElementState::Released => {
+                gs.state = ExportStp;
+            }
+        },
         _ => {}
     }
 }
\ No newline at end of file
@@ -17,6 +17,6 @@ pub async  fn runrust() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     let _ = console_log::init_with_level(Level::Info);
     let event_loop: EventLoop<Graphics> = EventLoop::with_user_event().build().unwrap();
-    let mut app: App = App::new(&event_loop);
+    let mut app: App = App::new(&event_loop, None);
     let _ = event_loop.run_app(&mut app);
 }
@@ -2,6 +2,7 @@ mod device;
 mod algo;
 mod utils;
 mod remote;
+mod cli;
 //mod libw;
 
 use env_logger::{Builder, Target};
@@ -14,13 +15,21 @@ use crate::device::app::App;
 use crate::device::graphics::Graphics;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if (cli::is_command(&args)) {
+        let mut builder = Builder::from_default_env();
+        builder.target(Target::Stderr);
+        builder.filter(None, LevelFilter::Error).init();
+        std::process::exit(cli::run(&args));
+    }
+
     let mut builder = Builder::from_default_env();
     builder.target(Target::Stdout);
     builder.filter(None, LevelFilter::Warn).init();
 
     let event_loop: EventLoop<Graphics> = EventLoop::with_user_event().build().unwrap();
 
-    let mut app: App = App::new(&event_loop);
+    let mut app: App = App::new(&event_loop, args.first().cloned());
 
     let _ = event_loop.run_app(&mut app);
 }
@@ -6,10 +6,14 @@ use log::{info, warn, Level};
 use once_cell::sync::Lazy;
 use shipyard::Unique;
 use web_sys::js_sys::{Float32Array, Uint8Array};
-use crate::algo::{analyze_stp, P_UP_REVERSE};
-use crate::algo::cnc::{all_to_stp, cnc_to_poly, LRACLR};
+use crate::algo::analyze_stp;
+use crate::algo::cnc::LRACLR;
+use crate::algo::edit::EditOp;
+use crate::algo::simulation::SimSeek;
+use crate::algo::datum::RotationDatum;
+use crate::algo::deviation::{analyze_stp_verified, MAX_DEVIATION};
 use crate::device::graphics::{Graphics, States};
-use crate::device::graphics::States::{ChangeDornDir, FullAnimate, LoadLRA, ReadyToLoad, ReverseLRACLR, Dismiss, NewBendParams, SelectFromWeb};
+use crate::device::graphics::States::{ChangeDornDir, FullAnimate, LoadLRA, ReadyToLoad, ReverseLRACLR, Dismiss, NewBendParams, SelectFromWeb, NewMachineProfile, NewObstacles, CheckCollisions, OptimizeProgram, NewCycleParams, NewToolLibrary, NewRollParams, ConvertLargeBends, NewTubeStock, LoadStp, LoadProject, SaveProject, NewJobInfo, LoadProgram, AddToCutList, ClearCutList, NewNestingParams, NewAllowanceParams, ExportStp, ExportProgramStp, ExportMesh, ExportAnimation, ExportDrawing, ExportMarking, NewRotationDatum, NewOutputMode, ShowBothHands, MirrorProgram, EditProgram, UndoEdit, RedoEdit, CompareLRA, CompareProgram, PlaySimulation, PauseSimulation, SeekSimulation};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::wasm_bindgen;
 #[cfg(target_arch = "wasm32")]
@@ -43,6 +47,40 @@ pub enum RemoteCommand {
     OnChangeBendParams((Vec<f32>)),
     OnSelectByTable(i32),
     OnStpFileRequest((Vec<f32>)),
+    OnChangeMachineProfile((Vec<f32>)),
+    OnChangeObstacles((Vec<f32>)),
+    OnCheckCollisions,
+    OnOptimize,
+    OnChangeCycleParams((Vec<f32>)),
+    OnChangeToolLibrary((Vec<f32>)),
+    OnChangeRollParams((Vec<f32>)),
+    OnConvertLargeBends,
+    OnChangeTubeStock((Vec<f32>)),
+    OnLoadProject((Vec<u8>)),
+    OnSaveProject,
+    OnChangeJobInfo((String, String)),
+    OnLoadProgram((Vec<u8>)),
+    OnAddToCutList(i32),
+    OnClearCutList,
+    OnChangeNestingParams((Vec<f32>)),
+    OnChangeAllowanceParams((Vec<f32>)),
+    OnExportStp,
+    OnExportMesh((i32, i32)),
+    OnExportAnimation,
+    OnExportDrawing(i32),
+    OnExportMarking(i32),
+    OnChangeRotationDatum((Vec<f32>)),
+    OnChangeOutputMode((Vec<f32>)),
+    OnShowBothHands(i32),
+    OnMirrorProgram(i32),
+    OnEditProgram((Vec<f32>)),
+    OnUndoEdit,
+    OnRedoEdit,
+    OnCompareProgram((Vec<u8>)),
+    OnCompareStp((Vec<u8>)),
+    OnPlaySimulation,
+    OnPauseSimulation,
+    OnSeekSimulation((Vec<f32>)),
 }
 
 
@@ -65,12 +103,7 @@ impl InCmd {
                     Some(command) => {
                         match command {
                             RemoteCommand::OnLoadSTPfile(stp) => {
-                                let  lracmds= analyze_stp(&stp);
-                                if(lracmds.is_empty()){
-                                    Dismiss
-                                }else{
-                                    ReadyToLoad( (lracmds,true))
-                                }
+                                LoadStp(stp)
                             }
                             RemoteCommand::OnSelectById(id) => {
                                 Dismiss
@@ -90,6 +123,9 @@ impl InCmd {
                             RemoteCommand::OnLoadLRAcommands(lra) => {
                                 LoadLRA(lra)
                             }
+                            RemoteCommand::OnLoadProgram(data) => {
+                                LoadProgram(data)
+                            }
                             RemoteCommand::OnChangeBendParams(params) => {
                                 NewBendParams(params)
                             }
@@ -121,13 +157,131 @@ impl InCmd {
                                         lra_cmds.push(lra_cmd);
                                     });
                                 }
-                                let (cyls, tors) = cnc_to_poly(&lra_cmds, &P_UP_REVERSE);
-                                let file=all_to_stp(&cyls,&tors);
-                                #[cfg(target_arch = "wasm32")]{
-                                    pipe_stp_file(wasm_bindgen_futures::js_sys::Uint8Array::from(file.as_slice()));
+                                ExportProgramStp(lra_cmds)
+                            }
+                            RemoteCommand::OnChangeMachineProfile(params) => {
+                                NewMachineProfile(params)
+                            }
+                            RemoteCommand::OnChangeObstacles(params) => {
+                                NewObstacles(params)
+                            }
+                            RemoteCommand::OnCheckCollisions => {
+                                CheckCollisions
+                            }
+                            RemoteCommand::OnOptimize => {
+                                OptimizeProgram
+                            }
+                            RemoteCommand::OnChangeCycleParams(params) => {
+                                NewCycleParams(params)
+                            }
+                            RemoteCommand::OnChangeToolLibrary(params) => {
+                                NewToolLibrary(params)
+                            }
+                            RemoteCommand::OnChangeRollParams(params) => {
+                                NewRollParams(params)
+                            }
+                            RemoteCommand::OnConvertLargeBends => {
+                                ConvertLargeBends
+                            }
+                            RemoteCommand::OnChangeTubeStock(params) => {
+                                NewTubeStock(params)
+                            }
+                            RemoteCommand::OnLoadProject(data) => {
+                                LoadProject(data)
+                            }
+                            RemoteCommand::OnSaveProject => {
+                                SaveProject
+                            }
+                            RemoteCommand::OnChangeJobInfo((part_number, notes)) => {
+                                NewJobInfo((part_number, notes))
+                            }
+                            RemoteCommand::OnAddToCutList(quantity) => {
+                                AddToCutList(quantity)
+                            }
+                            RemoteCommand::OnClearCutList => {
+                                ClearCutList
+                            }
+                            RemoteCommand::OnChangeNestingParams(params) => {
+                                NewNestingParams(params)
+                            }
+                            RemoteCommand::OnChangeAllowanceParams(params) => {
+                                NewAllowanceParams(params)
+                            }
+                            RemoteCommand::OnExportStp => {
+                                ExportStp
+                            }
+                            RemoteCommand::OnExportMesh((format, step)) => {
+                                ExportMesh((format, step))
+                            }
+                            RemoteCommand::OnExportAnimation => {
+                                ExportAnimation
+                            }
+                            RemoteCommand::OnExportDrawing(format) => {
+                                ExportDrawing(format)
+                            }
+                            RemoteCommand::OnExportMarking(format) => {
+                                ExportMarking(format)
+                            }
+                            RemoteCommand::OnChangeRotationDatum(params) => {
+                                NewRotationDatum(params)
+                            }
+                            RemoteCommand::OnChangeOutputMode(params) => {
+                                NewOutputMode(params)
+                            }
+                            RemoteCommand::OnShowBothHands(plane) => {
+                                ShowBothHands(plane)
+                            }
+                            RemoteCommand::OnMirrorProgram(plane) => {
+                                MirrorProgram(plane)
+                            }
+                            RemoteCommand::OnEditProgram(params) => {
+                                match EditOp::from_f32vec(&params) {
+                                    None => {
+                                        warn!("EDIT {:?} UNKNOWN", params);
+                                        Dismiss
+                                    }
+                                    Some(op) => EditProgram(op),
+                                }
+                            }
+                            RemoteCommand::OnUndoEdit => {
+                                UndoEdit
+                            }
+                            RemoteCommand::OnRedoEdit => {
+                                RedoEdit
+                            }
+                            RemoteCommand::OnCompareProgram(data) => {
+                                CompareProgram(data)
+                            }
+                            RemoteCommand::OnCompareStp(stp) => {
+                                //the diff aligns the first rotation, the datum does not matter here
+                                match analyze_stp_verified(&stp, MAX_DEVIATION, &RotationDatum::default()) {
+                                    Err(e) => {
+                                        warn!("COMPARE {}", e);
+                                        Dismiss
+                                    }
+                                    Ok((lracmds, report)) => {
+                                        if(lracmds.is_empty()){
+                                            Dismiss
+                                        }else if(report.is_failed()){
+                                            warn!("RECOGNITION FAILED {}", report);
+                                            Dismiss
+                                        }else{
+                                            CompareLRA(lracmds)
+                                        }
+                                    }
+                                }
+                            }
+                            RemoteCommand::OnPlaySimulation => {
+                                PlaySimulation
+                            }
+                            RemoteCommand::OnPauseSimulation => {
+                                PauseSimulation
+                            }
+                            RemoteCommand::OnSeekSimulation(v) => {
+                                match SimSeek::from_f32vec(&v) {
+                                    None => Dismiss,
+                                    Some(seek) => SeekSimulation(seek),
                                 }
-                               
-                                Dismiss 
                             }
                         }
                     }
@@ -176,6 +330,21 @@ pub async unsafe fn read_lra_commands(arr: Float32Array) {
     }
 }
 
+//Program as a wire message, see utils::wire
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn read_program(arr: Uint8Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<u8> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnLoadProgram(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub async unsafe fn do_bend() {
@@ -261,32 +430,635 @@ pub async unsafe fn stp_file_request(arr: Float32Array) {
     }
 }
 
-//////////FROM THIS
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(js_namespace = wvservice)]
-extern "C" {
-    pub fn pipe_bend_ops(ids: Int32Array);
+#[wasm_bindgen]
+pub async unsafe fn change_machine_profile(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(handler_v.len()>8){
+                m.values.push_back(RemoteCommand::OnChangeMachineProfile(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(js_namespace = wvservice)]
-extern "C" {
-    pub fn change_bend_step(id: i32);
+#[wasm_bindgen]
+pub async unsafe fn change_obstacles(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(handler_v.len()%7==0){
+                m.values.push_back(RemoteCommand::OnChangeObstacles(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(js_namespace = wvservice)]
-extern "C" {
-    pub fn bend_settings(settings: Float32Array);
+#[wasm_bindgen]
+pub async unsafe fn check_collisions() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnCheckCollisions);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(js_namespace = wvservice)]
-extern "C" {
-    pub fn select_by_id(id: i32);
+#[wasm_bindgen]
+pub async unsafe fn optimize() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnOptimize);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
 }
+
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(js_namespace = wvservice)]
-extern "C" {
-    pub fn pipe_stp_file(ids: Uint8Array);
-}
\ No newline at end of file
+#[wasm_bindgen]
+pub async unsafe fn change_cycle_params(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeCycleParams(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_tool_library(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(handler_v.len() % 5 == 0){
+                m.values.push_back(RemoteCommand::OnChangeToolLibrary(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_roll_params(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeRollParams(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn convert_large_bends() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnConvertLargeBends);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_tube_stock(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeTubeStock(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn read_project_file(arr: Uint8Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Warn);
+    let mut handler_v: Vec<u8> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnLoadProject(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn save_project() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnSaveProject);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_job_info(part_number: String, notes: String) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnChangeJobInfo((part_number, notes)));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//Adds the loaded part to the cut list, the stock is nested again after every change
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn add_to_cut_list(quantity: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnAddToCutList(quantity));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn clear_cut_list() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnClearCutList);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_nesting_params(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnChangeNestingParams(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_allowance_params(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeAllowanceParams(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//[0] off, [1, x, y, z] model direction, [2, face id] STEP face whose normal or axis points up at loading
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_rotation_datum(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeRotationDatum(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//[rotation, feed, sense], 0 incremental and 1 absolute, sense 0 CCW and 1 CW. Used by the drawing and program exports
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn change_output_mode(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnChangeOutputMode(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//plane 0 side, 1 top, 2 front. The part and its opposite hand side by side for checking, the program stays as it is
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn show_both_hands(plane: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnShowBothHands(plane));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//plane 0 side, 1 top, 2 front. Loads the opposite hand in place of the current program
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn mirror_program(plane: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnMirrorProgram(plane));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//[kind, row, values...]: 0 straight [l], 1 rotation [r], 2 bend angle [a], 3 clr [clr], 4 insert a bend in front of the row [l, r, a, clr],
+//5 delete the bend of the row. The result comes back as a new program, errors and the history state through pipe_edit_state
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn edit_program(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            if(!handler_v.is_empty()){
+                m.values.push_back(RemoteCommand::OnEditProgram(handler_v));
+            }
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn undo_edit() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnUndoEdit);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn redo_edit() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnRedoEdit);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//Revision of the loaded part as program bytes, the rows, deltas and shifts come back through pipe_diff
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn compare_program(arr: Uint8Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<u8> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnCompareProgram(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn compare_stp(arr: Uint8Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<u8> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnCompareStp(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//do_bend starts the bend sequence from the beginning, play_simulation goes on from where it was paused.
+//Every shown moment comes back through pipe_simulation
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn play_simulation() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnPlaySimulation);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn pause_simulation() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnPauseSimulation);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//[kind, value]: 0 time in seconds for scrubbing, 1 steps to go, -1 is the previous op and 1 the next one,
+//2 start of op N as counted by change_bend_step. The sequence stays paused at the new time
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn seek_simulation(arr: Float32Array) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    let mut handler_v: Vec<f32> = arr.to_vec();
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnSeekSimulation(handler_v));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//Exports the current program with the part number and the wall of the tube stock
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn export_stp() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnExportStp);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//format 0 STL, 1 OBJ, 2 glTF binary. A negative step exports the finished part, otherwise the tube after that bend step
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn export_mesh(format: i32, step: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnExportMesh((format, step)));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//Whole bend sequence as an animated glTF binary, sent back through pipe_mesh_file
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn export_animation() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnExportAnimation);
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//format 0 SVG, 1 DXF. A3 sheet with the views, LRA and XYZ tables, sent back through pipe_drawing
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn export_drawing(format: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnExportDrawing(format));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//format 0 SVG, 1 DXF. Unbent tube with the start and end of every bend and the rotation to mark,
+//the table comes back as text next to the drawing through pipe_marking
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub async unsafe fn export_marking(format: i32) {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let _ = console_log::init_with_level(Level::Error);
+    match COMMANDS.lock() {
+        Ok(mut m) => {
+            m.values.push_back(RemoteCommand::OnExportMarking(format));
+        }
+        Err(_e) => { warn!("CANT LOCK COMMANDS MEM") }
+    }
+}
+
+//////////FROM THIS
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_bend_ops(ids: Int32Array);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_bend_ops_wire(ops: Uint8Array);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn change_bend_step(id: i32);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn bend_settings(settings: Float32Array);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn select_by_id(id: i32);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_stp_file(ids: Uint8Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn machine_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_violations(violations: Uint8Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_collision(collision: Uint8Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_optimizer_results(candidates: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn cycle_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_cycle_time(cycle: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_tool_plan(ops: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_deviation(deviation: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn roll_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_roll_instructions(instructions: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn tube_stock_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_bendability(assessments: Uint8Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_project(project: Uint8Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn nesting_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_cut_plan(plan: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn allowance_settings(settings: Float32Array);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_allowance(allowance: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_mesh_file(data: Uint8Array, extension: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_drawing(data: Uint8Array, extension: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_marking(data: Uint8Array, extension: &str, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_edit_state(can_undo: bool, can_redo: bool, error: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_diff(diff: Uint8Array, report: &str);
+}
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_namespace = wvservice)]
+extern "C" {
+    pub fn pipe_simulation(pose: Uint8Array);
+}
@@ -47,7 +47,7 @@ struct LightUniforms {
 };
 @binding(2) @group(0) var<uniform> light_uniformsArray: array<LightUniforms, 140>;
 
-@binding(3) @group(0) var<uniform> vertex_meta_data0 : array<vec4<i32>, 256>;
+@binding(3) @group(0) var<uniform> vertex_meta_data0 : array<vec4<i32>, 258>;
 
 @binding(4) @group(0) var<uniform> dorn_scale : mat4x4<f32>;
 
@@ -0,0 +1,107 @@
+//Little endian tag-length-value blocks, readers skip tags they do not know
+pub struct ChunkWriter {
+    pub buf: Vec<u8>,
+}
+impl ChunkWriter {
+    pub fn new() -> Self {
+        Self { buf: vec![] }
+    }
+    pub fn write_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn write_i32(&mut self, v: i32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn write_f32(&mut self, v: f32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn write_f64(&mut self, v: f64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn write_bytes(&mut self, v: &[u8]) {
+        self.write_u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+    pub fn write_str(&mut self, v: &str) {
+        self.write_bytes(v.as_bytes());
+    }
+    pub fn write_f32vec(&mut self, v: &Vec<f32>) {
+        self.write_u32(v.len() as u32);
+        v.iter().for_each(|x| self.write_f32(*x));
+    }
+    pub fn write_chunk<F: FnOnce(&mut ChunkWriter)>(&mut self, tag: u32, f: F) {
+        let mut inner = ChunkWriter::new();
+        f(&mut inner);
+        self.write_u32(tag);
+        self.write_bytes(&inner.buf);
+    }
+}
+
+pub struct ChunkReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> ChunkReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data: data, pos: 0 }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if (self.pos + n > self.data.len()) {
+            None
+        } else {
+            let ret = &self.data[self.pos..self.pos + n];
+            self.pos = self.pos + n;
+            Some(ret)
+        }
+    }
+    pub fn read_u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    pub fn read_u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+    pub fn read_i32(&mut self) -> Option<i32> {
+        self.take(4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    pub fn read_f32(&mut self) -> Option<f32> {
+        self.take(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    pub fn read_f64(&mut self) -> Option<f64> {
+        self.take(8).map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+    pub fn read_bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+    pub fn read_str(&mut self) -> Option<String> {
+        self.read_bytes().map(|b| String::from_utf8_lossy(b).to_string())
+    }
+    pub fn read_f32vec(&mut self) -> Option<Vec<f32>> {
+        let len = self.read_u32()? as usize;
+        let mut ret: Vec<f32> = Vec::with_capacity(len.min(self.data.len() / 4));
+        for _ in 0..len {
+            ret.push(self.read_f32()?);
+        }
+        Some(ret)
+    }
+    pub fn read_chunk(&mut self) -> Option<(u32, ChunkReader<'a>)> {
+        let tag = self.read_u32()?;
+        let payload = self.read_bytes()?;
+        Some((tag, ChunkReader::new(payload)))
+    }
+}
+
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    data.iter().for_each(|b| {
+        hash = hash ^ (*b as u64);
+        hash = hash.wrapping_mul(0x100000001b3);
+    });
+    hash
+}
@@ -17,6 +17,7 @@ const DORN_ID: u64 = 200;
 const DORN_RADIUS: f64 = 20.0;
 const DORN_HEIGHT: f64 = 20.0;
 const DORN_PARK_POSITION: f64 = 300.0;
+pub const DORN_PARK_SCALE: Vector3<f64> = Vector3::new(DORN_RADIUS, DORN_RADIUS, DORN_HEIGHT);
 #[derive(Unique)]
 pub struct Dorn {
     pub is_scale_dirty: bool,
@@ -50,35 +51,7 @@ impl Dorn {
             contents: bytemuck::cast_slice(mt.as_ref()),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        let ca = MainCircle {
-            id: random(),
-            radius: 1.0,
-            loc: Point3::new(0.0, 0.0, 0.5),
-            dir: P_UP_REVERSE,
-            radius_dir: P_FORWARD,
-            r_gr_id: (round_by_dec(1.0, 5) * DIVIDER) as u64,
-        };
-        let cb = MainCircle {
-            id: random(),
-            radius: 1.0,
-            loc: Point3::new(0.0, 0.0, -0.5),
-            dir: P_UP_REVERSE,
-            radius_dir: P_FORWARD,
-            r_gr_id: (round_by_dec(1.0, 5) * DIVIDER) as u64,
-        };
-        let mut mc = MainCylinder {
-            id: DORN_ID,
-            ca: ca,
-            cb: cb,
-            h: 1.0,
-            r: 1.0,
-            r_gr_id: 0,
-            ca_tor: 0,
-            cb_tor: 0,
-            step_vertex_buffer: StepVertexBuffer::default(),
-            bbx: BoundingBox::default(),
-        };
-        mc.triangulate();
+        let mc = dorn_cylinder();
         let (v_buffer, i_buffer) = mc.step_vertex_buffer.to_buffers(device);
 
         Self {
@@ -145,16 +118,57 @@ impl Dorn {
     }
 
     pub fn dorn_action(&mut self, anim_state: &AnimState, v_up_orign: &Vector3<f64>) {
-        match anim_state.opcode {
-            2 => {
-                self.update_dorn_height(anim_state.lra.pipe_radius * 2.0);
-                self.update_dorn_radius(anim_state.lra.clr - anim_state.lra.pipe_radius);
-                self.update_dorn_position(anim_state.lra.clr,v_up_orign);
-            }
-            _ => {
-                self.set_dorn_park(v_up_orign);
-            }
+        let scale = Vector3::new(self.scale.x[0] as f64, self.scale.y[1] as f64, self.scale.z[2] as f64);
+        let (scale, translation) = dorn_pose(anim_state, v_up_orign, &scale);
+        self.update_dorn_height(scale.z);
+        self.update_dorn_radius(scale.x);
+        self.translate = Matrix4::from_translation(Vector3::<f32>::new(translation.x as f32, translation.y as f32, translation.z as f32));
+        self.is_translate_dirty = true;
+    }
+}
+
+//Unit cylinder along Z centered at the origin, the dorn is this mesh scaled and moved
+pub fn dorn_cylinder() -> MainCylinder {
+    let ca = MainCircle {
+        id: random(),
+        radius: 1.0,
+        loc: Point3::new(0.0, 0.0, 0.5),
+        dir: P_UP_REVERSE,
+        radius_dir: P_FORWARD,
+        r_gr_id: (round_by_dec(1.0, 5) * DIVIDER) as u64,
+    };
+    let cb = MainCircle {
+        id: random(),
+        radius: 1.0,
+        loc: Point3::new(0.0, 0.0, -0.5),
+        dir: P_UP_REVERSE,
+        radius_dir: P_FORWARD,
+        r_gr_id: (round_by_dec(1.0, 5) * DIVIDER) as u64,
+    };
+    let mut mc = MainCylinder {
+        id: DORN_ID,
+        ca: ca,
+        cb: cb,
+        h: 1.0,
+        r: 1.0,
+        r_gr_id: 0,
+        ca_tor: 0,
+        cb_tor: 0,
+        step_vertex_buffer: StepVertexBuffer::default(),
+        bbx: BoundingBox::default(),
+    };
+    mc.triangulate();
+    mc
+}
+
+//Scale and translation of the dorn for an animation stage, the scale is kept while the dorn is parked
+pub fn dorn_pose(anim_state: &AnimState, v_up_orign: &Vector3<f64>, scale: &Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+    match anim_state.opcode {
+        2 => {
+            let r = anim_state.lra.clr - anim_state.lra.pipe_radius;
+            (Vector3::new(r, r, anim_state.lra.pipe_radius * 2.0), P_RIGHT.mul(anim_state.lra.clr * v_up_orign.z))
         }
+        _ => (scale.clone(), P_RIGHT.mul(DORN_PARK_POSITION * v_up_orign.z)),
     }
 }
 unsafe impl Send for Dorn {}
@@ -1,3 +1,5 @@
 pub mod materials;
 pub mod dorn;
-pub mod dim;
\ No newline at end of file
+pub mod dim;
+pub mod chunk;
+pub mod wire;
@@ -0,0 +1,181 @@
+use crate::utils::chunk::{ChunkReader, ChunkWriter};
+use std::fmt::{Display, Formatter};
+
+//Messages exchanged with the JS side. A message is
+//magic, version, kind, then tagged blocks: one header record and any number of rows.
+//A record is a list of (field id, f64) pairs, readers skip blocks and fields they do not know,
+//so new fields can be appended without a version change. The version only grows on breaking changes
+pub const WIRE_MAGIC: [u8; 4] = *b"FTWB";
+pub const WIRE_VERSION: u32 = 1;
+
+const TAG_HEADER: u32 = 1;
+const TAG_ROW: u32 = 2;
+
+//Program row fields, ids are never reused
+pub const P_ID1: u32 = 1;
+pub const P_ID2: u32 = 2;
+pub const P_L: u32 = 3;
+pub const P_LT: u32 = 4;
+pub const P_R: u32 = 5;
+pub const P_A: u32 = 6;
+pub const P_CLR: u32 = 7;
+pub const P_PIPE_RADIUS: u32 = 8;
+pub const P_WALL: u32 = 9;
+pub const P_TOOL_STACK: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireKind {
+    Unknown = 0,
+    Program = 1,
+    Violations = 2,
+    Collision = 3,
+    Candidates = 4,
+    Cycle = 5,
+    ToolPlan = 6,
+    Deviation = 7,
+    RollInstructions = 8,
+    Bendability = 9,
+    CutPlan = 10,
+    Allowance = 11,
+    Diff = 12,
+    Simulation = 13,
+}
+impl WireKind {
+    pub fn from_u32(v: u32) -> WireKind {
+        match v {
+            1 => WireKind::Program,
+            2 => WireKind::Violations,
+            3 => WireKind::Collision,
+            4 => WireKind::Candidates,
+            5 => WireKind::Cycle,
+            6 => WireKind::ToolPlan,
+            7 => WireKind::Deviation,
+            8 => WireKind::RollInstructions,
+            9 => WireKind::Bendability,
+            10 => WireKind::CutPlan,
+            11 => WireKind::Allowance,
+            12 => WireKind::Diff,
+            13 => WireKind::Simulation,
+            _ => WireKind::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    UnexpectedKind(u32),
+    Truncated,
+}
+impl Display for WireError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireError::BadMagic => write!(f, "not a wire message"),
+            WireError::UnsupportedVersion(v) => write!(f, "wire version {} is newer than {}", v, WIRE_VERSION),
+            WireError::UnexpectedKind(k) => write!(f, "unexpected message kind {}", k),
+            WireError::Truncated => write!(f, "wire message is truncated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WireRecord {
+    pub fields: Vec<(u32, f64)>,
+}
+impl WireRecord {
+    pub fn new() -> Self {
+        Self { fields: vec![] }
+    }
+    pub fn push(&mut self, id: u32, v: f64) {
+        self.fields.push((id, v));
+    }
+    pub fn push_bool(&mut self, id: u32, v: bool) {
+        self.fields.push((id, if (v) { 1.0 } else { 0.0 }));
+    }
+    //Replaces the field when it is already present
+    pub fn set(&mut self, id: u32, v: f64) {
+        match self.fields.iter_mut().find(|(fid, _)| *fid == id) {
+            None => self.fields.push((id, v)),
+            Some(field) => field.1 = v,
+        }
+    }
+    pub fn get(&self, id: u32) -> Option<f64> {
+        self.fields.iter().find(|(fid, _)| *fid == id).map(|(_, v)| *v)
+    }
+    pub fn get_or(&self, id: u32, default: f64) -> f64 {
+        self.get(id).unwrap_or(default)
+    }
+    fn write(&self, w: &mut ChunkWriter) {
+        self.fields.iter().for_each(|(id, v)| {
+            w.write_u32(*id);
+            w.write_f64(*v);
+        });
+    }
+    fn read(r: &mut ChunkReader) -> Option<WireRecord> {
+        let mut ret = WireRecord::new();
+        while (!r.is_empty()) {
+            let id = r.read_u32()?;
+            let v = r.read_f64()?;
+            ret.fields.push((id, v));
+        }
+        Some(ret)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WireMessage {
+    pub kind: WireKind,
+    pub header: WireRecord,
+    pub rows: Vec<WireRecord>,
+}
+impl WireMessage {
+    pub fn new(kind: WireKind) -> Self {
+        Self {
+            kind: kind,
+            header: WireRecord::new(),
+            rows: vec![],
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = ChunkWriter::new();
+        w.buf.extend_from_slice(&WIRE_MAGIC);
+        w.write_u32(WIRE_VERSION);
+        w.write_u32(self.kind as u32);
+        if (!self.header.fields.is_empty()) {
+            w.write_chunk(TAG_HEADER, |c| self.header.write(c));
+        }
+        self.rows.iter().for_each(|row| {
+            w.write_chunk(TAG_ROW, |c| row.write(c));
+        });
+        w.buf
+    }
+    pub fn from_bytes(data: &[u8]) -> Result<WireMessage, WireError> {
+        if (data.len() < 12 || data[0..4] != WIRE_MAGIC) {
+            return Err(WireError::BadMagic);
+        }
+        let mut r = ChunkReader::new(&data[4..]);
+        let version = r.read_u32().ok_or(WireError::Truncated)?;
+        if (version > WIRE_VERSION) {
+            return Err(WireError::UnsupportedVersion(version));
+        }
+        let kind = r.read_u32().ok_or(WireError::Truncated)?;
+        let mut ret = WireMessage::new(WireKind::from_u32(kind));
+        while (!r.is_empty()) {
+            let (tag, mut c) = r.read_chunk().ok_or(WireError::Truncated)?;
+            match tag {
+                TAG_HEADER => ret.header = WireRecord::read(&mut c).ok_or(WireError::Truncated)?,
+                TAG_ROW => ret.rows.push(WireRecord::read(&mut c).ok_or(WireError::Truncated)?),
+                _ => {}
+            }
+        }
+        Ok(ret)
+    }
+    pub fn expect(self, kind: WireKind) -> Result<WireMessage, WireError> {
+        if (self.kind == kind) {
+            Ok(self)
+        } else {
+            Err(WireError::UnexpectedKind(self.kind as u32))
+        }
+    }
+}